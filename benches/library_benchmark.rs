@@ -8,8 +8,9 @@ use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 use ripasso::pass;
+use ripasso::cache::MetadataCache;
 
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 
 fn unpack_tar_gz(mut base_path: PathBuf, tar_gz_name: &str) -> Result<(), std::io::Error> {
     let target = format!("{}", base_path.as_path().display());
@@ -34,6 +35,10 @@ fn cleanup(mut base_path: PathBuf, path_name: &str) -> Result<(), std::io::Error
 }
 
 fn pop_list(password_dir: PathBuf) -> () {
+    pop_list_expecting(password_dir, 4);
+}
+
+fn pop_list_expecting(password_dir: PathBuf, expected: usize) -> () {
     let password_store_dir = Arc::new(Some(format!("{}", password_dir.as_path().display())));
 
     let results = Arc::new(Mutex::new(Vec::<pass::PasswordEntry>::new()));
@@ -41,7 +46,7 @@ fn pop_list(password_dir: PathBuf) -> () {
 
     pass::populate_password_list(&results, repo_opt, password_store_dir).unwrap();
 
-    assert_eq!((*(*results).lock().unwrap()).len(), 4);
+    assert_eq!((*(*results).lock().unwrap()).len(), expected);
 }
 
 fn criterion_benchmark_load_4_passwords(c: &mut Criterion) {
@@ -62,5 +67,94 @@ fn criterion_benchmark_load_4_passwords(c: &mut Criterion) {
     cleanup(base_path, "populate_password_list_large_repo").unwrap();
 }
 
-criterion_group!(benches, criterion_benchmark_load_4_passwords);
+/// Creates a signing key without a passphrase in a throwaway keyring, and points gpg at that
+/// keyring so that the benchmarks verify the signatures like they would in a real store.
+fn signing_key() -> (tempfile::TempDir, String) {
+    let gnupg_home = tempfile::tempdir().unwrap();
+    std::env::set_var("GNUPGHOME", gnupg_home.path());
+
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp).unwrap();
+    let result = ctx.create_key_with_flags("Bench <bench@example.com>", "default", std::time::Duration::from_secs(0),
+                                           gpgme::CreateKeyFlags::NOPASSWD | gpgme::CreateKeyFlags::SIGN).unwrap();
+    let fingerprint = result.fingerprint().unwrap().to_string();
+
+    (gnupg_home, fingerprint)
+}
+
+fn commit_all(repo: &git2::Repository, signing_key: &str, message: &str) {
+    let mut index = repo.index().unwrap();
+    index.add_all(&["*"], git2::IndexAddOption::DEFAULT, None).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("Bench", "bench@example.com").unwrap();
+
+    let parents = match repo.head() {
+        Ok(head) => vec![repo.find_commit(head.target().unwrap()).unwrap()],
+        Err(_) => vec![],
+    };
+    let parents: Vec<&git2::Commit> = parents.iter().collect();
+
+    let commit_buf = repo.commit_create_buffer(&signature, &signature, message, &tree, &parents).unwrap();
+    let commit_as_str = std::str::from_utf8(&commit_buf).unwrap();
+
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp).unwrap();
+    ctx.set_armor(true);
+    let key = ctx.get_secret_key(signing_key).unwrap();
+    ctx.add_signer(&key).unwrap();
+    let mut gpg_signature = Vec::new();
+    ctx.sign_detached(commit_as_str, &mut gpg_signature).unwrap();
+
+    let oid = repo.commit_signed(commit_as_str, std::str::from_utf8(&gpg_signature).unwrap(), Some("gpgsig")).unwrap();
+
+    // commit_signed doesn't move the branch, HEAD points to it even before the first commit
+    let branch = repo.find_reference("HEAD").unwrap().symbolic_target().unwrap().to_string();
+    repo.reference(&branch, oid, true, message).unwrap();
+}
+
+/// Creates a store with `passwords` files and a history of `commits` signed commits, that each
+/// change one of the files.
+fn synthetic_store(passwords: usize, commits: usize, signing_key: &str) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+
+    for i in 0..passwords {
+        std::fs::write(dir.path().join(format!("{}.gpg", i)), format!("initial {}", i)).unwrap();
+    }
+    commit_all(&repo, signing_key, "initial");
+
+    for i in 0..commits {
+        std::fs::write(dir.path().join(format!("{}.gpg", i % passwords)), format!("change {}", i)).unwrap();
+        commit_all(&repo, signing_key, &format!("change {}", i));
+    }
+
+    dir
+}
+
+fn criterion_benchmark_large_history(c: &mut Criterion) {
+    let (passwords, commits) = (500, 2000);
+    let (_gnupg_home, signing_key) = signing_key();
+    let store = synthetic_store(passwords, commits, &signing_key);
+    let password_dir = store.path().to_path_buf();
+    let repo = git2::Repository::open(&password_dir).unwrap();
+    let cache_file = MetadataCache::path_for(&repo);
+
+    c.bench_function("populate_password_list 500 passwords 2000 commits without cache", |b| b.iter_batched(
+        || { let _ = std::fs::remove_file(&cache_file); },
+        |_| pop_list_expecting(password_dir.clone(), passwords),
+        BatchSize::PerIteration));
+
+    pop_list_expecting(password_dir.clone(), passwords);
+    c.bench_function("populate_password_list 500 passwords 2000 commits with cache", |b| b.iter(|| pop_list_expecting(password_dir.clone(), passwords)));
+
+    // a cache that is one commit behind, like after a pull
+    let behind = std::fs::read(&cache_file).unwrap();
+    std::fs::write(password_dir.join("0.gpg"), "one more change").unwrap();
+    commit_all(&repo, &signing_key, "one more change");
+    c.bench_function("populate_password_list 500 passwords 2000 commits with cache one commit behind", |b| b.iter_batched(
+        || std::fs::write(&cache_file, &behind).unwrap(),
+        |_| pop_list_expecting(password_dir.clone(), passwords),
+        BatchSize::PerIteration));
+}
+
+criterion_group!(benches, criterion_benchmark_load_4_passwords, criterion_benchmark_large_history);
 criterion_main!(benches);
\ No newline at end of file
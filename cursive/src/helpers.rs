@@ -28,8 +28,42 @@ use self::cursive::event::{Key};
 
 use ripasso::pass;
 
+/// Returns a translated message for the error, with the paths and key ids it carries.
+pub fn error_message(err: &pass::Error) -> String {
+    let catalog = &super::CATALOG;
+    match err {
+        pass::Error::StoreNotFound { path } => format!("{} {}", catalog.gettext("The password store doesn't exist:"), path.display()),
+        pass::Error::FolderNotFound { path } => format!("{} {}", catalog.gettext("The folder doesn't exist in the password store:"), path.display()),
+        pass::Error::KeyNotFound { key_id } => format!("{} {}", catalog.gettext("Can't find the key in the keyring, please import it first:"), key_id),
+        pass::Error::KeyCannotEncrypt { key_id } => format!("{} {}", catalog.gettext("The key can't be used for encryption, it might be expired or revoked:"), key_id),
+        pass::Error::AmbiguousKey { key_id, matches } => format!("{} {} ({})", catalog.gettext("The key id matches several keys:"), key_id, matches),
+        pass::Error::RecipientsNotFound { path } => format!("{} {}", catalog.gettext("No .gpg-id file found for:"), path.display()),
+        pass::Error::EntryExists { path } => format!("{} {}", catalog.gettext("The entry already exists:"), path.display()),
+        pass::Error::PathEscape { path } => format!("{} {}", catalog.gettext("The path is outside of the password store:"), path.display()),
+        pass::Error::MergeConflict { paths } => {
+            let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+            format!("{} {}", catalog.gettext("The changes from the remote conflict with local changes in:"), paths.join(", "))
+        },
+        pass::Error::NoRemote { name } => format!("{} {}", catalog.gettext("The git repository has no remote called:"), name),
+        pass::Error::PolicyViolation { violations } => {
+            let descriptions: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+            format!("{} {}", catalog.gettext("The password breaks the folder policy:"), descriptions.join(", "))
+        },
+        pass::Error::RepositoryBusy => catalog.gettext("The git repository is busy, please try again").to_string(),
+        pass::Error::NotARecipient { key_id } => format!("{} {}", catalog.gettext("Team member isn't in the list of key ids:"), key_id),
+        pass::Error::AlreadyARecipient { key_id } => format!("{} {}", catalog.gettext("Team member is already in the list of key ids:"), key_id),
+        pass::Error::LastRecipient { path } => format!("{} {}", catalog.gettext("Can't delete the last encryption key in:"), path.display()),
+        pass::Error::NoUsableKeys { path } => format!("{} {}", catalog.gettext("None of the keys are in the keyring, in:"), path.display()),
+        pass::Error::SecretKeyUnavailable { key_id } => format!("{} {}", catalog.gettext("The key can't decrypt, is the secret key or smartcard available?"), key_id),
+        pass::Error::NotEncryptedFor { name, key_id } => format!("{} {} ({})", catalog.gettext("The password wasn't encrypted for the key:"), name, key_id),
+        pass::Error::NoKeysInFile { path } => format!("{} {}", catalog.gettext("No public keys found in the key file:"), path.display()),
+        pass::Error::SeveralKeysInFile { path, keys } => format!("{} {} ({})", catalog.gettext("The key file should contain one public key, but it contains several:"), path.display(), keys),
+        _ => err.to_string(),
+    }
+}
+
 pub fn errorbox(ui: &mut Cursive, err: &pass::Error) -> () {
-    let d = Dialog::around(TextView::new(format!("{:?}", err)))
+    let d = Dialog::around(TextView::new(error_message(err)))
         .dismiss_button(super::CATALOG.gettext("Ok"))
         .title(super::CATALOG.gettext("Error"));
 
@@ -21,29 +21,40 @@ extern crate ripasso;
 
 use self::cursive::traits::*;
 use self::cursive::views::{
-    Dialog, EditView, LinearLayout, OnEventView, SelectView, TextArea, TextView, CircularFocus,
+    Checkbox, Dialog, EditView, LinearLayout, OnEventView, SelectView, TextArea, TextView, CircularFocus,
 };
 
 use cursive::Cursive;
 use cursive::menu::MenuTree;
 
 use self::cursive::direction::Orientation;
-use self::cursive::event::{Event, Key};
-
-extern crate clipboard;
-use self::clipboard::{ClipboardContext, ClipboardProvider};
+use self::cursive::event::{Event, EventResult, EventTrigger, Key};
 
 use ripasso::pass;
 use ripasso::pass::SignatureStatus;
 use ripasso::pass::GitRepo;
+use ripasso::secret::SecretString;
+use ripasso::generator;
+use ripasso::words;
+use ripasso::strength;
+use ripasso::policy;
+use ripasso::audit;
+use ripasso::breach;
+use ripasso::import;
+use ripasso::export;
+use ripasso::share;
+use ripasso::backup;
+use ripasso::clipboard;
 
 use std::process;
 use std::{thread, time};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use unic_langid::LanguageIdentifier;
 
 mod helpers;
+mod osc52;
 mod wizard;
 
 #[macro_use]
@@ -51,6 +62,8 @@ extern crate lazy_static;
 
 lazy_static! {
     static ref CATALOG: gettext::Catalog = get_translation_catalog();
+    static ref CLIPBOARD_BACKEND: clipboard::BackendFactory = osc52::clipboard_from_env();
+    static ref CLIPBOARD: clipboard::ClipboardService = clipboard::ClipboardService::new(CLIPBOARD_BACKEND.clone(), clipboard::ClipboardOptions::default());
 }
 
 fn down(ui: &mut Cursive) -> () {
@@ -91,22 +104,38 @@ fn copy(ui: &mut Cursive) -> () {
         return;
     }
 
-    let ctx_res = clipboard::ClipboardContext::new();
-    if ctx_res.is_err() {
-        helpers::errorbox(ui, &pass::Error::GenericDyn(format!("{}", &ctx_res.err().unwrap())));
-        return;
-    }
-    let mut ctx: ClipboardContext = ctx_res.unwrap();
-    ctx.set_contents(password.unwrap().to_owned()).unwrap();
-
-    thread::spawn(|| {
-        thread::sleep(time::Duration::from_secs(40));
-        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-        ctx.set_contents("".to_string()).unwrap();
-    });
+    let events = match CLIPBOARD.copy(&password.unwrap()) {
+        Ok(events) => events,
+        Err(err) => {
+            helpers::errorbox(ui, &err);
+            return;
+        }
+    };
 
-    ui.call_on_id("status_bar", |l: &mut TextView| {
-        l.set_content(CATALOG.gettext("Copied password to copy buffer for 40 seconds"));
+    // show the countdown in the status bar, until the password is removed or replaced
+    let cb_sink = ui.cb_sink().clone();
+    thread::spawn(move || {
+        for event in events {
+            let status = match event {
+                clipboard::ClipboardEvent::Countdown(remaining) => format!("{} {}", CATALOG.gettext("Copied password to copy buffer, seconds left:"), (remaining.as_millis() as f64 / 1000.0).ceil()),
+                clipboard::ClipboardEvent::Cleared => CATALOG.gettext("Removed the password from the copy buffer").to_string(),
+                clipboard::ClipboardEvent::Restored => CATALOG.gettext("Removed the password from the copy buffer and restored its previous content").to_string(),
+                clipboard::ClipboardEvent::Replaced => CATALOG.gettext("The copy buffer has been changed, left it as it is").to_string(),
+                clipboard::ClipboardEvent::Superseded => return,
+                clipboard::ClipboardEvent::Error(err) => {
+                    let _ = cb_sink.send(Box::new(move |s: &mut Cursive| helpers::errorbox(s, &err)));
+                    return;
+                },
+            };
+            let res = cb_sink.send(Box::new(move |s: &mut Cursive| {
+                s.call_on_id("status_bar", |l: &mut TextView| {
+                    l.set_content(status);
+                });
+            }));
+            if res.is_err() {
+                return;
+            }
+        }
     });
 }
 
@@ -161,27 +190,48 @@ fn open(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Option<Stri
         None => return
     })).clone();
 
+    open_entry(ui, password_entry, repo_opt, password_store_dir);
+}
+
+fn open_entry(ui: &mut Cursive, password_entry: pass::PasswordEntry, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
     let password = match password_entry.secret() {
         Ok(p) => p,
         Err(_e) => return
     };
+    let meter = strength_meter_text(password.lines().next().unwrap_or(""));
+    let editbox = OnEventView::new(TextArea::new().content(password.expose_secret()).with_id("editbox"))
+        .on_pre_event_inner(EventTrigger::any(), |v, e| {
+            let res = v.on_event(e.clone());
+            Some(res.and(EventResult::with_cb(|s| {
+                let content = s.call_on_id("editbox", |e: &mut TextArea| e.get_content().to_string()).unwrap_or_default();
+                update_strength_meter(s, content.lines().next().unwrap_or(""));
+            })))
+        });
+    let fields = LinearLayout::vertical()
+        .child(editbox)
+        .child(TextView::new(meter).with_id("strength_meter"));
+
+    let password_entry2 = password_entry.clone();
+    let password_store_dir2 = password_store_dir.clone();
+
     let d =
-        Dialog::around(TextArea::new().content(password).with_id("editbox"))
+        Dialog::around(fields)
             .button(CATALOG.gettext("Save"), move |s| {
                 let new_password = s
                     .call_on_id("editbox", |e: &mut TextArea| {
                         e.get_content().to_string()
                     }).unwrap();
-                let r = password_entry.update(new_password, repo_opt.clone(), password_store_dir.clone());
-                if r.is_err() {
-                    helpers::errorbox(s, &r.unwrap_err())
+                let r = password_entry.update(SecretString::new(new_password), repo_opt.clone(), password_store_dir.clone());
+                match r {
+                    Ok(violations) => show_policy_warnings(s, &violations),
+                    Err(err) => helpers::errorbox(s, &err),
                 }
             })
             .button(CATALOG.gettext("Generate"), move |s| {
-                let new_password = ripasso::words::generate_password(6);
-                s.call_on_id("editbox", |e: &mut TextArea| {
-                    e.set_content(new_password);
-                });
+                match password_entry2.policy(password_store_dir2.clone()) {
+                    Ok(policy) => generator_dialog(s, "editbox", policy),
+                    Err(err) => helpers::errorbox(s, &err),
+                }
             })
             .dismiss_button(CATALOG.gettext("Ok"));
 
@@ -193,6 +243,191 @@ fn open(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Option<Stri
     ui.add_layer(ev);
 }
 
+fn show_policy_warnings(s: &mut Cursive, violations: &Vec<policy::Violation>) -> () {
+    if violations.is_empty() {
+        return;
+    }
+
+    let descriptions: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+    let text = format!("{} {}", CATALOG.gettext("Saved, but the password breaks the folder policy:"), descriptions.join(", "));
+    s.call_on_id("status_bar", |l: &mut TextView| {
+        l.set_content(text);
+    });
+}
+
+fn strength_meter_text(password: &str) -> String {
+    if password.is_empty() {
+        return format!("{} [    ]", CATALOG.gettext("Strength:"));
+    }
+
+    let strength = strength::estimate(password);
+    let bar: String = (0..4).map(|i| if i < strength.score { '#' } else { ' ' }).collect();
+
+    return format!("{} [{}] {}, {} {}",
+                   CATALOG.gettext("Strength:"),
+                   bar,
+                   CATALOG.gettext(strength.score_display()),
+                   strength.crack_time_display(strength::Attack::OfflineSlowHash),
+                   CATALOG.gettext("to crack offline"));
+}
+
+fn update_strength_meter(s: &mut Cursive, password: &str) -> () {
+    let text = strength_meter_text(password);
+    s.call_on_id("strength_meter", |l: &mut TextView| {
+        l.set_content(text);
+    });
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum GeneratorKind {
+    Characters,
+    Pronounceable,
+    Words,
+}
+
+fn is_checked(s: &mut Cursive, id: &str) -> bool {
+    s.call_on_id(id, |c: &mut Checkbox| c.is_checked()).unwrap_or(false)
+}
+
+fn generate_from_dialog(s: &mut Cursive, target: &'static str) -> () {
+    let length = get_value_from_input(s, "generator_length_input")
+        .and_then(|l| l.parse::<usize>().ok());
+    let length = match length {
+        Some(l) => l,
+        None => {
+            helpers::errorbox(s, &pass::Error::Generic("the length must be a number"));
+            return;
+        }
+    };
+
+    let kind = s.call_on_id("generator_kind", |l: &mut SelectView<GeneratorKind>| {
+        l.selection().map(|k| *k)
+    }).unwrap_or(None).unwrap_or(GeneratorKind::Characters);
+
+    let password = match kind {
+        GeneratorKind::Words => {
+            let list = s.call_on_id("generator_word_list", |l: &mut SelectView<words::WordList>| {
+                l.selection().map(|l| (*l).clone())
+            }).unwrap_or(None).unwrap_or_else(words::WordList::eff_large);
+
+            let options = words::PassphraseOptions {
+                words: length,
+                list,
+                separator: (*get_value_from_input(s, "generator_separator_input").unwrap()).clone(),
+                capitalisation: if is_checked(s, "generator_upper") { words::Capitalisation::First } else { words::Capitalisation::None },
+                digit: is_checked(s, "generator_digits"),
+                symbol: is_checked(s, "generator_symbols"),
+            };
+            words::generate_passphrase(&options).map(|p| {
+                s.call_on_id("status_bar", |l: &mut TextView| {
+                    l.set_content(format!("{} {:.0}", CATALOG.gettext("Passphrase entropy in bits:"), p.entropy));
+                });
+                p.passphrase
+            })
+        },
+        _ => {
+            let options = generator::GeneratorOptions {
+                length,
+                upper: is_checked(s, "generator_upper"),
+                lower: is_checked(s, "generator_lower"),
+                digits: is_checked(s, "generator_digits"),
+                symbols: is_checked(s, "generator_symbols"),
+                exclude_ambiguous: is_checked(s, "generator_exclude_ambiguous"),
+                at_least_one_of_each: is_checked(s, "generator_at_least_one"),
+                mode: if kind == GeneratorKind::Pronounceable { generator::Mode::Pronounceable } else { generator::Mode::Characters },
+            };
+            generator::generate(&options)
+        }
+    };
+
+    match password {
+        Ok(password) => {
+            s.call_on_id(target, |e: &mut EditView| {
+                e.set_content(password.expose_secret());
+            });
+            s.call_on_id(target, |e: &mut TextArea| {
+                e.set_content(password.expose_secret());
+            });
+            s.pop_layer();
+            update_strength_meter(s, &password);
+        },
+        Err(err) => helpers::errorbox(s, &err),
+    }
+}
+
+fn labeled_checkbox(label: &str, id: &str, checked: bool) -> LinearLayout {
+    let mut checkbox = Checkbox::new();
+    checkbox.set_checked(checked);
+    LinearLayout::horizontal()
+        .child(checkbox.with_id(id))
+        .child(TextView::new(format!(" {}", label)))
+}
+
+/// Shows the options for generating a new password, and puts the generated password into the
+/// EditView or TextArea with the id `target`. The options start out as the folder policy says.
+fn generator_dialog(ui: &mut Cursive, target: &'static str, policy: Option<policy::Policy>) -> () {
+    let defaults = match policy {
+        Some(policy) => policy.generator,
+        None => generator::GeneratorOptions::default(),
+    };
+
+    let mut kind = SelectView::<GeneratorKind>::new().popup();
+    kind.add_item(CATALOG.gettext("Characters"), GeneratorKind::Characters);
+    kind.add_item(CATALOG.gettext("Pronounceable"), GeneratorKind::Pronounceable);
+    kind.add_item(CATALOG.gettext("Words"), GeneratorKind::Words);
+    if defaults.mode == generator::Mode::Pronounceable {
+        kind.set_selection(1);
+    }
+
+    let mut length_fields = LinearLayout::horizontal();
+    length_fields.add_child(TextView::new(CATALOG.gettext("Length: "))
+        .fixed_size((10, 1)));
+    length_fields.add_child(EditView::new()
+        .content(format!("{}", defaults.length))
+        .with_id("generator_length_input")
+        .fixed_size((10, 1)));
+
+    let mut word_list = SelectView::<words::WordList>::new().popup();
+    for list in words::WordList::available() {
+        word_list.add_item(list.name.clone(), list);
+    }
+
+    let mut word_fields = LinearLayout::horizontal();
+    word_fields.add_child(TextView::new(CATALOG.gettext("Separator: "))
+        .fixed_size((10, 1)));
+    word_fields.add_child(EditView::new()
+        .content(" ")
+        .with_id("generator_separator_input")
+        .fixed_size((10, 1)));
+    word_fields.add_child(word_list.with_id("generator_word_list"));
+
+    let fields = LinearLayout::vertical()
+        .child(kind.with_id("generator_kind"))
+        .child(length_fields)
+        .child(TextView::new(CATALOG.gettext("For words, the length is the number of words")))
+        .child(word_fields)
+        .child(labeled_checkbox(CATALOG.gettext("Upper case letters"), "generator_upper", defaults.upper))
+        .child(labeled_checkbox(CATALOG.gettext("Lower case letters"), "generator_lower", defaults.lower))
+        .child(labeled_checkbox(CATALOG.gettext("Digits"), "generator_digits", defaults.digits))
+        .child(labeled_checkbox(CATALOG.gettext("Symbols"), "generator_symbols", defaults.symbols))
+        .child(labeled_checkbox(CATALOG.gettext("Exclude ambiguous characters"), "generator_exclude_ambiguous", defaults.exclude_ambiguous))
+        .child(labeled_checkbox(CATALOG.gettext("At least one of each"), "generator_at_least_one", defaults.at_least_one_of_each));
+
+    let d = Dialog::around(fields)
+        .title(CATALOG.gettext("Generate password"))
+        .button(CATALOG.gettext("Generate"), move |s| {
+            generate_from_dialog(s, target);
+        })
+        .dismiss_button(CATALOG.gettext("Cancel"));
+
+    let ev = OnEventView::new(d)
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        });
+
+    ui.add_layer(ev);
+}
+
 fn get_value_from_input(s: &mut Cursive, input_name: &str) -> Option<std::rc::Rc<String>> {
     let mut password= None;
     s.call_on_id(input_name, |e: &mut EditView| {
@@ -220,7 +455,7 @@ fn create_save(s: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Optio
         return;
     }
 
-    let res = pass::new_password_file(path.clone(), password, repo_opt.clone(), password_store_dir.clone());
+    let res = pass::new_password_file(path.clone(), SecretString::new((*password).clone()), repo_opt.clone(), password_store_dir.clone());
 
     let col = s.screen_size().x;
     if res.is_err() {
@@ -251,6 +486,7 @@ fn create_save(s: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Optio
         s.call_on_id("status_bar", |l: &mut TextView| {
             l.set_content(CATALOG.gettext("Created new password"));
         });
+        show_policy_warnings(s, &res.unwrap());
     }
 }
 
@@ -269,22 +505,28 @@ fn create(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Option<St
         .fixed_size((10, 1)));
     password_fields.add_child(EditView::new()
         .secret()
+        .on_edit(|s, content, _cursor| update_strength_meter(s, content))
         .with_id("new_password_input")
         .fixed_size((50, 1)));
     fields.add_child(path_fields);
     fields.add_child(password_fields);
+    fields.add_child(TextView::new(strength_meter_text("")).with_id("strength_meter"));
 
     let repo_opt2 = repo_opt.clone();
     let password_store_dir2 = password_store_dir.clone();
+    let password_store_dir3 = password_store_dir.clone();
 
     let d =
         Dialog::around(fields)
             .title(CATALOG.gettext("Add new password"))
             .button(CATALOG.gettext("Generate"), move |s| {
-                let new_password = ripasso::words::generate_password(6);
-                s.call_on_id("new_password_input", |e: &mut EditView| {
-                    e.set_content(new_password);
-                });
+                let path = get_value_from_input(s, "new_path_input").unwrap();
+                let policy = pass::password_dir(password_store_dir3.clone())
+                    .and_then(|dir| policy::Policy::for_name(&dir, &path));
+                match policy {
+                    Ok(policy) => generator_dialog(s, "new_password_input", policy),
+                    Err(err) => helpers::errorbox(s, &err),
+                }
             })
             .button(CATALOG.gettext("Save"), move |ui: &mut Cursive| {
                 create_save(ui, repo_opt.clone(), password_store_dir.clone())
@@ -333,7 +575,88 @@ fn delete_recipient_verification(ui: &mut Cursive, repo_opt: GitRepo, password_s
             .dismiss_button(CATALOG.gettext("Cancel"))));
 }
 
+fn fingerprints_text(recipients: &Vec<pass::Recipient>) -> String {
+    return recipients.iter()
+        .map(|r| format!("{} {}\n", r.fingerprint.clone().unwrap_or(r.key_id.clone()), r.name))
+        .collect();
+}
+
+fn add_recipient_from_key_file(ui: &mut Cursive, key_file: &str, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let key_file = std::path::PathBuf::from(key_file);
+    let recipients_res = pass::Recipient::from_key_file(&key_file);
+    if recipients_res.is_err() {
+        helpers::errorbox(ui, &recipients_res.unwrap_err());
+        return;
+    }
+    let recipients = recipients_res.unwrap();
+
+    let d = Dialog::around(TextView::new(format!("{}\n\n{}",
+                                                 CATALOG.gettext("The key file contains these keys, please verify the fingerprints before adding them to the team:"),
+                                                 fingerprints_text(&recipients))))
+        .title(CATALOG.gettext("Verify Fingerprint"))
+        .button(CATALOG.gettext("Add"), move |ui: &mut Cursive| {
+            for recipient in &recipients {
+                let fingerprint = recipient.fingerprint.clone().unwrap_or(recipient.key_id.clone());
+                let res = pass::Recipient::import_from_key_file(&key_file, &fingerprint)
+                    .and_then(|imported| pass::Recipient::add_recipient_to_file(&imported, repo_opt.clone(), password_store_dir.clone()));
+                if res.is_err() {
+                    helpers::errorbox(ui, &res.unwrap_err());
+                    return;
+                }
+            }
+            ui.pop_layer();
+            ui.pop_layer();
+            ui.call_on_id("status_bar", |l: &mut TextView| {
+                l.set_content(CATALOG.gettext("Added team member to password store"));
+            });
+        })
+        .dismiss_button(CATALOG.gettext("Cancel"));
+
+    ui.add_layer(d);
+}
+
+fn import_missing_public_keys(ui: &mut Cursive, password_store_dir: Arc<Option<String>>) -> () {
+    let missing_res = pass::missing_public_keys(password_store_dir);
+    if missing_res.is_err() {
+        helpers::errorbox(ui, &missing_res.unwrap_err());
+        return;
+    }
+
+    for missing in missing_res.unwrap() {
+        let recipient_res = missing.read_key();
+        if recipient_res.is_err() {
+            helpers::errorbox(ui, &recipient_res.unwrap_err());
+            continue;
+        }
+        let recipient = recipient_res.unwrap();
+
+        let d = Dialog::around(TextView::new(format!("{} {}\n\n{}\n{}",
+                                                     CATALOG.gettext("The password store has a public key for"),
+                                                     missing.key_id,
+                                                     fingerprints_text(&vec![recipient.clone()]),
+                                                     CATALOG.gettext("Do you trust this key?"))))
+            .title(CATALOG.gettext("Verify Fingerprint"))
+            .button(CATALOG.gettext("Yes"), move |ui: &mut Cursive| {
+                let fingerprint = recipient.fingerprint.clone().unwrap_or(recipient.key_id.clone());
+                let res = pass::Recipient::import_from_key_file(&missing.path, &fingerprint);
+                ui.pop_layer();
+                if res.is_err() {
+                    helpers::errorbox(ui, &res.unwrap_err());
+                }
+            })
+            .dismiss_button(CATALOG.gettext("No"));
+
+        ui.add_layer(d);
+    }
+}
+
 fn add_recipient(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let key_file = &*get_value_from_input(ui, "key_file_input").unwrap();
+    if !key_file.is_empty() {
+        add_recipient_from_key_file(ui, key_file, repo_opt, password_store_dir);
+        return;
+    }
+
     let l = &*get_value_from_input(ui, "key_id_input").unwrap();
 
     let recipient_result = pass::Recipient::new(l.clone());
@@ -354,14 +677,21 @@ fn add_recipient(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Op
 }
 
 fn add_recipient_dialog(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
-    let mut recipient_fields = LinearLayout::horizontal();
+    let mut recipient_fields = LinearLayout::vertical();
+    let mut key_id_fields = LinearLayout::horizontal();
+    let mut key_file_fields = LinearLayout::horizontal();
 
-    recipient_fields.add_child(TextView::new(CATALOG.gettext("GPG Key ID: "))
+    key_id_fields.add_child(TextView::new(CATALOG.gettext("GPG Key ID: "))
         .with_id("key_id")
         .fixed_size((16, 1)));
+    key_file_fields.add_child(TextView::new(CATALOG.gettext("or Key File: "))
+        .with_id("key_file")
+        .fixed_size((16, 1)));
 
     let repo_opt2 = repo_opt.clone();
     let password_store_dir2 = password_store_dir.clone();
+    let repo_opt3 = repo_opt.clone();
+    let password_store_dir3 = password_store_dir.clone();
 
     let gpg_key_edit_view = OnEventView::new(EditView::new()
         .with_id("key_id_input")
@@ -369,8 +699,17 @@ fn add_recipient_dialog(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir:
         .on_event(Key::Enter, move |ui: &mut Cursive| {
             add_recipient(ui, repo_opt.clone(), password_store_dir.clone())
         });
+    let key_file_edit_view = OnEventView::new(EditView::new()
+        .with_id("key_file_input")
+        .fixed_size((50, 1)))
+        .on_event(Key::Enter, move |ui: &mut Cursive| {
+            add_recipient(ui, repo_opt3.clone(), password_store_dir3.clone())
+        });
 
-    recipient_fields.add_child(gpg_key_edit_view);
+    key_id_fields.add_child(gpg_key_edit_view);
+    key_file_fields.add_child(key_file_edit_view);
+    recipient_fields.add_child(key_id_fields);
+    recipient_fields.add_child(key_file_fields);
 
     let cf = CircularFocus::wrap_tab(
         Dialog::around(recipient_fields)
@@ -387,6 +726,243 @@ fn add_recipient_dialog(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir:
     ui.add_layer(ev);
 }
 
+fn create_offboarding_item_label(item: &pass::OffboardingItem, col: usize) -> String {
+    return format!("{} {:3$} {}",
+                   if item.rotated { "✓" } else { "✗" },
+                   item.name,
+                   match item.updated {
+                       Some(d) => format!("{}", d.format("%Y-%m-%d")),
+                       None => CATALOG.gettext("n/a").to_string(),
+                   },
+                   _ = col.saturating_sub(2 + 11 + 9),
+    );
+}
+
+fn open_entry_by_name(ui: &mut Cursive, name: &str, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let dir = match pass::password_dir(password_store_dir.clone()) {
+        Ok(d) => d,
+        Err(e) => {
+            helpers::errorbox(ui, &e);
+            return;
+        }
+    };
+    let path = dir.join(format!("{}.gpg", name));
+    match pass::PasswordEntry::load_from_git(&dir, &path, repo_opt.clone()) {
+        Ok(entry) => open_entry(ui, entry, repo_opt, password_store_dir),
+        Err(e) => helpers::errorbox(ui, &e),
+    }
+}
+
+fn view_offboarding(ui: &mut Cursive, offboarding: &pass::Offboarding, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let col = ui.screen_size().x;
+    let mut items_view = SelectView::<pass::OffboardingItem>::new()
+        .h_align(cursive::align::HAlign::Left)
+        .with_id("offboarding_items");
+
+    for item in &offboarding.items {
+        items_view.get_mut().add_item(create_offboarding_item_label(item, col), item.clone());
+    }
+
+    let title = format!("{} {} ({}/{})",
+                        CATALOG.gettext("Rotate secrets seen by"),
+                        offboarding.name,
+                        offboarding.rotated_count(),
+                        offboarding.items.len());
+
+    let d = Dialog::around(items_view)
+        .title(title)
+        .dismiss_button(CATALOG.gettext("Ok"));
+
+    let ll = LinearLayout::new(Orientation::Vertical)
+        .child(d)
+        .child(LinearLayout::new(Orientation::Horizontal)
+            .child(TextView::new(CATALOG.gettext("enter: Open"))));
+
+    let items_event = OnEventView::new(ll)
+        .on_event(Key::Enter, move |ui: &mut Cursive| {
+            let sel = ui.find_id::<SelectView<pass::OffboardingItem>>("offboarding_items").unwrap().selection();
+            if let Some(item) = sel {
+                open_entry_by_name(ui, &item.name, repo_opt.clone(), password_store_dir.clone());
+            }
+        })
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        });
+
+    ui.add_layer(items_event);
+}
+
+fn view_offboardings(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let offboardings_res = pass::all_offboardings(repo_opt.clone(), password_store_dir.clone());
+    if offboardings_res.is_err() {
+        helpers::errorbox(ui, &offboardings_res.unwrap_err());
+        return;
+    }
+
+    let mut offboardings_view = SelectView::<pass::Offboarding>::new()
+        .h_align(cursive::align::HAlign::Left)
+        .with_id("offboardings");
+
+    for offboarding in offboardings_res.unwrap() {
+        offboardings_view.get_mut().add_item(format!("{} {} {} ({}/{})",
+                                                     offboarding.time.format("%Y-%m-%d"),
+                                                     offboarding.key_id,
+                                                     offboarding.name,
+                                                     offboarding.rotated_count(),
+                                                     offboarding.items.len()), offboarding);
+    }
+
+    let d = Dialog::around(offboardings_view)
+        .title(CATALOG.gettext("Offboarded Team Members"))
+        .dismiss_button(CATALOG.gettext("Ok"));
+
+    let offboardings_event = OnEventView::new(d)
+        .on_event(Key::Enter, move |ui: &mut Cursive| {
+            let sel = ui.find_id::<SelectView<pass::Offboarding>>("offboardings").unwrap().selection();
+            if let Some(offboarding) = sel {
+                view_offboarding(ui, &offboarding, repo_opt.clone(), password_store_dir.clone());
+            }
+        })
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        });
+
+    ui.add_layer(offboardings_event);
+}
+
+fn do_offboard_recipient(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let sel = ui.find_id::<SelectView<pass::Recipient>>("recipients").unwrap().selection();
+    if sel.is_none() {
+        return;
+    }
+    let recipient = sel.unwrap();
+
+    let folders: Vec<String> = get_value_from_input(ui, "offboard_folders_input").unwrap()
+        .split(',')
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty())
+        .collect();
+
+    let res = pass::offboard_recipient(&recipient, &folders, repo_opt.clone(), password_store_dir.clone());
+
+    if res.is_err() {
+        helpers::errorbox(ui, &res.unwrap_err());
+        return;
+    }
+
+    ui.pop_layer();
+    if folders.is_empty() {
+        let mut l = ui.find_id::<SelectView<pass::Recipient>>("recipients").unwrap();
+        let delete_id = l.selected_id().unwrap();
+        l.remove_item(delete_id);
+    }
+    ui.call_on_id("status_bar", |l: &mut TextView| {
+        l.set_content(CATALOG.gettext("Offboarded team member, please rotate the listed secrets"));
+    });
+
+    view_offboarding(ui, &res.unwrap(), repo_opt, password_store_dir);
+}
+
+fn offboard_recipient_dialog(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let fields = LinearLayout::vertical()
+        .child(TextView::new(CATALOG.gettext("Remove this person and reencrypt the passwords they had access to?\nLimit to these folders (comma separated, empty for the whole store):")))
+        .child(EditView::new()
+            .with_id("offboard_folders_input")
+            .fixed_size((50, 1)));
+
+    let cf = CircularFocus::wrap_tab(
+        Dialog::around(fields)
+            .title(CATALOG.gettext("Offboard"))
+            .button(CATALOG.gettext("Yes"), move |ui: &mut Cursive| {
+                do_offboard_recipient(ui, repo_opt.clone(), password_store_dir.clone())
+            })
+            .dismiss_button(CATALOG.gettext("Cancel")));
+
+    let ev = OnEventView::new(cf)
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        });
+
+    ui.add_layer(ev);
+}
+
+fn do_replace_recipient(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let sel = ui.find_id::<SelectView<pass::Recipient>>("recipients").unwrap().selection();
+    if sel.is_none() {
+        return;
+    }
+    let old = sel.unwrap();
+
+    let new_key_id = &*get_value_from_input(ui, "new_key_id_input").unwrap();
+    let new_res = pass::Recipient::new(new_key_id.clone());
+    if new_res.is_err() {
+        helpers::errorbox(ui, &new_res.unwrap_err());
+        return;
+    }
+    let new = new_res.unwrap();
+
+    let res = pass::replace_recipient(&old, &new, repo_opt, password_store_dir);
+    if res.is_err() {
+        helpers::errorbox(ui, &res.unwrap_err());
+        return;
+    }
+
+    ui.pop_layer();
+    ui.call_on_id("recipients", |l: &mut SelectView<pass::Recipient>| {
+        let selected_id = l.selected_id().unwrap();
+        l.remove_item(selected_id);
+        l.insert_item(selected_id, recipient_label(&new), new.clone());
+        l.set_selection(selected_id);
+    });
+    ui.call_on_id("status_bar", |l: &mut TextView| {
+        l.set_content(format!("{} {}", CATALOG.gettext("Replaced key, number of reencrypted passwords:"), res.unwrap().len()));
+    });
+}
+
+fn replace_recipient_dialog(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let mut key_fields = LinearLayout::horizontal();
+    key_fields.add_child(TextView::new(CATALOG.gettext("New GPG Key ID: "))
+        .fixed_size((16, 1)));
+    key_fields.add_child(EditView::new()
+        .with_id("new_key_id_input")
+        .fixed_size((50, 1)));
+
+    let cf = CircularFocus::wrap_tab(
+        Dialog::around(key_fields)
+            .title(CATALOG.gettext("Replace Key"))
+            .button(CATALOG.gettext("Replace"), move |ui: &mut Cursive| {
+                do_replace_recipient(ui, repo_opt.clone(), password_store_dir.clone())
+            })
+            .dismiss_button(CATALOG.gettext("Cancel")));
+
+    let ev = OnEventView::new(cf)
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        });
+
+    ui.add_layer(ev);
+}
+
+fn normalize_recipients(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let res = pass::Recipient::normalize_recipient_files(repo_opt, password_store_dir);
+
+    if res.is_err() {
+        helpers::errorbox(ui, &res.unwrap_err());
+    } else {
+        ui.call_on_id("status_bar", |l: &mut TextView| {
+            l.set_content(CATALOG.gettext("Rewrote the team member lists with full fingerprints"));
+        });
+    }
+}
+
+fn recipient_label(recipient: &pass::Recipient) -> String {
+    return match recipient.key_ring_status {
+        pass::KeyRingStatus::InKeyRing => format!("{} {}", recipient.key_id, recipient.name),
+        pass::KeyRingStatus::CannotEncrypt => format!("{} {} ({})", recipient.key_id, recipient.name, CATALOG.gettext("can't encrypt, expired or revoked")),
+        pass::KeyRingStatus::NotInKeyRing => format!("{} ({})", recipient.key_id, CATALOG.gettext("not in the keyring")),
+    };
+}
+
 fn view_recipients(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
     let recipients_res : Result<Vec<ripasso::pass::Recipient>, pass::Error> = ripasso::pass::Recipient::all_recipients(password_store_dir.clone());
 
@@ -401,7 +977,7 @@ fn view_recipients(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<
         .with_id("recipients");
 
     for recipient in recipients {
-        recipients_view.get_mut().add_item(format!("{} {}", recipient.key_id.clone(), recipient.name.clone()), recipient);
+        recipients_view.get_mut().add_item(recipient_label(&recipient), recipient);
     }
 
     let d = Dialog::around(recipients_view)
@@ -412,10 +988,19 @@ fn view_recipients(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<
         .child(d)
         .child(LinearLayout::new(Orientation::Horizontal)
             .child(TextView::new(CATALOG.gettext("ins: Add | ")))
-            .child(TextView::new(CATALOG.gettext("del: Remove"))));
+            .child(TextView::new(CATALOG.gettext("del: Remove | ")))
+            .child(TextView::new(CATALOG.gettext("o: Offboard | ")))
+            .child(TextView::new(CATALOG.gettext("r: Replace Key | ")))
+            .child(TextView::new(CATALOG.gettext("f: Use Fingerprints"))));
 
     let repo_opt2 = repo_opt.clone();
     let password_store_dir2 = password_store_dir.clone();
+    let repo_opt3 = repo_opt.clone();
+    let password_store_dir3 = password_store_dir.clone();
+    let repo_opt4 = repo_opt.clone();
+    let password_store_dir4 = password_store_dir.clone();
+    let repo_opt5 = repo_opt.clone();
+    let password_store_dir5 = password_store_dir.clone();
 
     let recipients_event = OnEventView::new(ll)
         .on_event(Key::Del, move |ui: &mut Cursive| {
@@ -424,6 +1009,15 @@ fn view_recipients(ui: &mut Cursive, repo_opt: GitRepo, password_store_dir: Arc<
         .on_event(Key::Ins, move |ui: &mut Cursive| {
             add_recipient_dialog(ui, repo_opt2.clone(), password_store_dir2.clone())
         })
+        .on_event(Event::Char('o'), move |ui: &mut Cursive| {
+            offboard_recipient_dialog(ui, repo_opt3.clone(), password_store_dir3.clone())
+        })
+        .on_event(Event::Char('r'), move |ui: &mut Cursive| {
+            replace_recipient_dialog(ui, repo_opt5.clone(), password_store_dir5.clone())
+        })
+        .on_event(Event::Char('f'), move |ui: &mut Cursive| {
+            normalize_recipients(ui, repo_opt4.clone(), password_store_dir4.clone())
+        })
         .on_event(Key::Esc, |s| {
             s.pop_layer();
         });
@@ -478,12 +1072,39 @@ fn search(passwords: &pass::PasswordList, ui: &mut Cursive, query: &str) -> () {
     }
 }
 
-fn help() {
-    println!("{}", CATALOG.gettext("A password manager that uses the file format of the standard unix password manager 'pass', implemented in Rust. Ripasso reads $HOME/.password-store/ by default, override this by setting the PASSWORD_STORE_DIR environmental variable."));
-}
+fn handle_password_events(ui: &mut Cursive, events: Vec<pass::PasswordEvent>, passwords: &pass::PasswordList) -> () {
+    let mut list_changed = false;
+    for event in events {
+        match event {
+            pass::PasswordEvent::Error(ref err) => helpers::errorbox(ui, err),
+            pass::PasswordEvent::RecipientsChanged(_) => {},
+            _ => list_changed = true,
+        }
+    }
 
-fn git_push(ui: &mut Cursive, repo_opt: GitRepo) {
-    let res = pass::push(repo_opt);
+    if !list_changed {
+        return;
+    }
+
+    let query = get_value_from_input(ui, "searchbox").unwrap_or_default();
+    let selected = ui.find_id::<SelectView<pass::PasswordEntry>>("results").unwrap().selected_id();
+    search(passwords, ui, &query);
+    ui.call_on_id("results", |l: &mut SelectView<pass::PasswordEntry>| {
+        if let Some(index) = selected {
+            if !l.is_empty() {
+                l.set_selection(index.min(l.len() - 1));
+            }
+        }
+    });
+}
+
+fn help() {
+    println!("{}", CATALOG.gettext("A password manager that uses the file format of the standard unix password manager 'pass', implemented in Rust. Ripasso reads $HOME/.password-store/ by default, override this by setting the PASSWORD_STORE_DIR environmental variable."));
+    println!("{}", CATALOG.gettext("Passwords are copied to the desktop clipboard, or to the terminal clipboard with OSC 52 when there is no desktop, for example over SSH. Choose one by setting the RIPASSO_CLIPBOARD environmental variable to system or osc52."));
+}
+
+fn git_push(ui: &mut Cursive, repo_opt: GitRepo) {
+    let res = pass::push(repo_opt);
 
     if res.is_err() {
         helpers::errorbox(ui, &res.unwrap_err());
@@ -495,15 +1116,19 @@ fn git_push(ui: &mut Cursive, repo_opt: GitRepo) {
 }
 
 fn git_pull(ui: &mut Cursive, passwords: pass::PasswordList, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) {
-    let pull_res = pass::pull(repo_opt.clone());
-
-    if pull_res.is_err() {
-        helpers::errorbox(ui, &pull_res.unwrap_err());
-    }
+    let heads = match pass::pull(repo_opt.clone()) {
+        Ok(heads) => heads,
+        Err(err) => {
+            helpers::errorbox(ui, &err);
+            return;
+        }
+    };
 
-    let res = pass::populate_password_list(&passwords, repo_opt, password_store_dir);
-    if res.is_err() {
-        helpers::errorbox(ui, &res.unwrap_err());
+    if let Some((old_head, new_head)) = heads {
+        let res = pass::update_password_list(&passwords, repo_opt, password_store_dir, old_head, new_head);
+        if res.is_err() {
+            helpers::errorbox(ui, &res.unwrap_err());
+        }
     }
 
     let col = ui.screen_size().x;
@@ -569,7 +1194,685 @@ fn get_translation_catalog() -> gettext::Catalog {
     return gettext::Catalog::empty();
 }
 
+fn weakness_text(weakness: &audit::Weakness) -> String {
+    match weakness {
+        audit::Weakness::Short { length } => format!("{} {}", CATALOG.gettext("short:"), length),
+        audit::Weakness::LowScore { score } => format!("{} {}/4", CATALOG.gettext("score:"), score),
+        audit::Weakness::Dictionary => CATALOG.gettext("dictionary word").to_string(),
+    }
+}
+
+fn view_audit_report(ui: &mut Cursive, report: audit::AuditReport, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let mut report_view = SelectView::<Option<String>>::new()
+        .h_align(cursive::align::HAlign::Left)
+        .with_id("audit_report");
+
+    {
+        let mut l = report_view.get_mut();
+        if report.cancelled {
+            l.add_item(CATALOG.gettext("The audit was cancelled, the report is incomplete"), None);
+        }
+
+        l.add_item(format!("{} ({})", CATALOG.gettext("Reused passwords"), report.reused.len()), None);
+        for (i, group) in report.reused.iter().enumerate() {
+            for name in group {
+                l.add_item(format!("  {:>3} {}", i + 1, name), Some(name.clone()));
+            }
+        }
+
+        l.add_item(format!("{} ({})", CATALOG.gettext("Weak passwords"), report.weak.len()), None);
+        for weak in &report.weak {
+            let reasons: Vec<String> = weak.weaknesses.iter().map(weakness_text).collect();
+            l.add_item(format!("  {} ({})", weak.name, reasons.join(", ")), Some(weak.name.clone()));
+        }
+
+        l.add_item(format!("{} ({})", CATALOG.gettext("Stale passwords"), report.stale.len()), None);
+        for stale in &report.stale {
+            l.add_item(format!("  {} {}", stale.updated.format("%Y-%m-%d"), stale.name), Some(stale.name.clone()));
+        }
+
+        if !report.failed.is_empty() {
+            l.add_item(format!("{} ({})", CATALOG.gettext("Couldn't be decrypted"), report.failed.len()), None);
+            for (name, err) in &report.failed {
+                l.add_item(format!("  {} {}", name, err), Some(name.clone()));
+            }
+        }
+    }
+
+    let d = Dialog::around(report_view.scrollable())
+        .title(CATALOG.gettext("Password health"))
+        .dismiss_button(CATALOG.gettext("Ok"));
+
+    let ll = LinearLayout::new(Orientation::Vertical)
+        .child(d)
+        .child(LinearLayout::new(Orientation::Horizontal)
+            .child(TextView::new(CATALOG.gettext("enter: Open"))));
+
+    let report_event = OnEventView::new(ll)
+        .on_event(Key::Enter, move |ui: &mut Cursive| {
+            let sel = ui.find_id::<SelectView<Option<String>>>("audit_report").unwrap().selection();
+            if let Some(name) = sel.and_then(|s| (*s).clone()) {
+                open_entry_by_name(ui, &name, repo_opt.clone(), password_store_dir.clone());
+            }
+        })
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        });
+
+    ui.add_layer(report_event);
+}
+
+fn run_audit(ui: &mut Cursive, passwords: pass::PasswordList, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let entries = passwords.lock().unwrap().clone();
+    let cancel = Arc::new(AtomicBool::new(false));
+
+    let cancel2 = cancel.clone();
+    let d = Dialog::around(TextView::new(CATALOG.gettext("Decrypting passwords...")).with_id("audit_progress"))
+        .title(CATALOG.gettext("Password health"))
+        .button(CATALOG.gettext("Cancel"), move |_| {
+            cancel2.store(true, Ordering::SeqCst);
+        });
+    ui.add_layer(d.with_id("audit_dialog"));
+
+    let cb_sink = ui.cb_sink().clone();
+    thread::spawn(move || {
+        let progress_sink = cb_sink.clone();
+        let report = audit::audit(&entries, &audit::AuditOptions::default(), &mut |p| {
+            let text = format!("{} {}/{} {}", CATALOG.gettext("Decrypted"), p.done, p.total, p.name);
+            let _ = progress_sink.send(Box::new(move |s: &mut Cursive| {
+                s.call_on_id("audit_progress", |l: &mut TextView| {
+                    l.set_content(text);
+                });
+            }));
+        }, &cancel);
+
+        let _ = cb_sink.send(Box::new(move |s: &mut Cursive| {
+            if s.find_id::<Dialog>("audit_dialog").is_some() {
+                s.pop_layer();
+            }
+            view_audit_report(s, report, repo_opt, password_store_dir);
+        }));
+    });
+}
+
+fn open_breach_database(ui: &mut Cursive) -> Option<breach::BreachDatabase> {
+    let path = get_value_from_input(ui, "breach_path_input").unwrap();
+    match breach::BreachDatabase::open(std::path::Path::new(&*path)) {
+        Ok(database) => Some(database),
+        Err(err) => {
+            helpers::errorbox(ui, &err);
+            None
+        }
+    }
+}
+
+fn check_selected_breached(ui: &mut Cursive) -> () {
+    let database = match open_breach_database(ui) {
+        Some(d) => d,
+        None => return,
+    };
+    let sel = ui.find_id::<SelectView<pass::PasswordEntry>>("results").unwrap().selection();
+    let entry = match sel {
+        Some(e) => e,
+        None => return,
+    };
+
+    let text = match database.check_entry(&entry) {
+        Ok(Some(count)) => format!("{} {} {}", entry.name, CATALOG.gettext("has been seen in breaches, number of times:"), count),
+        Ok(None) => format!("{} {}", entry.name, CATALOG.gettext("hasn't been seen in any breach")),
+        Err(err) => {
+            helpers::errorbox(ui, &err);
+            return;
+        }
+    };
+
+    ui.pop_layer();
+    ui.call_on_id("status_bar", |l: &mut TextView| {
+        l.set_content(text);
+    });
+}
+
+fn view_breach_report(ui: &mut Cursive, report: breach::BreachReport, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let mut report_view = SelectView::<Option<String>>::new()
+        .h_align(cursive::align::HAlign::Left)
+        .with_id("breach_report");
+
+    {
+        let mut l = report_view.get_mut();
+        if report.cancelled {
+            l.add_item(CATALOG.gettext("The check was cancelled, the report is incomplete"), None);
+        }
+        l.add_item(format!("{} ({})", CATALOG.gettext("Breached passwords"), report.breached.len()), None);
+        for (name, count) in &report.breached {
+            l.add_item(format!("  {:>10} {}", count, name), Some(name.clone()));
+        }
+        if !report.failed.is_empty() {
+            l.add_item(format!("{} ({})", CATALOG.gettext("Couldn't be checked"), report.failed.len()), None);
+            for (name, err) in &report.failed {
+                l.add_item(format!("  {} {}", name, err), Some(name.clone()));
+            }
+        }
+    }
+
+    let d = Dialog::around(report_view.scrollable())
+        .title(CATALOG.gettext("Breached Passwords"))
+        .dismiss_button(CATALOG.gettext("Ok"));
+
+    let ll = LinearLayout::new(Orientation::Vertical)
+        .child(d)
+        .child(LinearLayout::new(Orientation::Horizontal)
+            .child(TextView::new(CATALOG.gettext("enter: Open"))));
+
+    let report_event = OnEventView::new(ll)
+        .on_event(Key::Enter, move |ui: &mut Cursive| {
+            let sel = ui.find_id::<SelectView<Option<String>>>("breach_report").unwrap().selection();
+            if let Some(name) = sel.and_then(|s| (*s).clone()) {
+                open_entry_by_name(ui, &name, repo_opt.clone(), password_store_dir.clone());
+            }
+        })
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        });
+
+    ui.add_layer(report_event);
+}
+
+fn check_all_breached(ui: &mut Cursive, passwords: pass::PasswordList, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let database = match open_breach_database(ui) {
+        Some(d) => d,
+        None => return,
+    };
+    ui.pop_layer();
+
+    let entries = passwords.lock().unwrap().clone();
+    let cancel = Arc::new(AtomicBool::new(false));
+
+    let cancel2 = cancel.clone();
+    let d = Dialog::around(TextView::new(CATALOG.gettext("Decrypting passwords...")).with_id("breach_progress"))
+        .title(CATALOG.gettext("Breached Passwords"))
+        .button(CATALOG.gettext("Cancel"), move |_| {
+            cancel2.store(true, Ordering::SeqCst);
+        });
+    ui.add_layer(d.with_id("breach_dialog"));
+
+    let cb_sink = ui.cb_sink().clone();
+    thread::spawn(move || {
+        let progress_sink = cb_sink.clone();
+        let report = breach::check_entries(&entries, &database, &mut |done, total| {
+            let text = format!("{} {}/{}", CATALOG.gettext("Checked"), done, total);
+            let _ = progress_sink.send(Box::new(move |s: &mut Cursive| {
+                s.call_on_id("breach_progress", |l: &mut TextView| {
+                    l.set_content(text);
+                });
+            }));
+        }, &cancel);
+
+        let _ = cb_sink.send(Box::new(move |s: &mut Cursive| {
+            if s.find_id::<Dialog>("breach_dialog").is_some() {
+                s.pop_layer();
+            }
+            view_breach_report(s, report, repo_opt, password_store_dir);
+        }));
+    });
+}
+
+fn breach_check_dialog(ui: &mut Cursive, passwords: pass::PasswordList, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let default_path = std::env::var("RIPASSO_PWNED_PASSWORDS").unwrap_or_default();
+
+    let fields = LinearLayout::vertical()
+        .child(TextView::new(CATALOG.gettext("Pwned Passwords SHA-1 file or folder of range files:")))
+        .child(EditView::new()
+            .content(default_path)
+            .with_id("breach_path_input")
+            .fixed_size((60, 1)));
+
+    let d = Dialog::around(fields)
+        .title(CATALOG.gettext("Breached Passwords"))
+        .button(CATALOG.gettext("Check selected"), check_selected_breached)
+        .button(CATALOG.gettext("Check all"), move |ui: &mut Cursive| {
+            check_all_breached(ui, passwords.clone(), repo_opt.clone(), password_store_dir.clone())
+        })
+        .dismiss_button(CATALOG.gettext("Cancel"));
+
+    let ev = OnEventView::new(d)
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        });
+
+    ui.add_layer(ev);
+}
+
+fn read_import_records(ui: &mut Cursive) -> Option<(Vec<import::ImportRecord>, import::ConflictStrategy)> {
+    let path = get_value_from_input(ui, "import_path_input").unwrap();
+    let format = ui.find_id::<SelectView<import::ImportFormat>>("import_format").unwrap().selection().unwrap();
+    let strategy = ui.find_id::<SelectView<import::ConflictStrategy>>("import_conflicts").unwrap().selection().unwrap();
+
+    let contents = match std::fs::read_to_string(&*path) {
+        Ok(c) => c,
+        Err(err) => {
+            helpers::errorbox(ui, &pass::Error::from(err));
+            return None;
+        }
+    };
+
+    match import::parse(*format, &contents) {
+        Ok(records) => Some((records, *strategy)),
+        Err(err) => {
+            helpers::errorbox(ui, &err);
+            None
+        }
+    }
+}
+
+fn preview_import(ui: &mut Cursive, password_store_dir: Arc<Option<String>>) -> () {
+    let (records, strategy) = match read_import_records(ui) {
+        Some(r) => r,
+        None => return,
+    };
+
+    let actions = match import::plan(&records, strategy, password_store_dir) {
+        Ok(a) => a,
+        Err(err) => {
+            helpers::errorbox(ui, &err);
+            return;
+        }
+    };
+
+    let mut preview = SelectView::<()>::new().h_align(cursive::align::HAlign::Left);
+    for action in actions {
+        let label = match action {
+            import::ImportAction::Create(name) => format!("{} {}", CATALOG.gettext("create   "), name),
+            import::ImportAction::Overwrite(name) => format!("{} {}", CATALOG.gettext("overwrite"), name),
+            import::ImportAction::Rename { from, to } => format!("{} {} -> {}", CATALOG.gettext("rename   "), from, to),
+            import::ImportAction::Skip(name) => format!("{} {}", CATALOG.gettext("skip     "), name),
+        };
+        preview.add_item(label, ());
+    }
+
+    let d = Dialog::around(preview.scrollable())
+        .title(CATALOG.gettext("Import preview"))
+        .dismiss_button(CATALOG.gettext("Ok"));
+
+    ui.add_layer(OnEventView::new(d)
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        }));
+}
+
+fn do_import(ui: &mut Cursive, passwords: pass::PasswordList, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let (records, strategy) = match read_import_records(ui) {
+        Some(r) => r,
+        None => return,
+    };
+
+    match import::import(&records, strategy, repo_opt, password_store_dir) {
+        Ok(summary) => {
+            ui.pop_layer();
+            let text = format!("{} {}, {} {}, {} {}",
+                               CATALOG.gettext("Imported"), summary.imported.len(),
+                               CATALOG.gettext("skipped"), summary.skipped.len(),
+                               CATALOG.gettext("failed"), summary.failed.len());
+            ui.call_on_id("status_bar", |l: &mut TextView| {
+                l.set_content(text);
+            });
+            if !summary.failed.is_empty() {
+                let failures: Vec<String> = summary.failed.iter().map(|(name, err)| format!("{}: {}", name, err)).collect();
+                ui.add_layer(Dialog::around(TextView::new(failures.join("\n")).scrollable())
+                    .title(CATALOG.gettext("Couldn't import"))
+                    .dismiss_button(CATALOG.gettext("Ok")));
+            }
+            search(&passwords, ui, "");
+        },
+        Err(err) => helpers::errorbox(ui, &err),
+    }
+}
+
+fn import_dialog(ui: &mut Cursive, passwords: pass::PasswordList, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let mut format = SelectView::<import::ImportFormat>::new().popup();
+    format.add_item("KeePass 2 XML", import::ImportFormat::KeePassXml);
+    format.add_item("Bitwarden JSON", import::ImportFormat::BitwardenJson);
+    format.add_item("1Password 1PIF", import::ImportFormat::OnePassword1Pif);
+    format.add_item("1Password CSV", import::ImportFormat::OnePasswordCsv);
+    format.add_item("LastPass CSV", import::ImportFormat::LastPassCsv);
+
+    let mut conflicts = SelectView::<import::ConflictStrategy>::new().popup();
+    conflicts.add_item(CATALOG.gettext("Skip existing names"), import::ConflictStrategy::Skip);
+    conflicts.add_item(CATALOG.gettext("Rename imported entries"), import::ConflictStrategy::Rename);
+    conflicts.add_item(CATALOG.gettext("Overwrite existing entries"), import::ConflictStrategy::Overwrite);
+
+    let mut path_fields = LinearLayout::horizontal();
+    path_fields.add_child(TextView::new(CATALOG.gettext("File: "))
+        .fixed_size((10, 1)));
+    path_fields.add_child(EditView::new()
+        .with_id("import_path_input")
+        .fixed_size((50, 1)));
+
+    let fields = LinearLayout::vertical()
+        .child(path_fields)
+        .child(format.with_id("import_format"))
+        .child(conflicts.with_id("import_conflicts"));
+
+    let password_store_dir2 = password_store_dir.clone();
+    let d = Dialog::around(fields)
+        .title(CATALOG.gettext("Import passwords"))
+        .button(CATALOG.gettext("Preview"), move |ui: &mut Cursive| {
+            preview_import(ui, password_store_dir2.clone())
+        })
+        .button(CATALOG.gettext("Import"), move |ui: &mut Cursive| {
+            do_import(ui, passwords.clone(), repo_opt.clone(), password_store_dir.clone())
+        })
+        .dismiss_button(CATALOG.gettext("Cancel"));
+
+    ui.add_layer(OnEventView::new(d)
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        }));
+}
+
+fn do_export(ui: &mut Cursive, passwords: pass::PasswordList) -> () {
+    let path = get_value_from_input(ui, "export_path_input").unwrap();
+    let key = get_value_from_input(ui, "export_key_input").unwrap();
+    let folders: Vec<String> = get_value_from_input(ui, "export_folders_input").unwrap()
+        .split(',')
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty())
+        .collect();
+    let format = *ui.find_id::<SelectView<export::ExportFormat>>("export_format").unwrap().selection().unwrap();
+    let plaintext = is_checked(ui, "export_plaintext");
+
+    let encryption = if plaintext {
+        export::ExportEncryption::Plaintext
+    } else if key.trim().is_empty() {
+        helpers::errorbox(ui, &pass::Error::Generic("choose a key to encrypt the export to, or export in plain text"));
+        return;
+    } else {
+        export::ExportEncryption::ToKey(key.trim().to_string())
+    };
+
+    let entries = passwords.lock().unwrap().clone();
+    let res = std::fs::File::create(&*path)
+        .map_err(pass::Error::from)
+        .and_then(|mut file| export::export(&entries, &folders, format, &encryption, &mut file));
+
+    match res {
+        Ok(count) => {
+            ui.pop_layer();
+            let text = format!("{} {}", CATALOG.gettext("Exported passwords:"), count);
+            ui.call_on_id("status_bar", |l: &mut TextView| {
+                l.set_content(text);
+            });
+        },
+        Err(err) => {
+            let _ = std::fs::remove_file(&*path);
+            helpers::errorbox(ui, &err);
+        }
+    }
+}
+
+fn export_dialog(ui: &mut Cursive, passwords: pass::PasswordList) -> () {
+    let mut format = SelectView::<export::ExportFormat>::new().popup();
+    format.add_item("JSON", export::ExportFormat::Json);
+    format.add_item("CSV", export::ExportFormat::Csv);
+    format.add_item("KeePass 2 XML", export::ExportFormat::KeePassXml);
+
+    let input_row = |label: &str, id: &str| {
+        LinearLayout::horizontal()
+            .child(TextView::new(label).fixed_size((16, 1)))
+            .child(EditView::new().with_id(id).fixed_size((50, 1)))
+    };
+
+    let fields = LinearLayout::vertical()
+        .child(input_row(CATALOG.gettext("File: "), "export_path_input"))
+        .child(input_row(CATALOG.gettext("Encrypt to key: "), "export_key_input"))
+        .child(input_row(CATALOG.gettext("Folders: "), "export_folders_input"))
+        .child(TextView::new(CATALOG.gettext("Comma separated, leave empty to export everything")))
+        .child(format.with_id("export_format"))
+        .child(labeled_checkbox(CATALOG.gettext("Export in plain text, without encryption"), "export_plaintext", false));
+
+    let d = Dialog::around(fields)
+        .title(CATALOG.gettext("Export passwords"))
+        .button(CATALOG.gettext("Export"), move |ui: &mut Cursive| {
+            do_export(ui, passwords.clone())
+        })
+        .dismiss_button(CATALOG.gettext("Cancel"));
+
+    ui.add_layer(OnEventView::new(d)
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        }));
+}
+
+fn do_share(ui: &mut Cursive, entry: pass::PasswordEntry) -> () {
+    let key = get_value_from_input(ui, "share_key_input").unwrap();
+    let path = get_value_from_input(ui, "share_path_input").unwrap();
+
+    let key = key.trim();
+    if key.is_empty() || path.trim().is_empty() {
+        helpers::errorbox(ui, &pass::Error::Generic("choose a key and a file to write the shared entry to"));
+        return;
+    }
+    let recipient = if std::path::Path::new(key).is_file() {
+        share::ShareRecipient::KeyFile(std::path::PathBuf::from(key))
+    } else {
+        share::ShareRecipient::Key(key.to_string())
+    };
+
+    let res = std::fs::File::create(&*path)
+        .map_err(pass::Error::from)
+        .and_then(|mut file| share::share_entry(&entry, &recipient, &mut file));
+
+    match res {
+        Ok(()) => {
+            ui.pop_layer();
+            let text = format!("{} {}", CATALOG.gettext("Shared entry written to"), path);
+            ui.call_on_id("status_bar", |l: &mut TextView| {
+                l.set_content(text);
+            });
+        },
+        Err(err) => {
+            let _ = std::fs::remove_file(&*path);
+            helpers::errorbox(ui, &err);
+        }
+    }
+}
+
+fn share_dialog(ui: &mut Cursive) -> () {
+    let sel = ui.find_id::<SelectView<pass::PasswordEntry>>("results").unwrap().selection();
+    let entry = match sel {
+        Some(e) => (*e).clone(),
+        None => return,
+    };
+
+    let input_row = |label: &str, id: &str| {
+        LinearLayout::horizontal()
+            .child(TextView::new(label).fixed_size((24, 1)))
+            .child(EditView::new().with_id(id).fixed_size((50, 1)))
+    };
+
+    let fields = LinearLayout::vertical()
+        .child(input_row(CATALOG.gettext("Key id or key file: "), "share_key_input"))
+        .child(input_row(CATALOG.gettext("Write to file: "), "share_path_input"));
+
+    let d = Dialog::around(fields)
+        .title(format!("{} {}", CATALOG.gettext("Share"), entry.name))
+        .button(CATALOG.gettext("Share"), move |ui: &mut Cursive| {
+            do_share(ui, entry.clone())
+        })
+        .dismiss_button(CATALOG.gettext("Cancel"));
+
+    ui.add_layer(OnEventView::new(d)
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        }));
+}
+
+fn do_import_shared(ui: &mut Cursive, passwords: pass::PasswordList, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let path = get_value_from_input(ui, "import_shared_path_input").unwrap();
+    let name = get_value_from_input(ui, "import_shared_name_input").unwrap();
+
+    if name.trim().is_empty() {
+        helpers::errorbox(ui, &pass::Error::Generic("choose a name for the new entry"));
+        return;
+    }
+
+    let res = std::fs::read(&*path)
+        .map_err(pass::Error::from)
+        .and_then(|message| share::import_shared(&message, name.trim(), repo_opt, password_store_dir));
+
+    match res {
+        Ok(violations) => {
+            ui.pop_layer();
+            let text = format!("{} {}", CATALOG.gettext("Imported shared entry"), name);
+            ui.call_on_id("status_bar", |l: &mut TextView| {
+                l.set_content(text);
+            });
+            search(&passwords, ui, "");
+            show_policy_warnings(ui, &violations);
+        },
+        Err(err) => helpers::errorbox(ui, &err),
+    }
+}
+
+fn import_shared_dialog(ui: &mut Cursive, passwords: pass::PasswordList, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> () {
+    let input_row = |label: &str, id: &str| {
+        LinearLayout::horizontal()
+            .child(TextView::new(label).fixed_size((16, 1)))
+            .child(EditView::new().with_id(id).fixed_size((50, 1)))
+    };
+
+    let fields = LinearLayout::vertical()
+        .child(input_row(CATALOG.gettext("Message file: "), "import_shared_path_input"))
+        .child(input_row(CATALOG.gettext("New entry: "), "import_shared_name_input"));
+
+    let d = Dialog::around(fields)
+        .title(CATALOG.gettext("Import shared entry"))
+        .button(CATALOG.gettext("Import"), move |ui: &mut Cursive| {
+            do_import_shared(ui, passwords.clone(), repo_opt.clone(), password_store_dir.clone())
+        })
+        .dismiss_button(CATALOG.gettext("Cancel"));
+
+    ui.add_layer(OnEventView::new(d)
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        }));
+}
+
+fn do_backup(ui: &mut Cursive, password_store_dir: Arc<Option<String>>) -> () {
+    let path = get_value_from_input(ui, "backup_path_input").unwrap();
+    let recovery_key = get_value_from_input(ui, "backup_recovery_key_input").unwrap();
+
+    if path.trim().is_empty() {
+        helpers::errorbox(ui, &pass::Error::Generic("choose a file to write the backup to"));
+        return;
+    }
+    let options = backup::BackupOptions {
+        include_git: is_checked(ui, "backup_include_git"),
+        recovery_key: if recovery_key.trim().is_empty() { None } else { Some(recovery_key.trim().to_string()) },
+        signing_key: None,
+    };
+
+    let res = std::fs::File::create(&*path)
+        .map_err(pass::Error::from)
+        .and_then(|mut file| backup::create_backup(password_store_dir, &options, &mut file));
+
+    match res {
+        Ok(manifest) => {
+            ui.pop_layer();
+            let text = format!("{} {}", CATALOG.gettext("Files in the backup:"), manifest.entries.len());
+            ui.call_on_id("status_bar", |l: &mut TextView| {
+                l.set_content(text);
+            });
+        },
+        Err(err) => {
+            let _ = std::fs::remove_file(&*path);
+            helpers::errorbox(ui, &err);
+        }
+    }
+}
+
+fn backup_dialog(ui: &mut Cursive, password_store_dir: Arc<Option<String>>) -> () {
+    let input_row = |label: &str, id: &str| {
+        LinearLayout::horizontal()
+            .child(TextView::new(label).fixed_size((16, 1)))
+            .child(EditView::new().with_id(id).fixed_size((50, 1)))
+    };
+
+    let fields = LinearLayout::vertical()
+        .child(input_row(CATALOG.gettext("File: "), "backup_path_input"))
+        .child(input_row(CATALOG.gettext("Recovery key: "), "backup_recovery_key_input"))
+        .child(TextView::new(CATALOG.gettext("Leave the recovery key empty to not encrypt the backup")))
+        .child(labeled_checkbox(CATALOG.gettext("Include the git history"), "backup_include_git", true));
+
+    let d = Dialog::around(fields)
+        .title(CATALOG.gettext("Backup"))
+        .button(CATALOG.gettext("Backup"), move |ui: &mut Cursive| {
+            do_backup(ui, password_store_dir.clone())
+        })
+        .dismiss_button(CATALOG.gettext("Cancel"));
+
+    ui.add_layer(OnEventView::new(d)
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        }));
+}
+
+fn do_restore(ui: &mut Cursive) -> () {
+    let path = get_value_from_input(ui, "restore_path_input").unwrap();
+    let target = get_value_from_input(ui, "restore_target_input").unwrap();
+    let signer = get_value_from_input(ui, "restore_signer_input").unwrap();
+
+    if target.trim().is_empty() {
+        helpers::errorbox(ui, &pass::Error::Generic("choose a folder to restore the backup to"));
+        return;
+    }
+    if signer.trim().is_empty() {
+        helpers::errorbox(ui, &pass::Error::Generic("choose the key that signed the backup"));
+        return;
+    }
+
+    let res = std::fs::read(&*path)
+        .map_err(pass::Error::from)
+        .and_then(|data| backup::restore_backup(&data, std::path::Path::new(target.trim()), signer.trim()));
+
+    match res {
+        Ok(manifest) => {
+            ui.pop_layer();
+            let text = format!("{} {}, {} {}", CATALOG.gettext("Restored files:"), manifest.entries.len(), CATALOG.gettext("to"), target);
+            ui.call_on_id("status_bar", |l: &mut TextView| {
+                l.set_content(text);
+            });
+        },
+        Err(err) => helpers::errorbox(ui, &err),
+    }
+}
+
+fn restore_dialog(ui: &mut Cursive) -> () {
+    let input_row = |label: &str, id: &str| {
+        LinearLayout::horizontal()
+            .child(TextView::new(label).fixed_size((16, 1)))
+            .child(EditView::new().with_id(id).fixed_size((50, 1)))
+    };
+
+    let fields = LinearLayout::vertical()
+        .child(input_row(CATALOG.gettext("Backup file: "), "restore_path_input"))
+        .child(input_row(CATALOG.gettext("New store: "), "restore_target_input"))
+        .child(input_row(CATALOG.gettext("Signed by: "), "restore_signer_input"))
+        .child(TextView::new(CATALOG.gettext("The backup is only restored if it's signed by this key")));
+
+    let d = Dialog::around(fields)
+        .title(CATALOG.gettext("Restore Backup"))
+        .button(CATALOG.gettext("Restore"), do_restore)
+        .dismiss_button(CATALOG.gettext("Cancel"));
+
+    ui.add_layer(OnEventView::new(d)
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        }));
+}
+
 fn main() {
+    clipboard::run_helper_if_requested(&CLIPBOARD_BACKEND);
+
     env_logger::init();
 
     let password_store_dir = Arc::new(match std::env::var("PASSWORD_STORE_DIR") {
@@ -609,7 +1912,7 @@ fn main() {
     }
     let pdir_res = pass::password_dir(password_store_dir.clone());
     if pdir_res.is_err() {
-        eprintln!("Error {:?}", pdir_res.err().unwrap());
+        eprintln!("{}", helpers::error_message(&pdir_res.err().unwrap()));
         process::exit(1);
     }
     let repo_res = git2::Repository::open(pdir_res.unwrap());
@@ -631,30 +1934,35 @@ fn main() {
     }
 
     // Load and watch all the passwords in the background
-    let (password_rx, passwords) = match pass::watch(repo_opt.clone(), password_store_dir.clone()) {
+    let (password_rx, passwords, watch_handle) = match pass::watch(repo_opt.clone(), password_store_dir.clone()) {
         Ok(t) => t,
         Err(e) => {
-            eprintln!("Error {:?}", e);
+            eprintln!("Error: {}", helpers::error_message(&e));
             process::exit(1);
         }
     };
 
     let mut ui = Cursive::default();
 
-    // Update UI on password change event
-    let e = ui.cb_sink().send(Box::new(move |s: &mut Cursive| {
-        let event = password_rx.try_recv();
-        if let Ok(e) = event {
-            if let pass::PasswordEvent::Error(ref err) = e {
-                helpers::errorbox(s, err)
+    // Update UI on password change events, a batch at a time so that the list isn't redrawn for
+    // every entry while the metadata is loaded
+    let cb_sink = ui.cb_sink().clone();
+    let passwords_event_clone = std::sync::Arc::clone(&passwords);
+    thread::spawn(move || {
+        while let Ok(first) = password_rx.recv() {
+            thread::sleep(time::Duration::from_millis(100));
+            let mut events = vec![first];
+            events.extend(password_rx.try_iter());
+
+            let passwords = passwords_event_clone.clone();
+            let res = cb_sink.send(Box::new(move |s: &mut Cursive| {
+                handle_password_events(s, events, &passwords)
+            }));
+            if res.is_err() {
+                break;
             }
         }
-    }));
-
-    if e.is_err() {
-        eprintln!("Application error: {}", e.err().unwrap());
-        return;
-    }
+    });
 
     let (repo_opt2, password_store_dir2) = (repo_opt.clone(), password_store_dir.clone());
     let (repo_opt3, password_store_dir3) = (repo_opt.clone(), password_store_dir.clone());
@@ -668,6 +1976,12 @@ fn main() {
     let repo_opt11 = repo_opt.clone();
     let repo_opt12 = repo_opt.clone();
     let repo_opt13 = repo_opt.clone();
+    let (repo_opt14, password_store_dir10) = (repo_opt.clone(), password_store_dir.clone());
+    let (repo_opt15, password_store_dir11) = (repo_opt.clone(), password_store_dir.clone());
+    let (repo_opt16, password_store_dir12) = (repo_opt.clone(), password_store_dir.clone());
+    let (repo_opt17, password_store_dir13) = (repo_opt.clone(), password_store_dir.clone());
+    let (repo_opt18, password_store_dir14) = (repo_opt.clone(), password_store_dir.clone());
+    let password_store_dir15 = password_store_dir.clone();
 
     ui.add_global_callback(Event::CtrlChar('y'), copy);
     ui.add_global_callback(Key::Enter, copy);
@@ -744,6 +2058,11 @@ fn main() {
     );
 
     let passwords_git_pull_clone2 = std::sync::Arc::clone(&passwords);
+    let passwords_audit_clone = std::sync::Arc::clone(&passwords);
+    let passwords_breach_clone = std::sync::Arc::clone(&passwords);
+    let passwords_import_clone = std::sync::Arc::clone(&passwords);
+    let passwords_export_clone = std::sync::Arc::clone(&passwords);
+    let passwords_import_shared_clone = std::sync::Arc::clone(&passwords);
     ui.menubar()
         .add_subtree(CATALOG.gettext("Operations"),
                      MenuTree::new()
@@ -760,6 +2079,29 @@ fn main() {
                          .leaf(CATALOG.gettext("Team Members (ctrl-v)"), move |ui: &mut Cursive| {
                              view_recipients(ui, repo_opt11.clone(), password_store_dir8.clone())
                          })
+                         .leaf(CATALOG.gettext("Offboarded Team Members"), move |ui: &mut Cursive| {
+                             view_offboardings(ui, repo_opt14.clone(), password_store_dir10.clone())
+                         })
+                         .leaf(CATALOG.gettext("Password Health"), move |ui: &mut Cursive| {
+                             run_audit(ui, passwords_audit_clone.clone(), repo_opt15.clone(), password_store_dir11.clone())
+                         })
+                         .leaf(CATALOG.gettext("Breached Passwords"), move |ui: &mut Cursive| {
+                             breach_check_dialog(ui, passwords_breach_clone.clone(), repo_opt16.clone(), password_store_dir12.clone())
+                         })
+                         .leaf(CATALOG.gettext("Import"), move |ui: &mut Cursive| {
+                             import_dialog(ui, passwords_import_clone.clone(), repo_opt17.clone(), password_store_dir13.clone())
+                         })
+                         .leaf(CATALOG.gettext("Export"), move |ui: &mut Cursive| {
+                             export_dialog(ui, passwords_export_clone.clone())
+                         })
+                         .leaf(CATALOG.gettext("Share Entry"), share_dialog)
+                         .leaf(CATALOG.gettext("Import Shared Entry"), move |ui: &mut Cursive| {
+                             import_shared_dialog(ui, passwords_import_shared_clone.clone(), repo_opt18.clone(), password_store_dir14.clone())
+                         })
+                         .leaf(CATALOG.gettext("Backup"), move |ui: &mut Cursive| {
+                             backup_dialog(ui, password_store_dir15.clone())
+                         })
+                         .leaf(CATALOG.gettext("Restore Backup"), restore_dialog)
                          .delimiter()
                          .leaf(CATALOG.gettext("Git Pull (ctrl-f)"), move |ui: &mut Cursive| {
                              git_pull(ui, passwords_git_pull_clone2.clone(), repo_opt12.clone(), password_store_dir9.clone())
@@ -772,10 +2114,19 @@ fn main() {
 
     ui.add_global_callback(Key::F1, |s| s.select_menubar());
 
-    // This construction is to make sure that the password list is populated when the program starts
-    // it would be better to signal this somehow from the library, but that got tricky
-    thread::sleep(time::Duration::from_millis(200));
+    import_missing_public_keys(&mut ui, password_store_dir.clone());
+
     search(&passwords, &mut ui, "");
 
+    osc52::set_ui_sink(Some(ui.cb_sink().clone()));
     ui.run();
+    osc52::set_ui_sink(None);
+
+    if let Err(err) = CLIPBOARD.handoff() {
+        eprintln!("Error: {}", helpers::error_message(&err));
+    }
+
+    if let Err(err) = watch_handle.join() {
+        eprintln!("Error: {}", helpers::error_message(&err));
+    }
 }
@@ -0,0 +1,152 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+extern crate base64;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use cursive::{CbSink, Cursive};
+
+use ripasso::clipboard::{BackendFactory, ClipboardBackend, SystemClipboard};
+use ripasso::pass;
+use ripasso::secret::SecretString;
+
+use crate::helpers;
+
+/// Selects the clipboard: `system` for the clipboard of the desktop, `osc52` for the clipboard
+/// of the terminal. Without it, the desktop clipboard is used if it's available.
+pub const CLIPBOARD_ENV: &str = "RIPASSO_CLIPBOARD";
+
+/// The terminal that the OSC 52 sequences are written to. It's set when ripasso starts, so that
+/// the clipboard helper, which runs without a terminal of its own, can clear the clipboard after
+/// ripasso has exited.
+const TTY_ENV: &str = "RIPASSO_OSC52_TTY";
+
+lazy_static! {
+    /// The callback sink of the running UI, see `set_ui_sink`.
+    static ref UI_SINK: Mutex<Option<CbSink>> = Mutex::new(None);
+}
+
+/// While the UI runs, the sequences are written from the UI thread between two redraws, so that
+/// they don't end up in the middle of the escape sequences that draw the screen. Set it before
+/// the UI starts, and set it to `None` when it has stopped.
+pub fn set_ui_sink(sink: Option<CbSink>) {
+    *UI_SINK.lock().unwrap() = sink;
+}
+
+/// The clipboard of the terminal, written with the OSC 52 escape sequence. This works over SSH,
+/// as long as the local terminal supports OSC 52.
+///
+/// Inside tmux the sequence is wrapped in a passthrough sequence, which tmux 3.3 and later only
+/// forwards with `set -g allow-passthrough on`. The terminal clipboard can't be read, so the
+/// previous content can't be restored.
+pub struct Osc52Clipboard {
+    tty: PathBuf,
+    tmux: bool,
+}
+
+/// Returns the terminal that ripasso runs in.
+fn terminal_path() -> PathBuf {
+    if let Some(tty) = std::env::var_os(TTY_ENV) {
+        return PathBuf::from(tty);
+    }
+    // the controlling terminal has a stable name that the helper can open, unlike /dev/tty
+    match std::fs::read_link("/proc/self/fd/0") {
+        Ok(path) if path.starts_with("/dev/") => path,
+        _ => PathBuf::from("/dev/tty"),
+    }
+}
+
+impl Osc52Clipboard {
+    pub fn new() -> Osc52Clipboard {
+        Osc52Clipboard {
+            tty: terminal_path(),
+            tmux: std::env::var_os("TMUX").is_some(),
+        }
+    }
+}
+
+fn write_to_terminal(tty: &Path, sequence: &str) -> Result<(), pass::Error> {
+    let mut tty = OpenOptions::new().write(true).open(tty)?;
+    tty.write_all(sequence.as_bytes())?;
+    tty.flush()?;
+    Ok(())
+}
+
+/// Returns the escape sequence that puts `contents` on the clipboard.
+fn sequence(contents: &str, tmux: bool) -> String {
+    let osc = format!("\x1b]52;c;{}\x07", base64::encode(contents));
+    if !tmux {
+        return osc;
+    }
+    // tmux passes on the content of a DCS sequence, with the escape characters doubled
+    return format!("\x1bPtmux;{}\x1b\\", osc.replace('\x1b', "\x1b\x1b"));
+}
+
+impl ClipboardBackend for Osc52Clipboard {
+    fn get_contents(&mut self) -> Result<String, pass::Error> {
+        Err(pass::Error::Generic("the terminal clipboard can't be read"))
+    }
+
+    fn set_contents(&mut self, contents: &str) -> Result<(), pass::Error> {
+        let sequence = SecretString::new(sequence(contents, self.tmux));
+
+        if let Some(sink) = &*UI_SINK.lock().unwrap() {
+            let tty = self.tty.clone();
+            let sequence = sequence.clone();
+            let res = sink.send(Box::new(move |ui: &mut Cursive| {
+                if let Err(err) = write_to_terminal(&tty, &sequence) {
+                    helpers::errorbox(ui, &err);
+                }
+            }));
+            // if the UI has stopped, the sequence is written directly instead
+            if res.is_ok() {
+                return Ok(());
+            }
+        }
+
+        return write_to_terminal(&self.tty, &sequence);
+    }
+
+    fn is_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Returns the clipboard chosen with `CLIPBOARD_ENV`. Without a choice, the desktop clipboard is
+/// used when it can be reached, and otherwise the terminal clipboard, for example over SSH.
+pub fn clipboard_from_env() -> BackendFactory {
+    let choice = std::env::var(CLIPBOARD_ENV).unwrap_or_default();
+    let use_osc52 = match choice.as_str() {
+        "osc52" => true,
+        "system" => false,
+        _ => SystemClipboard::new().is_err(),
+    };
+
+    // the clipboard helper process inherits the choice, so that it doesn't have to probe again
+    if !use_osc52 {
+        std::env::set_var(CLIPBOARD_ENV, "system");
+        return ripasso::clipboard::system_clipboard();
+    }
+    std::env::set_var(CLIPBOARD_ENV, "osc52");
+    std::env::set_var(TTY_ENV, terminal_path());
+
+    return Arc::new(|| -> Result<Box<dyn ClipboardBackend>, pass::Error> { Ok(Box::new(Osc52Clipboard::new())) });
+}
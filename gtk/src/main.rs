@@ -37,10 +37,10 @@ fn main() {
     let repo_opt = Arc::new(Some(Mutex::new(git2::Repository::open(pass::password_dir(password_store_dir.clone()).unwrap()).unwrap())));
 
     // Load and watch all the passwords in the background
-    let (password_rx, passwords) = match pass::watch(repo_opt.clone(), password_store_dir) {
+    let (password_rx, passwords, watch_handle) = match pass::watch(repo_opt.clone(), password_store_dir) {
         Ok(t) => t,
         Err(e) => {
-            eprintln!("Error: {:?}", e);
+            eprintln!("Error: {}", e);
             process::exit(0x01);
         }
     };
@@ -94,12 +94,18 @@ fn main() {
 
     window.show_all();
     gtk::idle_add(move || {
-        if password_rx.try_recv().is_ok() {
+        // redraw once for all the events that are waiting, the metadata of the entries is filled
+        // in one event per entry
+        if password_rx.try_iter().count() > 0 {
             receive();
         };
         glib::Continue(true)
     });
     gtk::main();
+
+    if let Err(e) = watch_handle.join() {
+        eprintln!("Error: {}", e);
+    }
 }
 
 fn results(passwords: &pass::PasswordList, query: &str) -> ListStore {
@@ -15,7 +15,6 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-extern crate clipboard;
 extern crate qml;
 extern crate ripasso;
 
@@ -24,19 +23,28 @@ use self::qml::*;
 use pass::PasswordEntry;
 use ripasso::pass;
 use std::sync::{Arc, Mutex};
-use std::thread;
 use std::time::Duration;
 
-use self::clipboard::{ClipboardContext, ClipboardProvider};
+use ripasso::clipboard;
 
 use std::panic;
 
+/// The metadata column of an entry, which is filled in by the background loading of the git
+/// history. The list shows the current state every time it's queried.
+fn entry_meta(p: &PasswordEntry) -> String {
+    match (&p.committed_by, &p.updated) {
+        (Some(by), Some(updated)) => format!("{} {}", updated.format("%Y-%m-%d"), by),
+        _ => p.meta.clone(),
+    }
+}
+
 // UI state
 pub struct UI {
     all_passwords: Arc<Mutex<Vec<PasswordEntry>>>,
     current_passwords: Vec<PasswordEntry>,
     password: Box<QPasswordView>,
     passwords: QPasswordEntry,
+    clipboard: clipboard::ClipboardService,
 }
 
 impl UI {
@@ -54,7 +62,7 @@ impl UI {
             self.current_passwords
                 .clone()
                 .into_iter()
-                .map(|p| (p.name.clone(), p.meta.clone()))
+                .map(|p| (p.name.clone(), entry_meta(&p)))
                 .collect(),
         );
         None
@@ -68,28 +76,22 @@ impl UI {
         if self.current_passwords.is_empty() {// Exit fun if we have no passwords to copy
             return None;
         }
-        // Open password file
-        let password = self.get_password(i).password().unwrap();
-
-        // Copy password to clipboard
-        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-        ctx.set_contents(password.to_owned()).unwrap();
-        println!("password copied to clipboard");
-
-        thread::spawn(move || {
-            thread::sleep(Duration::new(5, 0));
-            let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-            ctx.set_contents("".into()).unwrap();
-            println!("clipoard cleared");
-        });
+        // Open password file and copy the password to the clipboard, it's removed again after
+        // the timeout. The view doesn't show the countdown, so the events are ignored.
+        let res = self.get_password(i).password().and_then(|password| self.clipboard.copy(&password));
+        match res {
+            Ok(_) => println!("password copied to clipboard"),
+            Err(err) => println!("failed to copy the password: {}", err),
+        }
         None
     }
     pub fn select(&mut self, i: i32) -> Option<&QVariant> {
         println!("select: {}", i);
         if !self.current_passwords.is_empty() { // Select notihng if passwords list is empty
             let pass = self.get_password(i);
+            let meta = entry_meta(&pass);
             self.password.set_name(pass.name);
-            self.password.set_meta(pass.meta);
+            self.password.set_meta(meta);
         }
         None
     }
@@ -152,6 +154,8 @@ Q_LISTMODEL!(
 );
 
 fn main() {
+    clipboard::run_helper_if_requested(&clipboard::system_clipboard());
+
     panic::set_hook(Box::new(|panic_info| {
         if let Some(location) = panic_info.location() {
             println!(
@@ -177,7 +181,7 @@ fn main() {
     let repo_opt = Arc::new(Some(Mutex::new(git2::Repository::open(pass::password_dir(password_store_dir.clone()).unwrap()).unwrap())));
 
     // Load and watch all the passwords in the background
-    let (_, passwords) = pass::watch(repo_opt.clone(), password_store_dir).expect("error");
+    let (_, passwords, watch_handle) = pass::watch(repo_opt.clone(), password_store_dir).expect("error");
 
     // Set up all the UI stuff
     let mut engine = QmlEngine::new();
@@ -187,6 +191,10 @@ fn main() {
             all_passwords: passwords.clone(),
             current_passwords: Vec::<PasswordEntry>::new(),
             passwords: QPasswordEntry::new(),
+            clipboard: clipboard::ClipboardService::new(clipboard::system_clipboard(), clipboard::ClipboardOptions {
+                timeout: Duration::from_secs(5),
+                ..clipboard::ClipboardOptions::default()
+            }),
             password: QPasswordView::new(
                 PasswordView,
                 true,
@@ -205,4 +213,12 @@ fn main() {
     engine.set_and_store_property("password", password.get_qobj());
     engine.load_file("res/main.qml");
     engine.exec();
+
+    if let Err(e) = ui.clipboard.handoff() {
+        eprintln!("Error: {}", e);
+    }
+
+    if let Err(e) = watch_handle.join() {
+        eprintln!("Error: {}", e);
+    }
 }
@@ -0,0 +1,209 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::prelude::*;
+
+use crate::pass::PasswordEntry;
+use crate::strength;
+
+/// The limits used to decide if a password is weak or stale.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditOptions {
+    /// Passwords shorter than this are weak.
+    pub min_length: usize,
+    /// Passwords with a lower strength score than this are weak, see `strength::Strength::score`.
+    pub min_score: u8,
+    /// Passwords that haven't been changed for longer than this are stale.
+    pub max_age: chrono::Duration,
+}
+
+impl Default for AuditOptions {
+    fn default() -> AuditOptions {
+        AuditOptions {
+            min_length: 12,
+            min_score: 3,
+            max_age: chrono::Duration::days(365),
+        }
+    }
+}
+
+/// The reasons a password is weak.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Weakness {
+    /// The password is shorter than `AuditOptions::min_length`.
+    Short {
+        /// The length of the password.
+        length: usize,
+    },
+    /// The password has a lower strength score than `AuditOptions::min_score`.
+    LowScore {
+        /// The strength score of the password.
+        score: u8,
+    },
+    /// Most of the password is a single common password or dictionary word.
+    Dictionary,
+}
+
+/// A password that is weak.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeakPassword {
+    /// The name of the entry.
+    pub name: String,
+    /// Why the password is weak.
+    pub weaknesses: Vec<Weakness>,
+}
+
+/// A password that hasn't been changed for a long time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StalePassword {
+    /// The name of the entry.
+    pub name: String,
+    /// When the entry was last changed, according to git.
+    pub updated: DateTime<Local>,
+}
+
+/// The result of an audit of the password store.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuditReport {
+    /// Groups of entries that have the same password, each group is sorted by name.
+    pub reused: Vec<Vec<String>>,
+    /// Entries with weak passwords.
+    pub weak: Vec<WeakPassword>,
+    /// Entries that haven't been changed for a long time, oldest first.
+    pub stale: Vec<StalePassword>,
+    /// Entries that couldn't be decrypted, with the error.
+    pub failed: Vec<(String, String)>,
+    /// If the audit was cancelled before all entries were checked.
+    pub cancelled: bool,
+}
+
+/// Reports how far an audit has come.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditProgress {
+    /// The name of the entry that was just checked.
+    pub name: String,
+    /// The number of entries checked so far.
+    pub done: usize,
+    /// The number of entries to check.
+    pub total: usize,
+}
+
+fn weaknesses(password: &str, options: &AuditOptions) -> Vec<Weakness> {
+    let mut weaknesses = vec![];
+
+    let length = password.chars().count();
+    if length < options.min_length {
+        weaknesses.push(Weakness::Short { length });
+    }
+
+    let strength = strength::estimate(password);
+    if strength.score < options.min_score {
+        weaknesses.push(Weakness::LowScore { score: strength.score });
+    }
+
+    // a single word with some decoration, like Summer2019!, but not a passphrase of several words
+    let longest_word = strength.matches.iter()
+        .filter(|m| match m.pattern {
+            strength::Pattern::Dictionary { .. } => true,
+            _ => false,
+        })
+        .map(|m| m.token.chars().count())
+        .max()
+        .unwrap_or(0);
+    if longest_word * 2 > length {
+        weaknesses.push(Weakness::Dictionary);
+    }
+
+    return weaknesses;
+}
+
+/// Decrypts every entry and reports reused, weak and stale passwords. The plain text passwords
+/// are dropped as soon as they are checked, only a hash that is keyed with a random key for this
+/// run is kept to find reused passwords.
+///
+/// `progress` is called after every entry, and the audit stops early with a partial report if
+/// `cancel` becomes true.
+pub fn audit(entries: &Vec<PasswordEntry>, options: &AuditOptions, progress: &mut dyn FnMut(AuditProgress), cancel: &AtomicBool) -> AuditReport {
+    let mut report = AuditReport::default();
+    let hasher = RandomState::new();
+    let mut by_hash: HashMap<u64, Vec<String>> = HashMap::new();
+
+    let stale_before = Local::now() - options.max_age;
+
+    for (i, entry) in entries.iter().enumerate() {
+        if cancel.load(Ordering::SeqCst) {
+            report.cancelled = true;
+            break;
+        }
+
+        match entry.password() {
+            Ok(password) => {
+                if !password.is_empty() {
+                    let mut h = hasher.build_hasher();
+                    password.expose_secret().hash(&mut h);
+                    by_hash.entry(h.finish()).or_insert_with(Vec::new).push(entry.name.clone());
+                }
+
+                let weaknesses = weaknesses(&password, options);
+                if !weaknesses.is_empty() {
+                    report.weak.push(WeakPassword {
+                        name: entry.name.clone(),
+                        weaknesses,
+                    });
+                }
+            },
+            Err(err) => report.failed.push((entry.name.clone(), err.to_string())),
+        }
+
+        if let Some(updated) = entry.updated {
+            if updated < stale_before {
+                report.stale.push(StalePassword {
+                    name: entry.name.clone(),
+                    updated,
+                });
+            }
+        }
+
+        progress(AuditProgress {
+            name: entry.name.clone(),
+            done: i + 1,
+            total: entries.len(),
+        });
+    }
+
+    let mut reused: Vec<Vec<String>> = by_hash.into_iter()
+        .map(|(_, names)| names)
+        .filter(|names| names.len() > 1)
+        .collect();
+    for names in reused.iter_mut() {
+        names.sort();
+    }
+    reused.sort();
+    report.reused = reused;
+
+    report.stale.sort_by(|a, b| a.updated.cmp(&b.updated));
+
+    return report;
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,22 @@
+use super::*;
+
+#[test]
+fn weak_passwords_are_reported_with_reasons() {
+    let options = AuditOptions::default();
+
+    assert_eq!(weaknesses("password", &options), vec![Weakness::Short { length: 8 }, Weakness::LowScore { score: 0 }, Weakness::Dictionary]);
+    assert_eq!(weaknesses("Sunshine2019!", &options), vec![Weakness::LowScore { score: 1 }, Weakness::Dictionary]);
+    assert_eq!(weaknesses("abacusabdomenabide", &options), vec![]);
+    assert_eq!(weaknesses("x8#Kq2!vR9$mTz", &options), vec![]);
+}
+
+#[test]
+fn empty_store_gives_empty_report() {
+    let cancel = AtomicBool::new(false);
+    let mut calls = 0;
+
+    let report = audit(&vec![], &AuditOptions::default(), &mut |_| calls += 1, &cancel);
+
+    assert_eq!(report, AuditReport::default());
+    assert_eq!(calls, 0);
+}
@@ -0,0 +1,378 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path;
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+
+use crate::pass::{password_dir, resolve_encryption_key, resolve_key, same_key_id, write_atomically, Error};
+use crate::policy::POLICY_FILE_NAME;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The name of the manifest in the archive.
+pub const MANIFEST_NAME: &str = "MANIFEST";
+/// The name of the detached, armored signature of the manifest in the archive.
+pub const SIGNATURE_NAME: &str = "MANIFEST.asc";
+/// The folder in the archive that contains the files of the store.
+const STORE_FOLDER: &str = "store";
+/// The first line of the manifest, so that the format can change later.
+const MANIFEST_HEADER: &str = "ripasso backup 1";
+
+/// What to put in a backup, and how to protect it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BackupOptions {
+    /// Also back up the `.git` folder, with the history of the store.
+    pub include_git: bool,
+    /// Encrypt the archive to the key with this id, fingerprint or email address.
+    pub recovery_key: Option<String>,
+    /// Sign the manifest with the secret key with this id or fingerprint, instead of the default
+    /// key of gpg.
+    pub signing_key: Option<String>,
+}
+
+/// A file in the manifest of a backup.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ManifestEntry {
+    /// The path of the file, relative to the store and with `/` between the folders.
+    pub path: String,
+    /// The hex encoded SHA-256 hash of the content.
+    pub sha256: String,
+}
+
+/// The list of files in a backup together with their hashes. It's written in the format of
+/// `sha256sum`, so the restored files can also be checked by hand.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Manifest {
+    /// The files, ordered by path.
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Returns the hex encoded SHA-256 hash of `data`.
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+impl Manifest {
+    /// Creates the manifest of the files, which are paths relative to the store and their content.
+    pub fn for_files(files: &Vec<(String, Vec<u8>)>) -> Manifest {
+        let mut entries: Vec<ManifestEntry> = files.iter()
+            .map(|(path, content)| ManifestEntry { path: path.clone(), sha256: sha256_hex(content) })
+            .collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        return Manifest { entries };
+    }
+
+    /// Writes the manifest in the format it has in the archive.
+    pub fn render(&self) -> String {
+        let mut out = format!("{}\n", MANIFEST_HEADER);
+        for e in &self.entries {
+            out.push_str(&format!("{}  {}\n", e.sha256, e.path));
+        }
+        return out;
+    }
+
+    /// Reads a manifest from the archive.
+    pub fn parse(contents: &str) -> Result<Manifest> {
+        let mut lines = contents.lines();
+        if lines.next() != Some(MANIFEST_HEADER) {
+            return Err(Error::Generic("the backup manifest has an unknown format"));
+        }
+
+        let mut entries = vec![];
+        for line in lines.filter(|l| !l.is_empty()) {
+            let mut parts = line.splitn(2, "  ");
+            let sha256 = parts.next().unwrap();
+            let path = match parts.next() {
+                Some(p) if sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit()) => p,
+                _ => return Err(Error::GenericDyn(format!("malformed line in the backup manifest: {}", line))),
+            };
+            entries.push(ManifestEntry { path: path.to_string(), sha256: sha256.to_lowercase() });
+        }
+
+        return Ok(Manifest { entries });
+    }
+
+    /// Checks that `files` are exactly the files in the manifest, with the same content.
+    pub fn verify(&self, files: &Vec<(String, Vec<u8>)>) -> Result<()> {
+        let actual = Manifest::for_files(files);
+
+        for e in &self.entries {
+            match actual.entries.iter().find(|a| a.path == e.path) {
+                None => return Err(Error::GenericDyn(format!("{} is in the backup manifest but missing from the archive", e.path))),
+                Some(a) if a.sha256 != e.sha256 => return Err(Error::GenericDyn(format!("{} doesn't match the hash in the backup manifest", e.path))),
+                Some(_) => {},
+            }
+        }
+        for a in &actual.entries {
+            if !self.entries.iter().any(|e| e.path == a.path) {
+                return Err(Error::GenericDyn(format!("{} is in the archive but not in the backup manifest", a.path)));
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+fn should_back_up(relative: &path::Path, include_git: bool) -> bool {
+    let first = relative.components().next().map(|c| c.as_os_str().to_string_lossy().into_owned()).unwrap_or_default();
+    if first == ".git" {
+        return include_git;
+    }
+    if first == ".public-keys" {
+        return true;
+    }
+
+    let name = relative.file_name().unwrap_or_default().to_string_lossy();
+    return name == ".gpg-id" || name == POLICY_FILE_NAME || (name.ends_with(".gpg") && !name.starts_with('.'));
+}
+
+fn collect_files(dir: &path::Path, folder: &path::Path, include_git: bool, files: &mut Vec<(String, Vec<u8>)>) -> Result<()> {
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        let metadata = fs::symlink_metadata(&path)?;
+        let relative = path.strip_prefix(dir).unwrap();
+
+        if metadata.is_dir() {
+            if relative != path::Path::new(".git") || include_git {
+                collect_files(dir, &path, include_git, files)?;
+            }
+        } else if metadata.is_file() && should_back_up(relative, include_git) {
+            let name: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+            files.push((name.join("/"), fs::read(&path)?));
+        }
+    }
+
+    return Ok(());
+}
+
+/// Reads the files of the store at `dir` that belong in a backup: the password files, the
+/// `.gpg-id` and policy files, the `.public-keys` folder and, if `include_git` is set, the
+/// `.git` folder. The paths are relative to the store, with `/` between the folders.
+pub(crate) fn files_to_back_up(dir: &path::Path, include_git: bool) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut files = vec![];
+    collect_files(dir, dir, include_git, &mut files)?;
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    return Ok(files);
+}
+
+fn append(builder: &mut tar::Builder<Vec<u8>>, path: &str, content: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o600);
+    builder.append_data(&mut header, path, content)?;
+
+    return Ok(());
+}
+
+/// Writes the manifest, its signature and the files to a tar archive.
+pub(crate) fn pack(manifest: &Manifest, signature: Option<&[u8]>, files: &Vec<(String, Vec<u8>)>) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(vec![]);
+
+    append(&mut builder, MANIFEST_NAME, manifest.render().as_bytes())?;
+    if let Some(signature) = signature {
+        append(&mut builder, SIGNATURE_NAME, signature)?;
+    }
+    for (path, content) in files {
+        append(&mut builder, &format!("{}/{}", STORE_FOLDER, path), content)?;
+    }
+
+    return Ok(builder.into_inner()?);
+}
+
+/// The content of a backup archive, after the files have been checked against the manifest.
+pub(crate) struct Unpacked {
+    pub manifest: Manifest,
+    pub manifest_text: String,
+    pub signature: Option<Vec<u8>>,
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+/// Reads a tar archive made by `pack` and checks the files against the manifest. The signature
+/// isn't checked.
+pub(crate) fn unpack_verified(archive: &[u8]) -> Result<Unpacked> {
+    let mut manifest_text = None;
+    let mut signature = None;
+    let mut files = vec![];
+
+    let mut archive = tar::Archive::new(archive);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let mut content = vec![];
+        entry.read_to_end(&mut content)?;
+
+        if path == MANIFEST_NAME {
+            manifest_text = Some(String::from_utf8(content)?);
+        } else if path == SIGNATURE_NAME {
+            signature = Some(content);
+        } else if path.starts_with(&format!("{}/", STORE_FOLDER)) {
+            files.push((path[STORE_FOLDER.len() + 1..].to_string(), content));
+        } else {
+            return Err(Error::GenericDyn(format!("unexpected file {} in the backup", path)));
+        }
+    }
+
+    let manifest_text = match manifest_text {
+        Some(m) => m,
+        None => return Err(Error::Generic("the backup has no manifest")),
+    };
+    let manifest = Manifest::parse(&manifest_text)?;
+    manifest.verify(&files)?;
+
+    return Ok(Unpacked { manifest, manifest_text, signature, files });
+}
+
+/// Writes the files to a new store at `target`, which must not exist or be an empty folder.
+pub(crate) fn write_store(target: &path::Path, files: &Vec<(String, Vec<u8>)>) -> Result<()> {
+    if target.exists() && fs::read_dir(target)?.next().is_some() {
+        return Err(Error::GenericDyn(format!("{} isn't empty, choose a new folder to restore to", target.display())));
+    }
+
+    // check every path before anything is written
+    for (name, _) in files {
+        let relative = path::Path::new(name);
+        if name.is_empty() || !relative.components().all(|c| match c {
+            path::Component::Normal(_) => true,
+            _ => false,
+        }) {
+            return Err(Error::GenericDyn(format!("the backup contains an unsafe path: {}", name)));
+        }
+    }
+
+    fs::create_dir_all(target)?;
+    for (name, content) in files {
+        let path = target.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_atomically(&path, content)?;
+    }
+
+    return Ok(());
+}
+
+/// Makes a backup of the store: a tar archive with the files, a manifest with their hashes and a
+/// signature of the manifest, optionally encrypted to a recovery key. The archive is written to
+/// `output` and the manifest is returned.
+///
+/// File permissions aren't kept, restored files get the default permissions for new files.
+pub fn create_backup(password_store_dir: Arc<Option<String>>, options: &BackupOptions, output: &mut dyn Write) -> Result<Manifest> {
+    let dir = password_dir(password_store_dir)?;
+
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    let recovery_key = match &options.recovery_key {
+        Some(id) => Some(resolve_encryption_key(&mut ctx, id)?),
+        None => None,
+    };
+    if let Some(id) = &options.signing_key {
+        let key = ctx.get_secret_key(id.clone()).map_err(|err| match err.code() {
+            code if code == gpgme::Error::EOF.code() => Error::KeyNotFound { key_id: id.clone() },
+            _ => Error::GPG(err),
+        })?;
+        ctx.add_signer(&key)?;
+    }
+
+    let files = files_to_back_up(&dir, options.include_git)?;
+    let manifest = Manifest::for_files(&files);
+
+    ctx.set_armor(true);
+    let mut signature = Vec::new();
+    ctx.sign_detached(manifest.render(), &mut signature)?;
+
+    let archive = pack(&manifest, Some(&signature), &files)?;
+
+    match recovery_key {
+        Some(key) => {
+            ctx.set_armor(false);
+            let mut ciphertext = Vec::new();
+            ctx.encrypt(&vec![key], archive, &mut ciphertext)?;
+            output.write_all(&ciphertext)?;
+        },
+        None => output.write_all(&archive)?,
+    }
+
+    return Ok(manifest);
+}
+
+fn is_tar(data: &[u8]) -> bool {
+    data.len() > 262 && &data[257..262] == b"ustar"
+}
+
+/// Checks that the manifest is signed by `signer`, and only by it. Any other key in the keyring
+/// could have signed a tampered archive, so a valid signature alone isn't enough.
+fn verify_signature(ctx: &mut gpgme::Context, manifest_text: &str, signature: &[u8], signer: &gpgme::Key) -> Result<()> {
+    let result = ctx.verify_detached(signature, manifest_text)?;
+
+    let mut signatures = result.signatures().peekable();
+    if signatures.peek().is_none() {
+        return Err(Error::Generic("the backup manifest isn't signed"));
+    }
+    for sig in signatures {
+        let fingerprint = sig.fingerprint().unwrap_or("an unknown key");
+        let by_signer = signer.subkeys().any(|k| k.fingerprint().map(|f| same_key_id(f, fingerprint)).unwrap_or(false));
+        if !by_signer {
+            return Err(Error::GenericDyn(format!("the backup manifest is signed by {}, not by the expected key", fingerprint)));
+        }
+        if sig.status().is_err() || sig.summary().contains(gpgme::SignatureSummary::RED) {
+            return Err(Error::GenericDyn(format!("the signature of the backup manifest by {} isn't valid", fingerprint)));
+        }
+    }
+
+    return Ok(());
+}
+
+/// Restores a backup made by `create_backup` to a new store at `target`. The archive is
+/// decrypted if needed, and nothing is written unless the manifest is signed by the key with the
+/// id, fingerprint or email address `signer` and every file matches its hash. Returns the
+/// manifest.
+pub fn restore_backup(backup: &[u8], target: &path::Path, signer: &str) -> Result<Manifest> {
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    let signer_key = match resolve_key(&mut ctx, signer)? {
+        Some(key) => key,
+        None => return Err(Error::KeyNotFound { key_id: signer.to_string() }),
+    };
+
+    let archive = if is_tar(backup) {
+        backup.to_vec()
+    } else {
+        let mut plaintext = Vec::new();
+        ctx.decrypt(backup, &mut plaintext)?;
+        plaintext
+    };
+
+    let unpacked = unpack_verified(&archive)?;
+    match &unpacked.signature {
+        Some(signature) => verify_signature(&mut ctx, &unpacked.manifest_text, signature, &signer_key)?,
+        None => return Err(Error::Generic("the backup manifest isn't signed")),
+    }
+
+    write_store(target, &unpacked.files)?;
+
+    return Ok(unpacked.manifest);
+}
+
+#[cfg(test)]
+mod test;
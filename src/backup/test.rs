@@ -0,0 +1,101 @@
+use super::*;
+
+use crate::pass::TemporaryKeyring;
+use crate::test_helpers::{testres, unpack_tar_gz};
+
+const BACKUP_ONE: &str = "B1559665FEE120EA7FB2D7636C322BFF2B052550";
+const BACKUP_TWO: &str = "6E5C8953587669918F19091915C66B2A547F78D1";
+
+#[test]
+fn files_to_back_up_skips_git_unless_asked() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = unpack_tar_gz(dir.path(), "populate_password_list_small_repo.tar.gz").unwrap();
+
+    let without_git: Vec<String> = files_to_back_up(&store, false).unwrap().into_iter().map(|(p, _)| p).collect();
+    assert_eq!(without_git, vec![".gpg-id".to_string(), "test.gpg".to_string()]);
+
+    let with_git = files_to_back_up(&store, true).unwrap();
+    assert!(with_git.iter().any(|(p, _)| p == ".git/HEAD"));
+    assert!(with_git.iter().any(|(p, _)| p == "test.gpg"));
+}
+
+#[test]
+fn backup_round_trip_rebuilds_store() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = unpack_tar_gz(dir.path(), "populate_password_list_small_repo.tar.gz").unwrap();
+
+    let files = files_to_back_up(&store, true).unwrap();
+    let manifest = Manifest::for_files(&files);
+    let archive = pack(&manifest, Some(b"signature"), &files).unwrap();
+
+    let unpacked = unpack_verified(&archive).unwrap();
+    assert_eq!(unpacked.manifest, manifest);
+    assert_eq!(unpacked.signature, Some(b"signature".to_vec()));
+
+    let target = dir.path().join("restored");
+    write_store(&target, &unpacked.files).unwrap();
+
+    for (path, content) in &files {
+        assert_eq!(&fs::read(target.join(path)).unwrap(), content);
+    }
+    git2::Repository::open(&target).unwrap();
+}
+
+#[test]
+fn unpack_verified_rejects_tampered_files() {
+    let files = vec![("test.gpg".to_string(), b"ciphertext".to_vec())];
+    let manifest = Manifest::for_files(&files);
+
+    let tampered = vec![("test.gpg".to_string(), b"different".to_vec())];
+    let archive = pack(&manifest, None, &tampered).unwrap();
+    assert!(unpack_verified(&archive).is_err());
+
+    let extra = vec![("test.gpg".to_string(), b"ciphertext".to_vec()), ("other.gpg".to_string(), b"x".to_vec())];
+    let archive = pack(&manifest, None, &extra).unwrap();
+    assert!(unpack_verified(&archive).is_err());
+}
+
+#[test]
+fn manifest_round_trip() {
+    let files = vec![("team/db server.gpg".to_string(), b"a".to_vec()), (".gpg-id".to_string(), b"b".to_vec())];
+    let manifest = Manifest::for_files(&files);
+
+    assert_eq!(manifest.entries[0].path, ".gpg-id");
+    assert_eq!(manifest.entries[1].sha256, "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb");
+    assert_eq!(Manifest::parse(&manifest.render()).unwrap(), manifest);
+    assert!(Manifest::parse("something else\n").is_err());
+}
+
+#[test]
+fn write_store_refuses_unsafe_paths_and_non_empty_targets() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let escaping = vec![("../outside.gpg".to_string(), b"x".to_vec())];
+    assert!(write_store(&dir.path().join("a"), &escaping).is_err());
+    assert!(!dir.path().join("outside.gpg").exists());
+
+    fs::write(dir.path().join("existing"), b"x").unwrap();
+    let files = vec![("test.gpg".to_string(), b"x".to_vec())];
+    assert!(write_store(dir.path(), &files).is_err());
+}
+
+#[test]
+fn verify_signature_only_accepts_the_expected_signer() {
+    let mut keyring = TemporaryKeyring::new().unwrap();
+    for name in &["backup-one.secret.asc", "backup-two.secret.asc"] {
+        keyring.ctx.import(fs::read(testres(name)).unwrap()).unwrap();
+    }
+    let one = keyring.ctx.get_key(BACKUP_ONE).unwrap();
+    let two = keyring.ctx.get_secret_key(BACKUP_TWO).unwrap();
+
+    let manifest_text = Manifest::for_files(&vec![("test.gpg".to_string(), b"ciphertext".to_vec())]).render();
+    keyring.ctx.set_armor(true);
+    keyring.ctx.add_signer(&two).unwrap();
+    let mut signature = Vec::new();
+    keyring.ctx.sign_detached(manifest_text.clone(), &mut signature).unwrap();
+    keyring.ctx.clear_signers();
+
+    assert!(verify_signature(&mut keyring.ctx, &manifest_text, &signature, &two).is_ok());
+    assert!(verify_signature(&mut keyring.ctx, &manifest_text, &signature, &one).is_err());
+    assert!(verify_signature(&mut keyring.ctx, "ripasso backup 1\n", &signature, &two).is_err());
+}
@@ -0,0 +1,186 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path;
+use std::sync::atomic::AtomicBool;
+
+use crate::pass::{Error, PasswordEntry};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The length of the hash prefix that names the range files.
+const RANGE_PREFIX_LENGTH: usize = 5;
+
+/// A local copy of the Pwned Passwords SHA-1 dataset.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BreachDatabase {
+    /// One file with a `HASH:COUNT` line per password, ordered by hash.
+    SortedFile(path::PathBuf),
+    /// A folder with one file per 5 character hash prefix, named after the prefix with or
+    /// without a `.txt` extension, with `SUFFIX:COUNT` lines ordered by suffix. This is the
+    /// format of the range API.
+    RangeDirectory(path::PathBuf),
+}
+
+/// Returns the upper case hex encoded SHA-1 hash of the password.
+pub fn sha1_hex(password: &str) -> String {
+    sha1::Sha1::from(password.as_bytes()).digest().to_string().to_uppercase()
+}
+
+/// Reads the first complete line that starts at or after `position`, and returns where it
+/// starts together with its content without the line ending.
+fn line_at_or_after(file: &File, position: u64) -> Result<Option<(u64, String)>> {
+    let mut reader = BufReader::new(file);
+    let mut start = position;
+
+    if position > 0 {
+        reader.seek(SeekFrom::Start(position - 1))?;
+        let mut skipped = vec![];
+        let read = reader.read_until(b'\n', &mut skipped)?;
+        start = position - 1 + read as u64;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    return Ok(Some((start, line)));
+}
+
+fn parse_count(line: &str) -> u64 {
+    line.splitn(2, ':').nth(1).and_then(|c| c.trim().parse().ok()).unwrap_or(1)
+}
+
+/// Searches a file with lines that start with a hex key followed by `:`, ordered by key, for
+/// `key` and returns the count on the line.
+fn search_sorted_file(path: &path::Path, key: &str) -> Result<Option<u64>> {
+    let file = File::open(path)?;
+    let mut low = 0;
+    let mut high = file.metadata()?.len();
+
+    // the line with the key, if it exists, starts in low..high, and low is always at a line start
+    while low < high {
+        let middle = low + (high - low) / 2;
+        let (start, line) = match line_at_or_after(&file, middle)? {
+            Some(found) if found.0 < high => found,
+            _ => {
+                high = middle;
+                continue;
+            }
+        };
+
+        let line_key = line.splitn(2, ':').next().unwrap().trim().to_uppercase();
+        match line_key.as_str().cmp(key) {
+            Ordering::Equal => return Ok(Some(parse_count(&line))),
+            Ordering::Less => low = start + line.len() as u64,
+            Ordering::Greater => high = middle,
+        }
+    }
+
+    return Ok(None);
+}
+
+impl BreachDatabase {
+    /// Opens the dataset at `path`, a folder is read as range files, and a file as a sorted
+    /// hash file.
+    pub fn open(path: &path::Path) -> Result<BreachDatabase> {
+        if path.is_dir() {
+            return Ok(BreachDatabase::RangeDirectory(path.to_path_buf()));
+        }
+        if path.is_file() {
+            return Ok(BreachDatabase::SortedFile(path.to_path_buf()));
+        }
+        return Err(Error::GenericDyn(format!("no breached password dataset at {}", path.display())));
+    }
+
+    /// Returns how many times the password with the upper case hex encoded SHA-1 hash `hash`
+    /// has been seen in breaches, or None if it hasn't.
+    pub fn lookup_hash(&self, hash: &str) -> Result<Option<u64>> {
+        let hash = hash.to_uppercase();
+        if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::Generic("not a SHA-1 hash"));
+        }
+
+        return match self {
+            BreachDatabase::SortedFile(path) => search_sorted_file(path, &hash),
+            BreachDatabase::RangeDirectory(dir) => {
+                let (prefix, suffix) = hash.split_at(RANGE_PREFIX_LENGTH);
+                let candidates = [dir.join(prefix), dir.join(format!("{}.txt", prefix)), dir.join(prefix.to_lowercase()), dir.join(format!("{}.txt", prefix.to_lowercase()))];
+                match candidates.iter().find(|p| p.is_file()) {
+                    Some(file) => search_sorted_file(file, suffix),
+                    None => Err(Error::GenericDyn(format!("the range file for {} is missing from {}", prefix, dir.display()))),
+                }
+            },
+        };
+    }
+
+    /// Returns how many times the password has been seen in breaches, or None if it hasn't.
+    pub fn check_password(&self, password: &str) -> Result<Option<u64>> {
+        return self.lookup_hash(&sha1_hex(password));
+    }
+
+    /// Decrypts the entry and checks its password.
+    pub fn check_entry(&self, entry: &PasswordEntry) -> Result<Option<u64>> {
+        return self.check_password(&entry.password()?);
+    }
+}
+
+/// The result of checking the whole store against a breached password dataset.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BreachReport {
+    /// The entries with breached passwords, with the number of times it has been seen.
+    pub breached: Vec<(String, u64)>,
+    /// Entries that couldn't be checked, with the error.
+    pub failed: Vec<(String, String)>,
+    /// If the check was cancelled before all entries were checked.
+    pub cancelled: bool,
+}
+
+/// Checks every entry against the dataset. The plain text passwords are only kept long enough to
+/// hash them. `progress` is called with the number of checked entries after every entry, and
+/// the check stops early if `cancel` becomes true.
+pub fn check_entries(entries: &Vec<PasswordEntry>, database: &BreachDatabase, progress: &mut dyn FnMut(usize, usize), cancel: &AtomicBool) -> BreachReport {
+    let mut report = BreachReport::default();
+
+    for (i, entry) in entries.iter().enumerate() {
+        if cancel.load(std::sync::atomic::Ordering::SeqCst) {
+            report.cancelled = true;
+            break;
+        }
+
+        match database.check_entry(entry) {
+            Ok(Some(count)) => report.breached.push((entry.name.clone(), count)),
+            Ok(None) => {},
+            Err(err) => report.failed.push((entry.name.clone(), err.to_string())),
+        }
+
+        progress(i + 1, entries.len());
+    }
+
+    report.breached.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    return report;
+}
+
+#[cfg(test)]
+mod test;
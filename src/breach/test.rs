@@ -0,0 +1,45 @@
+use super::*;
+
+use std::fs;
+
+const SORTED: &str = "000000005AD76BD555C1D6D771DE417A4B87E4B4:4\r
+00000000A8DAE4228F821FB418F59826079BF368:2\r
+5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:3861493\r
+7C4A8D09CA3762AF61E59520943DC26494F8941B:24230577\r
+A9993E364706816ABA3E25717850C26C9CD0D89D:1\r
+FFFFFFFFF8F3A17F2EC2ED41F2D9C50C6A2E8D0B:7\r
+";
+
+#[test]
+fn sha1_of_password() {
+    assert_eq!(sha1_hex("password"), "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8");
+}
+
+#[test]
+fn lookup_in_sorted_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("pwned-passwords-sha1-ordered-by-hash.txt");
+    fs::write(&path, SORTED).unwrap();
+
+    let database = BreachDatabase::open(&path).unwrap();
+
+    assert_eq!(database.check_password("password").unwrap(), Some(3861493));
+    assert_eq!(database.check_password("123456").unwrap(), Some(24230577));
+    assert_eq!(database.check_password("abc").unwrap(), Some(1));
+    assert_eq!(database.lookup_hash("000000005AD76BD555C1D6D771DE417A4B87E4B4").unwrap(), Some(4));
+    assert_eq!(database.lookup_hash("ffffffffF8F3A17F2EC2ED41F2D9C50C6A2E8D0B").unwrap(), Some(7));
+    assert_eq!(database.check_password("correct horse battery staple ripasso").unwrap(), None);
+    assert_eq!(database.lookup_hash("not a hash").is_err(), true);
+}
+
+#[test]
+fn lookup_in_range_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("5BAA6"), "1D72F35E95B7F3E9B1BD6B8FA6A8F5E9E1A:2\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:3861493\n1F0A8E6C8C8A0FDC5D0B8B5B0E2F1B4CB9A:1\n").unwrap();
+
+    let database = BreachDatabase::open(dir.path()).unwrap();
+
+    assert_eq!(database.check_password("password").unwrap(), Some(3861493));
+    assert_eq!(database.lookup_hash("5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD9").unwrap(), None);
+    assert_eq!(database.check_password("123456").is_err(), true);
+}
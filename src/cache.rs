@@ -0,0 +1,201 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::fs;
+use std::path;
+
+use chrono::prelude::*;
+use git2::Oid;
+
+use crate::pass::{changed_paths, write_atomically, Error, PasswordEntry, PathChange, SignatureStatus};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The name of the cache file in the `.git` folder of the store.
+pub const CACHE_FILE_NAME: &str = "ripasso-metadata-cache";
+/// The first line of the cache file, so that the format can change later.
+const CACHE_HEADER: &str = "ripasso metadata cache 1";
+
+/// The git metadata of one password file, as it's shown in the password list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedMetadata {
+    /// The id of the blob of the file in the commit that the cache is for.
+    pub blob: Oid,
+    /// The commit that the metadata comes from.
+    pub commit: Oid,
+    /// The commit time, in seconds since the epoch.
+    pub time: i64,
+    /// The name of the committer.
+    pub committer: Option<String>,
+    /// The result of verifying the signature of the commit.
+    pub signature_status: Option<SignatureStatus>,
+}
+
+impl CachedMetadata {
+    /// Creates the password entry for the file `name` in the store at `dir`.
+    pub fn to_entry(&self, dir: &path::PathBuf, name: &str) -> PasswordEntry {
+        PasswordEntry::new(dir,
+                           &dir.join(name),
+                           Ok(Local.timestamp(self.time, 0)),
+                           self.committer.clone().ok_or(Error::Generic("missing committer name")),
+                           self.signature_status.clone().ok_or(Error::Generic("missing signature")))
+    }
+}
+
+/// The git metadata of the password files at a commit, so that the history only has to be read
+/// for files that changed since the cache was written.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetadataCache {
+    /// The commit that the cache is for, normally HEAD.
+    pub head: Option<Oid>,
+    /// The metadata by file, with paths relative to the store.
+    pub entries: HashMap<String, CachedMetadata>,
+}
+
+fn signature_to_str(status: &Option<SignatureStatus>) -> &'static str {
+    match status {
+        Some(SignatureStatus::GoodSignature) => "good",
+        Some(SignatureStatus::AlmostGoodSignature) => "almost-good",
+        Some(SignatureStatus::BadSignature) => "bad",
+        None => "-",
+    }
+}
+
+fn signature_from_str(status: &str) -> Result<Option<SignatureStatus>> {
+    match status {
+        "good" => Ok(Some(SignatureStatus::GoodSignature)),
+        "almost-good" => Ok(Some(SignatureStatus::AlmostGoodSignature)),
+        "bad" => Ok(Some(SignatureStatus::BadSignature)),
+        "-" => Ok(None),
+        _ => Err(Error::GenericDyn(format!("unknown signature status {} in the metadata cache", status))),
+    }
+}
+
+impl MetadataCache {
+    /// Returns where the cache of the repository is stored.
+    pub fn path_for(repo: &git2::Repository) -> path::PathBuf {
+        repo.path().join(CACHE_FILE_NAME)
+    }
+
+    /// Reads a cache file. It has the head commit on the first line after the header, and then
+    /// one tab separated line per file.
+    pub fn parse(contents: &str) -> Result<MetadataCache> {
+        let mut lines = contents.lines();
+        if lines.next() != Some(CACHE_HEADER) {
+            return Err(Error::Generic("the metadata cache has an unknown format"));
+        }
+        let head = match lines.next() {
+            Some(h) => Oid::from_str(h)?,
+            None => return Err(Error::Generic("the metadata cache is missing the head commit")),
+        };
+
+        let mut entries = HashMap::new();
+        for line in lines.filter(|l| !l.is_empty()) {
+            let parts: Vec<&str> = line.split('\t').collect();
+            if parts.len() != 6 {
+                return Err(Error::GenericDyn(format!("malformed line in the metadata cache: {}", line)));
+            }
+            let time = parts[3].parse::<i64>().map_err(|_| Error::GenericDyn(format!("malformed time in the metadata cache: {}", parts[3])))?;
+
+            entries.insert(parts[0].to_string(), CachedMetadata {
+                blob: Oid::from_str(parts[1])?,
+                commit: Oid::from_str(parts[2])?,
+                time,
+                committer: if parts[4].is_empty() { None } else { Some(parts[4].to_string()) },
+                signature_status: signature_from_str(parts[5])?,
+            });
+        }
+
+        return Ok(MetadataCache { head: Some(head), entries });
+    }
+
+    /// Writes the cache in the format that `parse` reads. Files with tabs or line breaks in
+    /// their paths are left out.
+    pub fn render(&self) -> String {
+        let mut out = format!("{}\n{}\n", CACHE_HEADER, self.head.map(|h| h.to_string()).unwrap_or_default());
+
+        let mut paths: Vec<&String> = self.entries.keys()
+            .filter(|p| !p.contains('\t') && !p.contains('\n'))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let meta = &self.entries[path];
+            let committer = meta.committer.clone().unwrap_or_default().replace(|c| c == '\t' || c == '\n', " ");
+            out.push_str(&format!("{}\t{}\t{}\t{}\t{}\t{}\n", path, meta.blob, meta.commit, meta.time, committer, signature_to_str(&meta.signature_status)));
+        }
+
+        return out;
+    }
+
+    /// Reads the cache of the repository and brings it up to `head`. If the cache is for an
+    /// older commit, the files that changed since then are dropped from it. If it's missing,
+    /// unreadable or for a commit that isn't an ancestor of `head`, an empty cache is returned.
+    pub fn load(repo: &git2::Repository, head: Oid) -> MetadataCache {
+        let empty = MetadataCache { head: Some(head), entries: HashMap::new() };
+
+        let mut cache = match fs::read_to_string(MetadataCache::path_for(repo)) {
+            Ok(contents) => match MetadataCache::parse(&contents) {
+                Ok(c) => c,
+                Err(err) => {
+                    warn!("ignoring the metadata cache: {}", err);
+                    return empty;
+                }
+            },
+            Err(_) => return empty,
+        };
+
+        let cached_head = match cache.head {
+            Some(h) if h == head => return cache,
+            Some(h) => h,
+            None => return empty,
+        };
+        if repo.graph_descendant_of(head, cached_head).ok() != Some(true) {
+            return empty;
+        }
+
+        let changes = match changed_paths(repo, cached_head, head) {
+            Ok(c) => c,
+            Err(_) => return empty,
+        };
+        for change in changes {
+            let paths = match change {
+                PathChange::Added(p) | PathChange::Modified(p) | PathChange::Deleted(p) => vec![p],
+                PathChange::Renamed(from, to) => vec![from, to],
+            };
+            for p in paths {
+                cache.entries.remove(&p.to_string_lossy().into_owned());
+            }
+        }
+        cache.head = Some(head);
+
+        return cache;
+    }
+
+    /// Writes the cache to the repository.
+    pub fn save(&self, repo: &git2::Repository) -> Result<()> {
+        return write_atomically(&MetadataCache::path_for(repo), self.render().as_bytes());
+    }
+
+    /// Returns the metadata of the file, if it's cached for the same content.
+    pub fn get(&self, path: &str, blob: Oid) -> Option<&CachedMetadata> {
+        self.entries.get(path).filter(|m| m.blob == blob)
+    }
+}
+
+#[cfg(test)]
+mod test;
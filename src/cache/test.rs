@@ -0,0 +1,87 @@
+use super::*;
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::pass::populate_password_list;
+use crate::test_helpers::unpack_tar_gz;
+
+fn populate(dir: &PathBuf) -> Vec<PasswordEntry> {
+    let passwords = Arc::new(Mutex::new(vec![]));
+    let repo_opt = Arc::new(Some(Mutex::new(git2::Repository::open(dir).unwrap())));
+    populate_password_list(&passwords, repo_opt, Arc::new(Some(format!("{}", dir.display())))).unwrap();
+
+    let list = passwords.lock().unwrap().clone();
+    list
+}
+
+fn summary(entries: &Vec<PasswordEntry>) -> Vec<String> {
+    entries.iter().map(|e| format!("{} {:?} {:?} {:?}", e.name, e.updated, e.committed_by, e.signature_status)).collect()
+}
+
+#[test]
+fn parse_and_render_round_trip() {
+    let mut cache = MetadataCache {
+        head: Some(Oid::from_str("ff37617c1e5c0a9cc0c2ba3fc5dd4b9a18b0e0f7").unwrap()),
+        entries: HashMap::new(),
+    };
+    cache.entries.insert("team/db.gpg".to_string(), CachedMetadata {
+        blob: Oid::from_str("0ab97d3c1e5c0a9cc0c2ba3fc5dd4b9a18b0e0f7").unwrap(),
+        commit: Oid::from_str("ff37617c1e5c0a9cc0c2ba3fc5dd4b9a18b0e0f7").unwrap(),
+        time: 1572771600,
+        committer: Some("Alexander Kjäll".to_string()),
+        signature_status: Some(SignatureStatus::AlmostGoodSignature),
+    });
+    cache.entries.insert("old.gpg".to_string(), CachedMetadata {
+        blob: Oid::from_str("0ab97d3c1e5c0a9cc0c2ba3fc5dd4b9a18b0e0f7").unwrap(),
+        commit: Oid::from_str("ff37617c1e5c0a9cc0c2ba3fc5dd4b9a18b0e0f7").unwrap(),
+        time: 0,
+        committer: None,
+        signature_status: None,
+    });
+
+    assert_eq!(MetadataCache::parse(&cache.render()).unwrap(), cache);
+    assert!(MetadataCache::parse("something else\n").is_err());
+}
+
+#[test]
+fn populate_password_list_gives_same_result_with_cache() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = unpack_tar_gz(tmp.path(), "populate_password_list_large_repo.tar.gz").unwrap();
+    let repo = git2::Repository::open(&dir).unwrap();
+
+    let uncached = populate(&dir);
+    assert_eq!(uncached.len(), 4);
+
+    let cache = MetadataCache::load(&repo, repo.head().unwrap().target().unwrap());
+    assert_eq!(cache.entries.len(), 4);
+
+    let cached = populate(&dir);
+    assert_eq!(summary(&cached), summary(&uncached));
+}
+
+#[test]
+fn load_drops_files_that_changed_since_the_cached_head() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = unpack_tar_gz(tmp.path(), "populate_password_list_large_repo.tar.gz").unwrap();
+    populate(&dir);
+
+    let repo = git2::Repository::open(&dir).unwrap();
+    std::fs::write(dir.join("1.gpg"), b"changed").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(path::Path::new("1.gpg")).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let parent = repo.find_commit(repo.head().unwrap().target().unwrap()).unwrap();
+    let signature = git2::Signature::now("Alice", "alice@example.com").unwrap();
+    let head = repo.commit(Some("HEAD"), &signature, &signature, "change", &tree, &[&parent]).unwrap();
+
+    let cache = MetadataCache::load(&repo, head);
+    assert_eq!(cache.head, Some(head));
+    assert!(!cache.entries.contains_key("1.gpg"));
+    assert_eq!(cache.entries.len(), 3);
+
+    let entries = populate(&dir);
+    let changed = entries.iter().find(|e| e.name == "1").unwrap();
+    assert_eq!(changed.committed_by, Some("Alice".to_string()));
+}
@@ -0,0 +1,425 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::io::{Read, Write};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "system-clipboard")]
+use ::clipboard::ClipboardProvider;
+use zeroize::Zeroize;
+
+use crate::pass::Error;
+use crate::secret::SecretString;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The environment variable that makes `run_helper_if_requested` turn the process into the
+/// helper that clears the clipboard after the frontend has exited.
+pub const HELPER_ENV: &str = "RIPASSO_CLIPBOARD_HELPER";
+
+/// How often the countdown is reported.
+const COUNTDOWN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A clipboard that text can be read from and written to.
+pub trait ClipboardBackend {
+    /// Returns the text on the clipboard.
+    fn get_contents(&mut self) -> Result<String>;
+
+    /// Replaces the content of the clipboard with `contents`.
+    fn set_contents(&mut self, contents: &str) -> Result<()>;
+
+    /// Returns false if the clipboard can only be written, like the clipboard of a terminal.
+    /// Secrets on such a clipboard are cleared when their time is up without checking that
+    /// they are still there, and nothing is restored.
+    fn is_readable(&self) -> bool {
+        true
+    }
+}
+
+/// Creates a connection to a clipboard. The service connects again for every operation, since
+/// the connections of most clipboards can't be moved between threads.
+pub type BackendFactory = Arc<dyn Fn() -> Result<Box<dyn ClipboardBackend>> + Send + Sync>;
+
+/// The clipboard of the desktop. It can't be reached when ripasso is built without the
+/// `system-clipboard` feature, for example for servers without a desktop.
+pub struct SystemClipboard {
+    #[cfg(feature = "system-clipboard")]
+    ctx: ::clipboard::ClipboardContext,
+}
+
+#[cfg(not(feature = "system-clipboard"))]
+const NO_SYSTEM_CLIPBOARD: &str = "ripasso was built without the system-clipboard feature";
+
+impl SystemClipboard {
+    /// Connects to the clipboard of the desktop.
+    #[cfg(feature = "system-clipboard")]
+    pub fn new() -> Result<SystemClipboard> {
+        let ctx = ::clipboard::ClipboardContext::new().map_err(|e| Error::GenericDyn(format!("{}", e)))?;
+        return Ok(SystemClipboard { ctx });
+    }
+
+    /// Connects to the clipboard of the desktop.
+    #[cfg(not(feature = "system-clipboard"))]
+    pub fn new() -> Result<SystemClipboard> {
+        return Err(Error::Generic(NO_SYSTEM_CLIPBOARD));
+    }
+}
+
+#[cfg(feature = "system-clipboard")]
+impl ClipboardBackend for SystemClipboard {
+    fn get_contents(&mut self) -> Result<String> {
+        self.ctx.get_contents().map_err(|e| Error::GenericDyn(format!("{}", e)))
+    }
+
+    fn set_contents(&mut self, contents: &str) -> Result<()> {
+        self.ctx.set_contents(contents.to_string()).map_err(|e| Error::GenericDyn(format!("{}", e)))
+    }
+}
+
+#[cfg(not(feature = "system-clipboard"))]
+impl ClipboardBackend for SystemClipboard {
+    fn get_contents(&mut self) -> Result<String> {
+        Err(Error::Generic(NO_SYSTEM_CLIPBOARD))
+    }
+
+    fn set_contents(&mut self, _contents: &str) -> Result<()> {
+        Err(Error::Generic(NO_SYSTEM_CLIPBOARD))
+    }
+}
+
+/// Returns a factory for the clipboard of the desktop.
+pub fn system_clipboard() -> BackendFactory {
+    Arc::new(|| -> Result<Box<dyn ClipboardBackend>> { Ok(Box::new(SystemClipboard::new()?)) })
+}
+
+/// Settings for how long a copied secret stays on the clipboard.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClipboardOptions {
+    /// How long the secret stays on the clipboard.
+    pub timeout: Duration,
+    /// Put back what was on the clipboard before the secret was copied, instead of leaving the
+    /// clipboard empty.
+    pub restore_previous: bool,
+}
+
+impl Default for ClipboardOptions {
+    fn default() -> ClipboardOptions {
+        ClipboardOptions {
+            // the same as pass
+            timeout: Duration::from_secs(45),
+            restore_previous: true,
+        }
+    }
+}
+
+/// What happens to a copied secret, sent on the channel returned by `ClipboardService::copy`.
+#[derive(Debug)]
+pub enum ClipboardEvent {
+    /// The secret is on the clipboard, and is removed after the duration.
+    Countdown(Duration),
+    /// The secret was removed and the clipboard is empty.
+    Cleared,
+    /// The secret was removed and the previous content of the clipboard was put back.
+    Restored,
+    /// Something else was copied after the secret, so the clipboard was left alone.
+    Replaced,
+    /// Another secret was copied, or the clearing was handed off to the helper process.
+    Superseded,
+    /// The clipboard couldn't be read or written when the secret was to be removed.
+    Error(Error),
+}
+
+/// A secret that the service has put on the clipboard.
+struct ActiveCopy {
+    secret: SecretString,
+    previous: Option<SecretString>,
+    deadline: Instant,
+    cancel: Arc<AtomicBool>,
+}
+
+/// Copies secrets to the clipboard and removes them again after a timeout.
+///
+/// The secret is only removed if it's still on the clipboard, and the previous content is put
+/// back if `ClipboardOptions::restore_previous` is set. Call `handoff` before the frontend exits,
+/// so that a secret that is still on the clipboard is removed by a helper process.
+#[derive(Clone)]
+pub struct ClipboardService {
+    backend: BackendFactory,
+    options: ClipboardOptions,
+    active: Arc<Mutex<Option<ActiveCopy>>>,
+}
+
+/// Removes `secret` from the clipboard, if it's still there. Returns the event that describes
+/// what happened.
+fn remove_secret(backend: &BackendFactory, secret: &SecretString, previous: Option<&SecretString>) -> ClipboardEvent {
+    let res = backend().and_then(|mut clipboard| {
+        if !clipboard.is_readable() {
+            return clipboard.set_contents("").map(|_| ClipboardEvent::Cleared);
+        }
+        let current = SecretString::new(clipboard.get_contents()?);
+        if current != *secret {
+            return Ok(ClipboardEvent::Replaced);
+        }
+        return match previous {
+            Some(previous) => clipboard.set_contents(previous).map(|_| ClipboardEvent::Restored),
+            None => clipboard.set_contents("").map(|_| ClipboardEvent::Cleared),
+        };
+    });
+
+    return res.unwrap_or_else(ClipboardEvent::Error);
+}
+
+/// Waits until `deadline`, sending the remaining time every `COUNTDOWN_INTERVAL`. Returns false
+/// if the wait was cancelled.
+fn count_down(deadline: Instant, cancel: &AtomicBool, events: Option<&Sender<ClipboardEvent>>) -> bool {
+    loop {
+        if cancel.load(Ordering::SeqCst) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        let remaining = deadline - now;
+        if let Some(events) = events {
+            let _ = events.send(ClipboardEvent::Countdown(remaining));
+        }
+        thread::sleep(remaining.min(COUNTDOWN_INTERVAL));
+    }
+}
+
+impl ClipboardService {
+    /// Creates a service that uses the clipboards from `backend`.
+    pub fn new(backend: BackendFactory, options: ClipboardOptions) -> ClipboardService {
+        ClipboardService {
+            backend,
+            options,
+            active: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Puts `secret` on the clipboard, and removes it after the timeout. The returned channel
+    /// gets the countdown, and then what happened to the secret.
+    ///
+    /// If a secret that the service copied earlier is still waiting to be removed, it's
+    /// replaced, and what was on the clipboard before that secret is what gets restored.
+    pub fn copy(&self, secret: &SecretString) -> Result<Receiver<ClipboardEvent>> {
+        let mut clipboard = (self.backend)()?;
+
+        let current = match self.options.restore_previous && clipboard.is_readable() {
+            true => clipboard.get_contents().ok().filter(|c| !c.is_empty()).map(SecretString::new),
+            false => None,
+        };
+
+        let mut active = self.active.lock().unwrap();
+        let previous = match active.take() {
+            Some(earlier) => {
+                earlier.cancel.store(true, Ordering::SeqCst);
+                if current.as_ref() == Some(&earlier.secret) { earlier.previous.clone() } else { current }
+            },
+            None => current,
+        };
+
+        clipboard.set_contents(secret)?;
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let deadline = Instant::now() + self.options.timeout;
+        *active = Some(ActiveCopy {
+            secret: secret.clone(),
+            previous: previous.clone(),
+            deadline,
+            cancel: cancel.clone(),
+        });
+
+        let (tx, rx) = channel();
+        let backend = self.backend.clone();
+        let shared = self.active.clone();
+        let secret = secret.clone();
+        thread::spawn(move || {
+            if !count_down(deadline, &cancel, Some(&tx)) {
+                let _ = tx.send(ClipboardEvent::Superseded);
+                return;
+            }
+
+            {
+                let mut active = shared.lock().unwrap();
+                match &*active {
+                    Some(copy) if Arc::ptr_eq(&copy.cancel, &cancel) => *active = None,
+                    _ => {
+                        let _ = tx.send(ClipboardEvent::Superseded);
+                        return;
+                    },
+                }
+            }
+
+            let _ = tx.send(remove_secret(&backend, &secret, previous.as_ref()));
+        });
+
+        return Ok(rx);
+    }
+
+    /// Returns how long the secret that was copied last stays on the clipboard, or None if
+    /// there is no secret waiting to be removed.
+    pub fn remaining(&self) -> Option<Duration> {
+        let active = self.active.lock().unwrap();
+        return active.as_ref().map(|copy| copy.deadline.saturating_duration_since(Instant::now()));
+    }
+
+    /// Hands the removal of a secret that is still waiting to be removed over to a helper
+    /// process, which is the current executable started with `HELPER_ENV` set. Frontends call
+    /// this before they exit, and `run_helper_if_requested` first in `main`.
+    ///
+    /// The secret is sent to the helper over a pipe, never on the command line or in the
+    /// environment. Returns false if there was nothing to hand over, because no secret is
+    /// waiting to be removed or something else has been copied since.
+    pub fn handoff(&self) -> Result<bool> {
+        let copy = match self.active.lock().unwrap().take() {
+            Some(copy) => copy,
+            None => return Ok(false),
+        };
+        copy.cancel.store(true, Ordering::SeqCst);
+
+        let mut clipboard = (self.backend)()?;
+        if clipboard.is_readable() && SecretString::new(clipboard.get_contents()?) != copy.secret {
+            return Ok(false);
+        }
+
+        let remaining = copy.deadline.saturating_duration_since(Instant::now());
+        let mut message = encode_handoff(remaining, &copy.secret, copy.previous.as_ref());
+
+        let mut command = process::Command::new(std::env::current_exe()?);
+        command.env(HELPER_ENV, "1")
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null());
+        detach(&mut command);
+
+        let res = command.spawn().and_then(|mut child| {
+            let mut stdin = child.stdin.take().unwrap();
+            stdin.write_all(&message)
+        });
+        message.zeroize();
+        res?;
+
+        return Ok(true);
+    }
+}
+
+/// Starts the helper in a new session, so that it isn't stopped together with the terminal of
+/// the frontend.
+#[cfg(unix)]
+fn detach(command: &mut process::Command) {
+    use std::os::unix::process::CommandExt;
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn detach(_command: &mut process::Command) {}
+
+/// What the frontend sends to the helper process.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Handoff {
+    pub remaining: Duration,
+    pub secret: SecretString,
+    pub previous: Option<SecretString>,
+}
+
+/// Encodes the handoff as a header line with the remaining milliseconds and the byte lengths of
+/// the secret and the previous content (`-` if there is none), followed by the two texts.
+pub(crate) fn encode_handoff(remaining: Duration, secret: &SecretString, previous: Option<&SecretString>) -> Vec<u8> {
+    let previous_length = previous.map(|p| p.len().to_string()).unwrap_or_else(|| "-".to_string());
+    let header = format!("{} {} {}\n", remaining.as_millis(), secret.len(), previous_length);
+
+    let mut message = Vec::with_capacity(header.len() + secret.len() + previous.map(|p| p.len()).unwrap_or(0));
+    message.extend_from_slice(header.as_bytes());
+    message.extend_from_slice(secret.as_bytes());
+    if let Some(previous) = previous {
+        message.extend_from_slice(previous.as_bytes());
+    }
+    return message;
+}
+
+pub(crate) fn decode_handoff(message: &[u8]) -> Result<Handoff> {
+    let invalid = || Error::Generic("invalid clipboard handoff");
+
+    let header_end = message.iter().position(|b| *b == b'\n').ok_or_else(invalid)?;
+    let header = std::str::from_utf8(&message[..header_end]).map_err(|_| invalid())?;
+    let parts: Vec<&str> = header.split(' ').collect();
+    if parts.len() != 3 {
+        return Err(invalid());
+    }
+    let remaining: u64 = parts[0].parse().map_err(|_| invalid())?;
+    let secret_length: usize = parts[1].parse().map_err(|_| invalid())?;
+    let previous_length: Option<usize> = match parts[2] {
+        "-" => None,
+        length => Some(length.parse().map_err(|_| invalid())?),
+    };
+
+    let body = &message[header_end + 1..];
+    if body.len() != secret_length + previous_length.unwrap_or(0) {
+        return Err(invalid());
+    }
+    let secret = SecretString::from_utf8(body[..secret_length].to_vec())?;
+    let previous = match previous_length {
+        Some(_) => Some(SecretString::from_utf8(body[secret_length..].to_vec())?),
+        None => None,
+    };
+
+    return Ok(Handoff {
+        remaining: Duration::from_millis(remaining),
+        secret,
+        previous,
+    });
+}
+
+/// If the process was started by `ClipboardService::handoff`, takes over the secret, removes it
+/// from the clipboard when its time is up and exits. Otherwise returns at once.
+///
+/// The helper puts the secret on the clipboard again, since on some desktops the content of the
+/// clipboard disappears with the process that copied it.
+pub fn run_helper_if_requested(backend: &BackendFactory) {
+    if std::env::var_os(HELPER_ENV).is_none() {
+        return;
+    }
+
+    let mut message = vec![];
+    let read = std::io::stdin().read_to_end(&mut message);
+    let handoff = read.map_err(Error::from).and_then(|_| decode_handoff(&message));
+    message.zeroize();
+
+    if let Ok(handoff) = handoff {
+        let copied = backend().and_then(|mut clipboard| clipboard.set_contents(&handoff.secret));
+        if copied.is_ok() && count_down(Instant::now() + handoff.remaining, &AtomicBool::new(false), None) {
+            remove_secret(backend, &handoff.secret, handoff.previous.as_ref());
+        }
+    }
+
+    process::exit(0);
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,159 @@
+use super::*;
+
+/// A clipboard in memory, shared by every connection from the factory.
+struct MemoryClipboard {
+    contents: Arc<Mutex<String>>,
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn get_contents(&mut self) -> Result<String> {
+        Ok(self.contents.lock().unwrap().clone())
+    }
+
+    fn set_contents(&mut self, contents: &str) -> Result<()> {
+        *self.contents.lock().unwrap() = contents.to_string();
+        Ok(())
+    }
+}
+
+/// A clipboard that can only be written, like a terminal clipboard.
+struct WriteOnlyClipboard {
+    contents: Arc<Mutex<String>>,
+}
+
+impl ClipboardBackend for WriteOnlyClipboard {
+    fn get_contents(&mut self) -> Result<String> {
+        Err(Error::Generic("the clipboard can't be read"))
+    }
+
+    fn set_contents(&mut self, contents: &str) -> Result<()> {
+        *self.contents.lock().unwrap() = contents.to_string();
+        Ok(())
+    }
+
+    fn is_readable(&self) -> bool {
+        false
+    }
+}
+
+fn memory_clipboard(initial: &str) -> (Arc<Mutex<String>>, BackendFactory) {
+    let contents = Arc::new(Mutex::new(initial.to_string()));
+    let shared = contents.clone();
+    let factory: BackendFactory = Arc::new(move || -> Result<Box<dyn ClipboardBackend>> {
+        Ok(Box::new(MemoryClipboard { contents: shared.clone() }))
+    });
+    (contents, factory)
+}
+
+fn options(timeout_ms: u64, restore_previous: bool) -> ClipboardOptions {
+    ClipboardOptions {
+        timeout: Duration::from_millis(timeout_ms),
+        restore_previous,
+    }
+}
+
+fn last_event(events: Receiver<ClipboardEvent>) -> ClipboardEvent {
+    loop {
+        match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            ClipboardEvent::Countdown(remaining) => assert_eq!(remaining <= Duration::from_secs(1), true),
+            event => return event,
+        }
+    }
+}
+
+#[test]
+fn copy_restores_previous_content_after_timeout() {
+    let (contents, factory) = memory_clipboard("a link");
+    let service = ClipboardService::new(factory, options(50, true));
+
+    let events = service.copy(&SecretString::from("hunter2")).unwrap();
+    assert_eq!(*contents.lock().unwrap(), "hunter2");
+    assert_eq!(service.remaining().is_some(), true);
+
+    match last_event(events) {
+        ClipboardEvent::Restored => {},
+        event => panic!("unexpected event {:?}", event),
+    }
+    assert_eq!(*contents.lock().unwrap(), "a link");
+    assert_eq!(service.remaining(), None);
+}
+
+#[test]
+fn copy_clears_only_when_the_secret_is_still_there() {
+    let (contents, factory) = memory_clipboard("a link");
+    let service = ClipboardService::new(factory, options(50, false));
+
+    let events = service.copy(&SecretString::from("hunter2")).unwrap();
+    match last_event(events) {
+        ClipboardEvent::Cleared => {},
+        event => panic!("unexpected event {:?}", event),
+    }
+    assert_eq!(*contents.lock().unwrap(), "");
+
+    let events = service.copy(&SecretString::from("hunter2")).unwrap();
+    *contents.lock().unwrap() = "copied by the user".to_string();
+    match last_event(events) {
+        ClipboardEvent::Replaced => {},
+        event => panic!("unexpected event {:?}", event),
+    }
+    assert_eq!(*contents.lock().unwrap(), "copied by the user");
+}
+
+#[test]
+fn second_copy_supersedes_the_first_and_keeps_the_original_content() {
+    let (contents, factory) = memory_clipboard("a link");
+    let service = ClipboardService::new(factory, options(200, true));
+
+    let first = service.copy(&SecretString::from("hunter2")).unwrap();
+    let second = service.copy(&SecretString::from("correct horse")).unwrap();
+
+    loop {
+        match first.recv_timeout(Duration::from_secs(5)).unwrap() {
+            ClipboardEvent::Countdown(_) => {},
+            ClipboardEvent::Superseded => break,
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+    match last_event(second) {
+        ClipboardEvent::Restored => {},
+        event => panic!("unexpected event {:?}", event),
+    }
+    assert_eq!(*contents.lock().unwrap(), "a link");
+}
+
+#[test]
+fn write_only_clipboard_is_cleared_without_reading() {
+    let contents = Arc::new(Mutex::new("a link".to_string()));
+    let shared = contents.clone();
+    let factory: BackendFactory = Arc::new(move || -> Result<Box<dyn ClipboardBackend>> {
+        Ok(Box::new(WriteOnlyClipboard { contents: shared.clone() }))
+    });
+    let service = ClipboardService::new(factory, options(50, true));
+
+    let events = service.copy(&SecretString::from("hunter2")).unwrap();
+    assert_eq!(*contents.lock().unwrap(), "hunter2");
+    match last_event(events) {
+        ClipboardEvent::Cleared => {},
+        event => panic!("unexpected event {:?}", event),
+    }
+    assert_eq!(*contents.lock().unwrap(), "");
+}
+
+#[test]
+fn handoff_round_trip() {
+    let secret = SecretString::from("hunter2\nlogin: ålice");
+    let previous = SecretString::from("a link");
+
+    let message = encode_handoff(Duration::from_millis(1500), &secret, Some(&previous));
+    assert_eq!(decode_handoff(&message).unwrap(), Handoff {
+        remaining: Duration::from_millis(1500),
+        secret: secret.clone(),
+        previous: Some(previous),
+    });
+
+    let message = encode_handoff(Duration::from_millis(0), &secret, None);
+    assert_eq!(decode_handoff(&message).unwrap().previous, None);
+
+    assert_eq!(decode_handoff(&message[..message.len() - 1]).is_err(), true);
+    assert_eq!(decode_handoff(b"12 x -\n").is_err(), true);
+}
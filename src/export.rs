@@ -0,0 +1,328 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use chrono::prelude::*;
+use serde_json::{json, Value};
+
+use zeroize::Zeroize;
+
+use crate::pass::{resolve_encryption_key, Error, PasswordEntry};
+use crate::secret::{SecretBuffer, SecretString};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The formats that the store can be exported to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// A JSON array with one object per entry.
+    Json,
+    /// A CSV file with one row per entry, the fields are written as `key: value` lines in the
+    /// notes column.
+    Csv,
+    /// The XML format of KeePass 2, with the folders as groups.
+    KeePassXml,
+}
+
+/// How the export is protected.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExportEncryption {
+    /// Encrypt the export to the key with this id, fingerprint or email address, ASCII armored.
+    ToKey(String),
+    /// Write the export in plain text. This has to be chosen explicitly, since the passwords
+    /// end up unprotected on disk.
+    Plaintext,
+}
+
+/// A decrypted entry, as it is written to the export.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportedEntry {
+    /// The name of the entry, with `/` between the folders.
+    pub name: String,
+    /// The first line of the entry.
+    pub password: SecretString,
+    /// The `key: value` lines that follow the password.
+    pub fields: Vec<(String, String)>,
+    /// The rest of the entry.
+    pub notes: Option<String>,
+    /// When the entry was last changed, from git.
+    pub updated: Option<DateTime<Local>>,
+    /// Who last changed the entry, from git.
+    pub committed_by: Option<String>,
+}
+
+/// Splits the content of an entry into the password on the first line, the `key: value` lines
+/// directly after it, and the remaining lines as notes.
+pub fn parse_entry_content(content: &str) -> (String, Vec<(String, String)>, Option<String>) {
+    let mut lines = content.lines();
+    let password = lines.next().unwrap_or("").to_string();
+
+    let mut fields = vec![];
+    let mut notes: Vec<&str> = vec![];
+    for line in lines {
+        if notes.is_empty() {
+            let mut parts = line.splitn(2, ": ");
+            let key = parts.next().unwrap();
+            if let Some(value) = parts.next() {
+                if !key.is_empty() && !key.starts_with(char::is_whitespace) && key.chars().count() <= 40 {
+                    fields.push((key.to_string(), value.to_string()));
+                    continue;
+                }
+            }
+        }
+        notes.push(line);
+    }
+
+    while notes.last() == Some(&"") {
+        notes.pop();
+    }
+    let notes = if notes.is_empty() { None } else { Some(notes.join("\n")) };
+
+    return (password, fields, notes);
+}
+
+/// Returns the entries that are selected by `selection`, that is entries with one of the names
+/// or in one of the folders. An empty selection selects everything.
+pub fn select_entries(entries: &Vec<PasswordEntry>, selection: &Vec<String>) -> Vec<PasswordEntry> {
+    let selection: Vec<String> = selection.iter().map(|s| s.trim_matches('/').to_string()).filter(|s| !s.is_empty()).collect();
+
+    entries.iter()
+        .filter(|e| selection.is_empty() || selection.iter().any(|s| e.name == *s || e.name.starts_with(&format!("{}/", s))))
+        .cloned()
+        .collect()
+}
+
+fn format_time(time: &Option<DateTime<Local>>) -> String {
+    time.map(|t| t.to_rfc3339()).unwrap_or_default()
+}
+
+fn render_json(entries: &Vec<ExportedEntry>) -> Result<SecretString> {
+    let mut values: Vec<Value> = entries.iter().map(|e| {
+        let fields: serde_json::Map<String, Value> = e.fields.iter().map(|(k, v)| (k.clone(), Value::String(v.clone()))).collect();
+        json!({
+            "name": e.name,
+            "password": e.password.expose_secret(),
+            "fields": fields,
+            "notes": e.notes,
+            "updated": e.updated.map(|t| t.to_rfc3339()),
+            "committed_by": e.committed_by,
+        })
+    }).collect();
+
+    let mut out = SecretBuffer::new();
+    let res = serde_json::to_writer_pretty(&mut out, &values);
+
+    for value in values.iter_mut() {
+        if let Some(Value::String(password)) = value.get_mut("password") {
+            password.zeroize();
+        }
+    }
+    res.map_err(|e| Error::GenericDyn(format!("{}", e)))?;
+
+    return Ok(out.into_secret());
+}
+
+/// Writes one CSV row, the fields that contain separators, quotes or line breaks are quoted.
+fn write_csv_record(out: &mut SecretBuffer, fields: &[&str]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push_str(",");
+        }
+        if !field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+            out.push_str(field);
+            continue;
+        }
+        out.push_str("\"");
+        for (j, part) in field.split('"').enumerate() {
+            if j > 0 {
+                out.push_str("\"\"");
+            }
+            out.push_str(part);
+        }
+        out.push_str("\"");
+    }
+    out.push_str("\n");
+}
+
+fn render_csv(entries: &Vec<ExportedEntry>) -> Result<SecretString> {
+    let mut out = SecretBuffer::new();
+
+    write_csv_record(&mut out, &["name", "password", "username", "url", "notes", "updated", "committed_by"]);
+    for e in entries {
+        let field = |names: &[&str]| e.fields.iter().find(|(k, _)| names.contains(&k.as_str())).map(|(_, v)| v.clone()).unwrap_or_default();
+        let mut notes: Vec<String> = e.fields.iter()
+            .filter(|(k, _)| !["login", "user", "username", "url"].contains(&k.as_str()))
+            .map(|(k, v)| format!("{}: {}", k, v))
+            .collect();
+        if let Some(n) = &e.notes {
+            notes.push(n.clone());
+        }
+
+        write_csv_record(&mut out, &[
+            &e.name,
+            e.password.expose_secret(),
+            &field(&["login", "user", "username"]),
+            &field(&["url"]),
+            &notes.join("\n"),
+            &format_time(&e.updated),
+            e.committed_by.as_ref().map(|c| c.as_str()).unwrap_or(""),
+        ]);
+    }
+
+    return Ok(out.into_secret());
+}
+
+/// Writes `text` with the characters that have a meaning in XML escaped, without copying it.
+fn write_xml_escaped(out: &mut SecretBuffer, text: &str) {
+    let mut rest = text;
+    while let Some(i) = rest.find(|c| c == '&' || c == '<' || c == '>' || c == '"' || c == '\'') {
+        out.push_str(&rest[..i]);
+        out.push_str(match &rest[i..i + 1] {
+            "&" => "&amp;",
+            "<" => "&lt;",
+            ">" => "&gt;",
+            "\"" => "&quot;",
+            _ => "&apos;",
+        });
+        rest = &rest[i + 1..];
+    }
+    out.push_str(rest);
+}
+
+/// A folder in the KeePass export, with its entries and sub folders.
+#[derive(Default)]
+struct XmlGroup<'a> {
+    entries: Vec<&'a ExportedEntry>,
+    groups: BTreeMap<String, XmlGroup<'a>>,
+}
+
+fn write_xml_string(out: &mut SecretBuffer, indent: &str, key: &str, value: &str) {
+    out.push_str(indent);
+    out.push_str("<String><Key>");
+    write_xml_escaped(out, key);
+    out.push_str("</Key><Value>");
+    write_xml_escaped(out, value);
+    out.push_str("</Value></String>\n");
+}
+
+fn write_xml_group(out: &mut SecretBuffer, name: &str, group: &XmlGroup, depth: usize) {
+    let indent = "\t".repeat(depth);
+    out.push_str(&format!("{}<Group>\n{}\t<Name>", indent, indent));
+    write_xml_escaped(out, name);
+    out.push_str("</Name>\n");
+
+    for e in &group.entries {
+        let title = e.name.rsplit('/').next().unwrap_or("");
+        out.push_str(&format!("{}\t<Entry>\n", indent));
+        if let Some(updated) = e.updated {
+            out.push_str(&format!("{}\t\t<Times><LastModificationTime>{}</LastModificationTime></Times>\n", indent, updated.with_timezone(&Utc).format("%Y-%m-%dT%H:%M:%SZ")));
+        }
+        let inner = format!("{}\t\t", indent);
+        write_xml_string(out, &inner, "Title", title);
+        write_xml_string(out, &inner, "Password", &e.password);
+        for (key, value) in &e.fields {
+            let key = match key.as_str() {
+                "login" | "user" | "username" => "UserName",
+                "url" => "URL",
+                k => k,
+            };
+            write_xml_string(out, &inner, key, value);
+        }
+        write_xml_string(out, &inner, "Notes", e.notes.as_ref().map(|n| n.as_str()).unwrap_or(""));
+        if let Some(committed_by) = &e.committed_by {
+            write_xml_string(out, &inner, "committed_by", committed_by);
+        }
+        out.push_str(&format!("{}\t</Entry>\n", indent));
+    }
+
+    for (name, sub) in &group.groups {
+        write_xml_group(out, name, sub, depth + 1);
+    }
+
+    out.push_str(&format!("{}</Group>\n", indent));
+}
+
+fn render_keepass_xml(entries: &Vec<ExportedEntry>) -> Result<SecretString> {
+    let mut root = XmlGroup::default();
+    for e in entries {
+        let parts: Vec<&str> = e.name.split('/').collect();
+        let mut group = &mut root;
+        for folder in &parts[..parts.len() - 1] {
+            group = group.groups.entry(folder.to_string()).or_insert_with(XmlGroup::default);
+        }
+        group.entries.push(e);
+    }
+
+    let mut out = SecretBuffer::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<KeePassFile>\n\t<Root>\n");
+    write_xml_group(&mut out, "ripasso", &root, 2);
+    out.push_str("\t</Root>\n</KeePassFile>\n");
+
+    return Ok(out.into_secret());
+}
+
+/// Writes the entries in the format.
+pub fn render(format: ExportFormat, entries: &Vec<ExportedEntry>) -> Result<SecretString> {
+    match format {
+        ExportFormat::Json => render_json(entries),
+        ExportFormat::Csv => render_csv(entries),
+        ExportFormat::KeePassXml => render_keepass_xml(entries),
+    }
+}
+
+/// Decrypts the entries in `selection` (see `select_entries`) and writes them to `output` in the
+/// format, encrypted as requested. Returns the number of exported entries.
+pub fn export(entries: &Vec<PasswordEntry>, selection: &Vec<String>, format: ExportFormat, encryption: &ExportEncryption, output: &mut dyn Write) -> Result<usize> {
+    // find the key before decrypting anything, so that a typo doesn't waste a round of pin entry
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    let key = match encryption {
+        ExportEncryption::ToKey(id) => Some(resolve_encryption_key(&mut ctx, id)?),
+        ExportEncryption::Plaintext => None,
+    };
+
+    let mut exported = vec![];
+    for entry in select_entries(entries, selection) {
+        let (password, fields, notes) = parse_entry_content(&entry.secret()?);
+        exported.push(ExportedEntry {
+            name: entry.name.clone(),
+            password: SecretString::new(password),
+            fields,
+            notes,
+            updated: entry.updated,
+            committed_by: entry.committed_by.clone(),
+        });
+    }
+
+    let rendered = render(format, &exported)?;
+
+    match key {
+        Some(key) => {
+            ctx.set_armor(true);
+            let mut ciphertext = Vec::new();
+            ctx.encrypt(&vec![key], rendered.as_bytes(), &mut ciphertext)?;
+            output.write_all(&ciphertext)?;
+        },
+        None => output.write_all(rendered.as_bytes())?,
+    }
+
+    return Ok(exported.len());
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,65 @@
+use super::*;
+
+use crate::import;
+
+fn entries() -> Vec<ExportedEntry> {
+    vec![ExportedEntry {
+        name: "servers/db".to_string(),
+        password: SecretString::from("p<a>ss&"),
+        fields: vec![("login".to_string(), "postgres".to_string()), ("port".to_string(), "5432".to_string())],
+        notes: Some("rotate \"yearly\"".to_string()),
+        updated: Some(Local.ymd(2019, 11, 3).and_hms(10, 0, 0)),
+        committed_by: None,
+    }]
+}
+
+#[test]
+fn parse_entry_content_splits_fields_and_notes() {
+    let (password, fields, notes) = parse_entry_content("hunter2\nlogin: alice\nurl: https://example.com\n\nfree text: with colon\nmore\n");
+
+    assert_eq!(password, "hunter2");
+    assert_eq!(fields, vec![("login".to_string(), "alice".to_string()), ("url".to_string(), "https://example.com".to_string())]);
+    assert_eq!(notes, Some("\nfree text: with colon\nmore".to_string()));
+}
+
+#[test]
+fn select_entries_by_name_and_folder() {
+    let base = std::path::PathBuf::from("/store");
+    let entry = |name: &str| PasswordEntry::new(&base, &base.join(format!("{}.gpg", name)), Err(Error::Generic("no git")), Err(Error::Generic("no git")), Err(Error::Generic("no git")));
+    let all = vec![entry("servers/db"), entry("servers2/web"), entry("mail")];
+
+    let names = |selection: Vec<&str>| select_entries(&all, &selection.iter().map(|s| s.to_string()).collect()).iter().map(|e| e.name.clone()).collect::<Vec<String>>();
+
+    assert_eq!(names(vec!["servers/"]), vec!["servers/db".to_string()]);
+    assert_eq!(names(vec!["mail"]), vec!["mail".to_string()]);
+    assert_eq!(names(vec![]).len(), 3);
+}
+
+#[test]
+fn json_contains_metadata() {
+    let json: Value = serde_json::from_str(&render(ExportFormat::Json, &entries()).unwrap()).unwrap();
+
+    assert_eq!(json[0]["name"], "servers/db");
+    assert_eq!(json[0]["fields"]["port"], "5432");
+    assert_eq!(json[0]["committed_by"], Value::Null);
+    assert_eq!(json[0]["updated"].as_str().unwrap().starts_with("2019-11-03T10:00:00"), true);
+}
+
+#[test]
+fn csv_has_a_header_and_escapes() {
+    let csv = render(ExportFormat::Csv, &entries()).unwrap();
+
+    assert_eq!(csv.lines().next().unwrap(), "name,password,username,url,notes,updated,committed_by");
+    assert_eq!(csv.contains("\"port: 5432\nrotate \"\"yearly\"\"\""), true);
+}
+
+#[test]
+fn keepass_xml_can_be_imported_again() {
+    let xml = render(ExportFormat::KeePassXml, &entries()).unwrap();
+
+    let records = import::parse(import::ImportFormat::KeePassXml, &xml).unwrap();
+
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].name, "servers/db");
+    assert_eq!(records[0].to_pass_format(), "p<a>ss&\nlogin: postgres\nport: 5432\nrotate \"yearly\"\n");
+}
@@ -0,0 +1,195 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use zeroize::Zeroize;
+
+use crate::pass::Error;
+use crate::secret::SecretString;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+const VOWELS: &str = "aeiouy";
+const CONSONANTS: &str = "bcdfghjklmnpqrstvwxz";
+
+/// Characters that are easy to confuse with each other when reading a password out loud or
+/// copying it by hand.
+pub const AMBIGUOUS: &str = "0O1lI|`'\"";
+
+/// How the password should be built.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Random characters from all the enabled character classes.
+    Characters,
+    /// Alternating consonants and vowels, that can be read out loud, with digits and symbols at
+    /// the end.
+    Pronounceable,
+}
+
+/// The options for the character based password generator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratorOptions {
+    /// The number of characters in the password.
+    pub length: usize,
+    /// Include upper case letters.
+    pub upper: bool,
+    /// Include lower case letters.
+    pub lower: bool,
+    /// Include digits.
+    pub digits: bool,
+    /// Include symbols.
+    pub symbols: bool,
+    /// Leave out the characters in `AMBIGUOUS`.
+    pub exclude_ambiguous: bool,
+    /// Make sure that every enabled character class is used at least once.
+    pub at_least_one_of_each: bool,
+    /// How the password is built.
+    pub mode: Mode,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> GeneratorOptions {
+        GeneratorOptions {
+            length: 20,
+            upper: true,
+            lower: true,
+            digits: true,
+            symbols: true,
+            exclude_ambiguous: true,
+            at_least_one_of_each: true,
+            mode: Mode::Characters,
+        }
+    }
+}
+
+impl GeneratorOptions {
+    /// Returns the characters of every enabled class, with the ambiguous characters removed if
+    /// that is requested.
+    fn classes(&self) -> Vec<Vec<char>> {
+        let mut classes = vec![];
+        for (enabled, chars) in &[(self.upper, UPPER), (self.lower, LOWER), (self.digits, DIGITS), (self.symbols, SYMBOLS)] {
+            if *enabled {
+                classes.push(self.filter(chars));
+            }
+        }
+        return classes;
+    }
+
+    fn filter(&self, chars: &str) -> Vec<char> {
+        chars.chars()
+            .filter(|c| !self.exclude_ambiguous || !AMBIGUOUS.contains(*c))
+            .collect()
+    }
+}
+
+/// Generates a random password according to `options`, using the random number generator of
+/// the operating system.
+pub fn generate(options: &GeneratorOptions) -> Result<SecretString> {
+    let mut rng = OsRng;
+
+    let mut password = match options.mode {
+        Mode::Characters => generate_characters(options, &mut rng)?,
+        Mode::Pronounceable => generate_pronounceable(options, &mut rng)?,
+    };
+
+    let mut secret = String::with_capacity(password.iter().map(|c| c.len_utf8()).sum());
+    secret.extend(password.iter());
+    password.zeroize();
+
+    return Ok(SecretString::new(secret));
+}
+
+// the generators work on vectors that are allocated with room for the whole password, so that
+// they aren't reallocated and no copies of the password are left behind
+
+fn generate_characters<R: Rng>(options: &GeneratorOptions, rng: &mut R) -> Result<Vec<char>> {
+    let classes = options.classes();
+    if classes.is_empty() {
+        return Err(Error::Generic("at least one character class must be selected"));
+    }
+    if options.at_least_one_of_each && options.length < classes.len() {
+        return Err(Error::Generic("the password is too short to contain all the character classes"));
+    }
+
+    let pool: Vec<char> = classes.iter().flatten().cloned().collect();
+
+    let mut password = Vec::with_capacity(options.length);
+    if options.at_least_one_of_each {
+        for class in &classes {
+            password.push(*class.choose(rng).unwrap());
+        }
+    }
+    while password.len() < options.length {
+        password.push(*pool.choose(rng).unwrap());
+    }
+    password.shuffle(rng);
+
+    return Ok(password);
+}
+
+fn generate_pronounceable<R: Rng>(options: &GeneratorOptions, rng: &mut R) -> Result<Vec<char>> {
+    if !options.upper && !options.lower {
+        return Err(Error::Generic("a pronounceable password needs letters"));
+    }
+
+    let mut tail = vec![];
+    for (enabled, chars) in &[(options.digits, DIGITS), (options.symbols, SYMBOLS)] {
+        if *enabled {
+            tail.push(*options.filter(chars).choose(rng).unwrap());
+        }
+    }
+    if options.length <= tail.len() {
+        return Err(Error::Generic("the password is too short to contain all the character classes"));
+    }
+
+    // upper case only passwords pick from upper case letters, so that the ambiguous ones are
+    // left out in the right case
+    let only_upper = options.upper && !options.lower;
+    let letters_in_case = |chars: &str| if only_upper { chars.to_ascii_uppercase() } else { chars.to_string() };
+    let vowels = options.filter(&letters_in_case(VOWELS));
+    let consonants = options.filter(&letters_in_case(CONSONANTS));
+    let letters = options.length - tail.len();
+
+    let mut password = Vec::with_capacity(options.length);
+    for i in 0..letters {
+        password.push(if i % 2 == 0 { *consonants.choose(rng).unwrap() } else { *vowels.choose(rng).unwrap() });
+    }
+
+    if options.upper && options.lower && (options.at_least_one_of_each || rng.gen()) {
+        let i = rng.gen_range(0, password.len());
+        let upper = password[i].to_ascii_uppercase();
+        if !options.exclude_ambiguous || !AMBIGUOUS.contains(upper) {
+            password[i] = upper;
+        } else {
+            password[0] = password[0].to_ascii_uppercase();
+        }
+    }
+
+    password.extend(tail.iter());
+    tail.zeroize();
+
+    return Ok(password);
+}
+
+#[cfg(test)]
+mod test;
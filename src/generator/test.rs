@@ -0,0 +1,104 @@
+use super::*;
+
+#[test]
+fn characters_has_length_and_every_class() {
+    let options = GeneratorOptions {
+        length: 4,
+        ..GeneratorOptions::default()
+    };
+
+    for _ in 0..100 {
+        let password = generate(&options).unwrap();
+
+        assert_eq!(password.chars().count(), 4);
+        assert_eq!(password.chars().any(|c| c.is_ascii_uppercase()), true);
+        assert_eq!(password.chars().any(|c| c.is_ascii_lowercase()), true);
+        assert_eq!(password.chars().any(|c| c.is_ascii_digit()), true);
+        assert_eq!(password.chars().any(|c| SYMBOLS.contains(c)), true);
+    }
+}
+
+#[test]
+fn characters_excludes_ambiguous() {
+    let options = GeneratorOptions {
+        length: 200,
+        ..GeneratorOptions::default()
+    };
+
+    let password = generate(&options).unwrap();
+
+    assert_eq!(password.chars().any(|c| AMBIGUOUS.contains(c)), false);
+}
+
+#[test]
+fn characters_only_uses_selected_classes() {
+    let options = GeneratorOptions {
+        length: 50,
+        upper: false,
+        lower: false,
+        symbols: false,
+        ..GeneratorOptions::default()
+    };
+
+    let password = generate(&options).unwrap();
+
+    assert_eq!(password.chars().all(|c| c.is_ascii_digit()), true);
+}
+
+#[test]
+fn invalid_options_are_errors() {
+    let no_classes = GeneratorOptions {
+        upper: false,
+        lower: false,
+        digits: false,
+        symbols: false,
+        ..GeneratorOptions::default()
+    };
+    let too_short = GeneratorOptions {
+        length: 3,
+        ..GeneratorOptions::default()
+    };
+
+    assert_eq!(generate(&no_classes).is_err(), true);
+    assert_eq!(generate(&too_short).is_err(), true);
+}
+
+#[test]
+fn pronounceable_alternates_consonants_and_vowels() {
+    let options = GeneratorOptions {
+        length: 12,
+        upper: false,
+        mode: Mode::Pronounceable,
+        ..GeneratorOptions::default()
+    };
+
+    let password: Vec<char> = generate(&options).unwrap().chars().collect();
+
+    assert_eq!(password.len(), 12);
+    for (i, c) in password[..10].iter().enumerate() {
+        if i % 2 == 0 {
+            assert_eq!(CONSONANTS.contains(*c), true);
+        } else {
+            assert_eq!(VOWELS.contains(*c), true);
+        }
+    }
+    assert_eq!(password[10].is_ascii_digit(), true);
+    assert_eq!(SYMBOLS.contains(password[11]), true);
+}
+
+#[test]
+fn pronounceable_upper_case_only_excludes_ambiguous() {
+    let options = GeneratorOptions {
+        length: 200,
+        lower: false,
+        digits: false,
+        symbols: false,
+        mode: Mode::Pronounceable,
+        ..GeneratorOptions::default()
+    };
+
+    let password = generate(&options).unwrap();
+
+    assert_eq!(password.chars().all(|c| c.is_ascii_uppercase()), true);
+    assert_eq!(password.chars().any(|c| AMBIGUOUS.contains(c)), false);
+}
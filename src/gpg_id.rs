@@ -0,0 +1,212 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashSet;
+use std::fmt;
+
+/// One line in a `.gpg-id` file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Line {
+    /// A gpg key identifier, optionally followed by a `#` comment.
+    Id {
+        /// The identifier, as written in the file.
+        id: String,
+        /// The text after the `#`, if there was one.
+        comment: Option<String>,
+    },
+    /// A line that only contains a comment, stored verbatim.
+    Comment(String),
+    /// An empty line.
+    Empty,
+}
+
+/// The different ways a key can be identified in a `.gpg-id` file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IdKind {
+    /// A full fingerprint, 40 hex digits.
+    Fingerprint,
+    /// A short or long key id, 8 or 16 hex digits.
+    KeyId,
+    /// An email address, with or without surrounding `<>`.
+    Email,
+    /// Anything else, for example a name, that gpg will search for.
+    Other,
+}
+
+/// The content of a `.gpg-id` file, that can be modified without losing the comments or
+/// the ordering of the lines.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpgIdFile {
+    /// All lines of the file, in order.
+    pub lines: Vec<Line>,
+}
+
+fn parse_line(line: &str) -> Line {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        return Line::Empty;
+    }
+    if trimmed.starts_with('#') {
+        return Line::Comment(line.to_string());
+    }
+
+    let mut parts = trimmed.splitn(2, '#');
+    let id = parts.next().unwrap_or("").trim().to_string();
+    let comment = parts.next().map(|c| c.to_string());
+
+    return Line::Id {
+        id,
+        comment,
+    };
+}
+
+/// Returns the identifier without the decorations that are allowed in a `.gpg-id` file, that is
+/// without `0x` prefix, spaces in fingerprints and `<>` around email addresses. Hex identifiers
+/// are upper cased.
+pub fn normalize(id: &str) -> String {
+    let trimmed = id.trim();
+    if trimmed.starts_with('<') && trimmed.ends_with('>') && trimmed.len() > 2 {
+        return trimmed[1..trimmed.len() - 1].to_string();
+    }
+    if trimmed.contains('@') {
+        return trimmed.to_string();
+    }
+
+    let compact: String = trimmed.chars().filter(|c| !c.is_whitespace()).collect();
+    let without_prefix = if compact.starts_with("0x") || compact.starts_with("0X") {
+        &compact[2..]
+    } else {
+        &compact[..]
+    };
+    if !without_prefix.is_empty() && without_prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return without_prefix.to_uppercase();
+    }
+
+    return trimmed.to_string();
+}
+
+/// Decides what kind of identifier `id` is.
+pub fn kind(id: &str) -> IdKind {
+    let normalized = normalize(id);
+    if normalized.contains('@') {
+        return IdKind::Email;
+    }
+    if normalized.chars().all(|c| c.is_ascii_hexdigit()) {
+        return match normalized.len() {
+            40 => IdKind::Fingerprint,
+            8 | 16 => IdKind::KeyId,
+            _ => IdKind::Other,
+        };
+    }
+    return IdKind::Other;
+}
+
+impl GpgIdFile {
+    /// Parses the content of a `.gpg-id` file. Everything after a `#` is a comment, and
+    /// whitespace around the identifiers is ignored, in the same way as pass reads the file.
+    pub fn parse(contents: &str) -> GpgIdFile {
+        GpgIdFile {
+            lines: contents.lines().map(parse_line).collect(),
+        }
+    }
+
+    /// Returns the identifiers in the file, in the order they are written, without duplicates.
+    pub fn ids(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut ids = vec![];
+        for line in &self.lines {
+            if let Line::Id { id, .. } = line {
+                if seen.insert(normalize(id)) {
+                    ids.push(id.clone());
+                }
+            }
+        }
+        return ids;
+    }
+
+    /// Returns the identifiers that are written more than once in the file.
+    pub fn duplicates(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut reported = HashSet::new();
+        let mut duplicates = vec![];
+        for line in &self.lines {
+            if let Line::Id { id, .. } = line {
+                let normalized = normalize(id);
+                if !seen.insert(normalized.clone()) && reported.insert(normalized) {
+                    duplicates.push(id.clone());
+                }
+            }
+        }
+        return duplicates;
+    }
+
+    /// Adds an identifier at the end of the file.
+    pub fn add(&mut self, id: &str) {
+        while let Some(Line::Empty) = self.lines.last() {
+            self.lines.pop();
+        }
+        self.lines.push(Line::Id {
+            id: id.to_string(),
+            comment: None,
+        });
+    }
+
+    /// Removes all identifiers for which `keep` returns false, together with their comments.
+    /// Returns the number of removed lines.
+    pub fn retain_ids<F>(&mut self, mut keep: F) -> usize
+        where F: FnMut(&str) -> bool {
+        let before = self.lines.len();
+        self.lines.retain(|line| match line {
+            Line::Id { id, .. } => keep(id),
+            _ => true,
+        });
+        return before - self.lines.len();
+    }
+
+    /// Replaces every identifier for which `replacement` returns a new value, keeping the comments.
+    /// Returns the number of replaced identifiers.
+    pub fn replace_ids<F>(&mut self, mut replacement: F) -> usize
+        where F: FnMut(&str) -> Option<String> {
+        let mut replaced = 0;
+        for line in self.lines.iter_mut() {
+            if let Line::Id { id, .. } = line {
+                if let Some(new_id) = replacement(id) {
+                    *id = new_id;
+                    replaced += 1;
+                }
+            }
+        }
+        return replaced;
+    }
+}
+
+impl fmt::Display for GpgIdFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            match line {
+                Line::Id { id, comment: Some(c) } => writeln!(f, "{} #{}", id, c)?,
+                Line::Id { id, comment: None } => writeln!(f, "{}", id)?,
+                Line::Comment(c) => writeln!(f, "{}", c)?,
+                Line::Empty => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test;
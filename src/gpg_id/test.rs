@@ -0,0 +1,51 @@
+use super::*;
+
+#[test]
+fn parse_keeps_comments_and_order() {
+    let contents = "# the ops team\n0x7E068070D5EF794B # Alexander\n\nalice@example.com\n";
+
+    let file = GpgIdFile::parse(contents);
+
+    assert_eq!(file.lines.len(), 4);
+    assert_eq!(file.lines[0], Line::Comment("# the ops team".to_string()));
+    assert_eq!(file.lines[1], Line::Id { id: "0x7E068070D5EF794B".to_string(), comment: Some(" Alexander".to_string()) });
+    assert_eq!(file.lines[2], Line::Empty);
+    assert_eq!(file.ids(), vec!["0x7E068070D5EF794B".to_string(), "alice@example.com".to_string()]);
+    assert_eq!(format!("{}", file), contents);
+}
+
+#[test]
+fn duplicates_are_found_after_normalization() {
+    let file = GpgIdFile::parse("0x7E068070D5EF794B\n7e068070d5ef794b\n<alice@example.com>\nalice@example.com\n1D108E6C07CBC406\n");
+
+    assert_eq!(file.ids().len(), 3);
+    assert_eq!(file.duplicates(), vec!["7e068070d5ef794b".to_string(), "alice@example.com".to_string()]);
+}
+
+#[test]
+fn kinds_of_identifiers() {
+    assert_eq!(kind("0x7E068070D5EF794B"), IdKind::KeyId);
+    assert_eq!(kind("D5EF794B"), IdKind::KeyId);
+    assert_eq!(kind("E6A7 D758 338E C2EF 2A8A  9F4E E7E3 DB4B 3217 482F"), IdKind::Fingerprint);
+    assert_eq!(kind("<alice@example.com>"), IdKind::Email);
+    assert_eq!(kind("Alice Example"), IdKind::Other);
+}
+
+#[test]
+fn normalize_identifiers() {
+    assert_eq!(normalize("0x7e068070d5ef794b"), "7E068070D5EF794B");
+    assert_eq!(normalize(" <alice@example.com> "), "alice@example.com");
+    assert_eq!(normalize("E6A7 D758 338E C2EF 2A8A 9F4E E7E3 DB4B 3217 482F"), "E6A7D758338EC2EF2A8A9F4EE7E3DB4B3217482F");
+    assert_eq!(normalize("Alice Example"), "Alice Example");
+}
+
+#[test]
+fn retain_and_replace_keep_comments() {
+    let mut file = GpgIdFile::parse("# team\n0x7E068070D5EF794B # Alexander\nalice@example.com # Alice\n");
+
+    assert_eq!(file.retain_ids(|id| id != "alice@example.com"), 1);
+    assert_eq!(file.replace_ids(|id| if id.starts_with("0x") { Some("E6A7D758338EC2EF2A8A9F4EE7E3DB4B3217482F".to_string()) } else { None }), 1);
+    file.add("1D108E6C07CBC406");
+
+    assert_eq!(format!("{}", file), "# team\nE6A7D758338EC2EF2A8A9F4EE7E3DB4B3217482F # Alexander\n1D108E6C07CBC406\n");
+}
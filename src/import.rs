@@ -0,0 +1,482 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashSet;
+use std::fs;
+use std::path;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::pass::{add_and_commit, encrypt_for_path, password_dir, write_atomically, write_new_password_file, Error, GitRepo};
+use crate::policy::Policy;
+use crate::secret::{SecretBuffer, SecretString};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The line that separates the records in a 1Password 1PIF export.
+const ONE_PIF_SEPARATOR: &str = "***5642bee8-a5ff-11dc-8314-0800200c9a66***";
+
+/// The export formats that can be imported.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportFormat {
+    /// The XML export of KeePass 2. The encrypted KDBX format isn't supported, export to XML
+    /// from KeePass first.
+    KeePassXml,
+    /// The unencrypted JSON export of Bitwarden.
+    BitwardenJson,
+    /// The 1PIF export of 1Password.
+    OnePassword1Pif,
+    /// The CSV export of 1Password.
+    OnePasswordCsv,
+    /// The CSV export of LastPass.
+    LastPassCsv,
+}
+
+/// One record from an export, before it's written to the store.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportRecord {
+    /// The name of the entry, with `/` between the folders.
+    pub name: String,
+    /// The password, the first line of the entry.
+    pub password: SecretString,
+    /// The other fields, written as `key: value` lines. They can be secrets as well, like the
+    /// seed of a one time password.
+    pub fields: Vec<(String, SecretString)>,
+    /// Free text notes, written after the fields.
+    pub notes: Option<SecretString>,
+}
+
+impl ImportRecord {
+    fn new(folders: &[String], title: &str) -> ImportRecord {
+        let mut parts: Vec<String> = folders.iter().map(|f| sanitize_name_part(f)).filter(|f| !f.is_empty()).collect();
+        let title = sanitize_name_part(title);
+        parts.push(if title.is_empty() { "unnamed".to_string() } else { title });
+
+        ImportRecord {
+            name: parts.join("/"),
+            password: SecretString::default(),
+            fields: vec![],
+            notes: None,
+        }
+    }
+
+    fn add_field(&mut self, key: &str, value: &str) {
+        let value = value.trim();
+        if !value.is_empty() {
+            self.fields.push((key.to_string(), SecretString::from(value)));
+        }
+    }
+
+    fn set_notes(&mut self, notes: &str) {
+        if !notes.trim().is_empty() {
+            self.notes = Some(SecretString::from(notes.trim_end()));
+        }
+    }
+
+    /// The content of the entry in the pass format: the password on the first line, then the
+    /// fields and last the notes. The lines after the first of a multi line password are kept
+    /// as `password-2: ...` fields and so on.
+    pub fn to_pass_format(&self) -> SecretString {
+        let mut content = SecretBuffer::new();
+        let mut password_lines = self.password.lines();
+        content.push_str(password_lines.next().unwrap_or(""));
+        content.push_str("\n");
+        for (i, line) in password_lines.enumerate() {
+            push_field(&mut content, &format!("password-{}", i + 2), line);
+        }
+        for (key, value) in &self.fields {
+            push_field(&mut content, key, value);
+        }
+        if let Some(notes) = &self.notes {
+            content.push_str(notes);
+            content.push_str("\n");
+        }
+        return content.into_secret();
+    }
+}
+
+/// Writes a `key: value` line, with the line breaks in the value replaced by spaces.
+fn push_field(content: &mut SecretBuffer, key: &str, value: &str) {
+    content.push_str(key);
+    content.push_str(": ");
+    for (i, line) in value.split('\n').enumerate() {
+        if i > 0 {
+            content.push_str(" ");
+        }
+        content.push_str(line);
+    }
+    content.push_str("\n");
+}
+
+/// Makes a title or folder name usable as a part of an entry name, `/` would create folders and
+/// names starting with `.` would be hidden.
+fn sanitize_name_part(part: &str) -> String {
+    let cleaned: String = part.trim().chars()
+        .map(|c| if c == '/' || c == '\\' || c.is_control() { '-' } else { c })
+        .collect();
+    return cleaned.trim_start_matches('.').trim().to_string();
+}
+
+fn xml_error(err: roxmltree::Error) -> Error {
+    Error::GenericDyn(format!("invalid KeePass XML: {}", err))
+}
+
+fn json_error(err: serde_json::Error) -> Error {
+    Error::GenericDyn(format!("invalid JSON: {}", err))
+}
+
+fn csv_error(err: csv::Error) -> Error {
+    Error::GenericDyn(format!("invalid CSV: {}", err))
+}
+
+fn child<'a, 'input>(node: roxmltree::Node<'a, 'input>, name: &str) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|c| c.is_element() && c.tag_name().name() == name)
+}
+
+fn parse_keepass_group(group: roxmltree::Node, folders: &mut Vec<String>, records: &mut Vec<ImportRecord>) {
+    for node in group.children().filter(|c| c.is_element()) {
+        match node.tag_name().name() {
+            "Group" => {
+                folders.push(child(node, "Name").and_then(|n| n.text()).unwrap_or("").to_string());
+                parse_keepass_group(node, folders, records);
+                folders.pop();
+            },
+            "Entry" => {
+                let mut values = vec![];
+                for string in node.children().filter(|c| c.is_element() && c.tag_name().name() == "String") {
+                    let key = child(string, "Key").and_then(|k| k.text()).unwrap_or("");
+                    let value = child(string, "Value").and_then(|v| v.text()).unwrap_or("");
+                    values.push((key.to_string(), value.to_string()));
+                }
+
+                let title = values.iter().find(|(k, _)| k == "Title").map(|(_, v)| v.as_str()).unwrap_or("");
+                let mut record = ImportRecord::new(folders, title);
+                for (key, value) in &values {
+                    match key.as_str() {
+                        "Title" => {},
+                        "Password" => record.password = SecretString::new(value.clone()),
+                        "UserName" => record.add_field("login", value),
+                        "URL" => record.add_field("url", value),
+                        "Notes" => record.set_notes(value),
+                        _ => record.add_field(key, value),
+                    }
+                }
+                records.push(record);
+            },
+            _ => {},
+        }
+    }
+}
+
+fn parse_keepass_xml(contents: &str) -> Result<Vec<ImportRecord>> {
+    let doc = roxmltree::Document::parse(contents).map_err(xml_error)?;
+    let root = child(doc.root_element(), "Root").ok_or(Error::Generic("the KeePass XML has no Root element"))?;
+
+    let mut records = vec![];
+    // the top group is the database itself, its name isn't used as a folder
+    for top in root.children().filter(|c| c.is_element() && c.tag_name().name() == "Group") {
+        parse_keepass_group(top, &mut vec![], &mut records);
+    }
+    return Ok(records);
+}
+
+fn json_str<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+fn parse_bitwarden_json(contents: &str) -> Result<Vec<ImportRecord>> {
+    let doc: Value = serde_json::from_str(contents).map_err(json_error)?;
+    if doc.get("encrypted").and_then(|e| e.as_bool()).unwrap_or(false) {
+        return Err(Error::Generic("encrypted Bitwarden exports can't be imported, export as unencrypted JSON"));
+    }
+
+    let folders: Vec<(String, String)> = doc.get("folders").and_then(|f| f.as_array()).map(|folders| {
+        folders.iter().map(|f| (json_str(f, "id").to_string(), json_str(f, "name").to_string())).collect()
+    }).unwrap_or_default();
+
+    let items = doc.get("items").and_then(|i| i.as_array()).ok_or(Error::Generic("the Bitwarden export has no items"))?;
+
+    let mut records = vec![];
+    for item in items {
+        let folder: Vec<String> = folders.iter()
+            .filter(|(id, _)| id == json_str(item, "folderId"))
+            .flat_map(|(_, name)| name.split('/').map(|s| s.to_string()).collect::<Vec<String>>())
+            .collect();
+
+        let mut record = ImportRecord::new(&folder, json_str(item, "name"));
+        if let Some(login) = item.get("login") {
+            record.password = SecretString::from(json_str(login, "password"));
+            record.add_field("login", json_str(login, "username"));
+            for uri in login.get("uris").and_then(|u| u.as_array()).unwrap_or(&vec![]) {
+                record.add_field("url", json_str(uri, "uri"));
+            }
+            record.add_field("totp", json_str(login, "totp"));
+        }
+        for section in &["card", "identity"] {
+            if let Some(map) = item.get(*section).and_then(|s| s.as_object()) {
+                for (key, value) in map {
+                    if let Some(value) = value.as_str() {
+                        record.add_field(key, value);
+                    }
+                }
+            }
+        }
+        for field in item.get("fields").and_then(|f| f.as_array()).unwrap_or(&vec![]) {
+            record.add_field(json_str(field, "name"), json_str(field, "value"));
+        }
+        record.set_notes(json_str(item, "notes"));
+        records.push(record);
+    }
+    return Ok(records);
+}
+
+fn parse_1pif(contents: &str) -> Result<Vec<ImportRecord>> {
+    let mut records = vec![];
+    for chunk in contents.split(ONE_PIF_SEPARATOR) {
+        let chunk = chunk.trim();
+        if chunk.is_empty() {
+            continue;
+        }
+        let item: Value = serde_json::from_str(chunk).map_err(json_error)?;
+        if item.get("trashed").and_then(|t| t.as_bool()).unwrap_or(false) || json_str(&item, "typeName").starts_with("system.") {
+            continue;
+        }
+
+        let mut record = ImportRecord::new(&[], json_str(&item, "title"));
+        record.add_field("url", json_str(&item, "location"));
+
+        if let Some(secure) = item.get("secureContents") {
+            record.password = SecretString::from(json_str(secure, "password"));
+            for field in secure.get("fields").and_then(|f| f.as_array()).unwrap_or(&vec![]) {
+                match json_str(field, "designation") {
+                    "password" => record.password = SecretString::from(json_str(field, "value")),
+                    "username" => record.add_field("login", json_str(field, "value")),
+                    _ => record.add_field(json_str(field, "name"), json_str(field, "value")),
+                }
+            }
+            for section in secure.get("sections").and_then(|s| s.as_array()).unwrap_or(&vec![]) {
+                for field in section.get("fields").and_then(|f| f.as_array()).unwrap_or(&vec![]) {
+                    if let Some(value) = field.get("v").and_then(|v| v.as_str()) {
+                        record.add_field(json_str(field, "t"), value);
+                    }
+                }
+            }
+            record.set_notes(json_str(secure, "notesPlain"));
+        }
+        records.push(record);
+    }
+    return Ok(records);
+}
+
+/// Reads a CSV export, where the columns are found by their header names.
+fn parse_csv(contents: &str, format: ImportFormat) -> Result<Vec<ImportRecord>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(contents.as_bytes());
+    let headers: Vec<String> = reader.headers().map_err(csv_error)?.iter().map(|h| h.trim().to_lowercase()).collect();
+
+    let mut records = vec![];
+    for row in reader.records() {
+        let row = row.map_err(csv_error)?;
+        let value = |names: &[&str]| -> String {
+            headers.iter().position(|h| names.contains(&h.as_str()))
+                .and_then(|i| row.get(i))
+                .unwrap_or("")
+                .to_string()
+        };
+
+        let folders: Vec<String> = value(&["grouping", "folder"]).split(|c| c == '\\' || c == '/').map(|f| f.to_string()).collect();
+        let mut record = ImportRecord::new(&folders, &value(&["title", "name"]));
+        record.password = SecretString::new(value(&["password"]));
+        record.add_field("login", &value(&["username", "login"]));
+        let url = value(&["url", "website", "login_uri"]);
+        // LastPass uses this url to mark secure notes
+        if !(format == ImportFormat::LastPassCsv && url == "http://sn") {
+            record.add_field("url", &url);
+        }
+        record.add_field("totp", &value(&["totp", "otpauth"]));
+
+        let known = ["title", "name", "password", "username", "login", "url", "website", "login_uri", "totp", "otpauth", "notes", "extra", "grouping", "folder", "fav", "type"];
+        for (i, header) in headers.iter().enumerate() {
+            if !known.contains(&header.as_str()) {
+                record.add_field(header, row.get(i).unwrap_or(""));
+            }
+        }
+        record.set_notes(&value(&["notes", "extra"]));
+        records.push(record);
+    }
+    return Ok(records);
+}
+
+/// Parses the content of an export file into records.
+pub fn parse(format: ImportFormat, contents: &str) -> Result<Vec<ImportRecord>> {
+    match format {
+        ImportFormat::KeePassXml => parse_keepass_xml(contents),
+        ImportFormat::BitwardenJson => parse_bitwarden_json(contents),
+        ImportFormat::OnePassword1Pif => parse_1pif(contents),
+        ImportFormat::OnePasswordCsv | ImportFormat::LastPassCsv => parse_csv(contents, format),
+    }
+}
+
+/// What to do when an entry with the same name already exists.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConflictStrategy {
+    /// Keep the existing entry, and don't import the record.
+    Skip,
+    /// Replace the existing entry with the record.
+    Overwrite,
+    /// Import the record with a number added to the name.
+    Rename,
+}
+
+/// What the import will do with a record.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImportAction {
+    /// Create a new entry with the name.
+    Create(String),
+    /// Replace the existing entry with the name.
+    Overwrite(String),
+    /// Create a new entry with another name, since the name of the record was taken.
+    Rename {
+        /// The name of the record.
+        from: String,
+        /// The name the entry gets.
+        to: String,
+    },
+    /// Don't import the record, since the name was taken.
+    Skip(String),
+}
+
+fn exists(dir: &path::Path, name: &str) -> bool {
+    dir.join(format!("{}.gpg", name)).exists()
+}
+
+/// Decides what to do with every record, without changing anything. This is the dry run that
+/// can be shown to the user before the import. Records in the import with the same name are
+/// handled like conflicts with existing entries.
+pub fn plan(records: &Vec<ImportRecord>, strategy: ConflictStrategy, password_store_dir: Arc<Option<String>>) -> Result<Vec<ImportAction>> {
+    let dir = password_dir(password_store_dir)?;
+    let mut taken: HashSet<String> = HashSet::new();
+
+    let mut actions = vec![];
+    for record in records {
+        let name = record.name.clone();
+        let conflict = taken.contains(&name) || exists(&dir, &name);
+
+        let action = if !conflict {
+            ImportAction::Create(name.clone())
+        } else {
+            match strategy {
+                ConflictStrategy::Skip => ImportAction::Skip(name.clone()),
+                ConflictStrategy::Overwrite if !taken.contains(&name) => ImportAction::Overwrite(name.clone()),
+                _ => {
+                    let mut n = 2;
+                    while taken.contains(&format!("{}-{}", name, n)) || exists(&dir, &format!("{}-{}", name, n)) {
+                        n += 1;
+                    }
+                    ImportAction::Rename { from: name.clone(), to: format!("{}-{}", name, n) }
+                },
+            }
+        };
+
+        match &action {
+            ImportAction::Create(n) | ImportAction::Overwrite(n) | ImportAction::Rename { to: n, .. } => {
+                taken.insert(n.clone());
+            },
+            ImportAction::Skip(_) => {},
+        }
+        actions.push(action);
+    }
+    return Ok(actions);
+}
+
+/// The result of an import.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    /// The names of the entries that were written.
+    pub imported: Vec<String>,
+    /// The names of the records that were skipped because of conflicts.
+    pub skipped: Vec<String>,
+    /// The records that couldn't be written, with the error.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Writes the records to the store as planned by `plan`, and commits all of them in a single
+/// commit if a repository is supplied. If the commit fails, the store is restored.
+pub fn import(records: &Vec<ImportRecord>, strategy: ConflictStrategy, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<ImportSummary> {
+    let dir = password_dir(password_store_dir.clone())?;
+    let actions = plan(records, strategy, password_store_dir)?;
+
+    let mut summary = ImportSummary::default();
+    let mut created: Vec<path::PathBuf> = vec![];
+    let mut overwritten: Vec<(path::PathBuf, Vec<u8>)> = vec![];
+
+    for (record, action) in records.iter().zip(actions) {
+        let content = record.to_pass_format();
+        let res = match &action {
+            ImportAction::Skip(name) => {
+                summary.skipped.push(name.clone());
+                continue;
+            },
+            ImportAction::Create(name) | ImportAction::Rename { to: name, .. } => {
+                write_new_password_file(&dir, name, &content).map(|(path, _)| {
+                    created.push(path);
+                    name.clone()
+                })
+            },
+            ImportAction::Overwrite(name) => {
+                let path = dir.join(format!("{}.gpg", name));
+                fs::read(&path).map_err(Error::from)
+                    .and_then(|original| {
+                        if let Some(policy) = Policy::for_path(&dir, &path)? {
+                            policy.enforce(&content)?;
+                        }
+                        let ciphertext = encrypt_for_path(&dir, &path, &content)?;
+                        write_atomically(&path, &ciphertext)?;
+                        overwritten.push((path.clone(), original));
+                        Ok(name.clone())
+                    })
+            },
+        };
+
+        match res {
+            Ok(name) => summary.imported.push(name),
+            Err(err) => summary.failed.push((record.name.clone(), err.to_string())),
+        }
+    }
+
+    if repo_opt.is_none() || summary.imported.is_empty() {
+        return Ok(summary);
+    }
+
+    let paths: Vec<String> = summary.imported.iter().map(|name| format!("{}.gpg", name)).collect();
+    let message = format!("Imported {} passwords using ripasso", summary.imported.len());
+
+    if let Err(err) = add_and_commit(repo_opt, &paths, &message) {
+        for path in created {
+            let _ = fs::remove_file(path);
+        }
+        for (path, original) in overwritten {
+            let _ = write_atomically(&path, &original);
+        }
+        return Err(err);
+    }
+
+    return Ok(summary);
+}
+
+#[cfg(test)]
+mod test;
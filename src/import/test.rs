@@ -0,0 +1,119 @@
+use super::*;
+
+#[test]
+fn keepass_xml_groups_become_folders() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<KeePassFile>
+  <Root>
+    <Group>
+      <Name>Database</Name>
+      <Group>
+        <Name>Servers</Name>
+        <Entry>
+          <String><Key>Title</Key><Value>db/primary</Value></String>
+          <String><Key>UserName</Key><Value>postgres</Value></String>
+          <String><Key>Password</Key><Value>hunter2</Value></String>
+          <String><Key>URL</Key><Value>postgres://db.example.com</Value></String>
+          <String><Key>Notes</Key><Value>rotate yearly</Value></String>
+          <String><Key>Port</Key><Value>5432</Value></String>
+          <History>
+            <Entry>
+              <String><Key>Password</Key><Value>old</Value></String>
+            </Entry>
+          </History>
+        </Entry>
+      </Group>
+    </Group>
+  </Root>
+</KeePassFile>"#;
+
+    let records = parse(ImportFormat::KeePassXml, xml).unwrap();
+
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].name, "Servers/db-primary");
+    assert_eq!(records[0].to_pass_format(), "hunter2\nlogin: postgres\nurl: postgres://db.example.com\nPort: 5432\nrotate yearly\n");
+}
+
+#[test]
+fn bitwarden_json_uses_folders_and_fields() {
+    let json = r#"{"encrypted": false,
+        "folders": [{"id": "f1", "name": "Work/Cloud"}],
+        "items": [
+            {"type": 1, "name": "AWS", "folderId": "f1", "notes": null,
+             "login": {"username": "admin", "password": "s3cret", "uris": [{"uri": "https://aws.amazon.com"}], "totp": null},
+             "fields": [{"name": "account", "value": "1234"}]},
+            {"type": 2, "name": "Wifi", "folderId": null, "notes": "the guest network"}
+        ]}"#;
+
+    let records = parse(ImportFormat::BitwardenJson, json).unwrap();
+
+    assert_eq!(records[0].name, "Work/Cloud/AWS");
+    assert_eq!(records[0].to_pass_format(), "s3cret\nlogin: admin\nurl: https://aws.amazon.com\naccount: 1234\n");
+    assert_eq!(records[1].name, "Wifi");
+    assert_eq!(records[1].to_pass_format(), "\nthe guest network\n");
+}
+
+#[test]
+fn one_password_1pif_skips_trashed() {
+    let pif = r#"{"title": "Mail", "location": "https://mail.example.com", "typeName": "webforms.WebForm", "secureContents": {"fields": [{"designation": "username", "value": "alice"}, {"designation": "password", "value": "pw1"}], "notesPlain": "personal"}}
+***5642bee8-a5ff-11dc-8314-0800200c9a66***
+{"title": "Old", "trashed": true, "secureContents": {"password": "pw2"}}
+***5642bee8-a5ff-11dc-8314-0800200c9a66***
+"#;
+
+    let records = parse(ImportFormat::OnePassword1Pif, pif).unwrap();
+
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].to_pass_format(), "pw1\nurl: https://mail.example.com\nlogin: alice\npersonal\n");
+}
+
+#[test]
+fn lastpass_csv_with_groups_and_secure_notes() {
+    let csv = "url,username,password,totp,extra,name,grouping,fav\nhttps://example.com,bob,pw,,\"multi\nline\",Example,Shared-Ops\\Web,0\nhttp://sn,,,,a note,Note,,0\n";
+
+    let records = parse(ImportFormat::LastPassCsv, csv).unwrap();
+
+    assert_eq!(records[0].name, "Shared-Ops/Web/Example");
+    assert_eq!(records[0].to_pass_format(), "pw\nlogin: bob\nurl: https://example.com\nmulti\nline\n");
+    assert_eq!(records[1].to_pass_format(), "\na note\n");
+}
+
+#[test]
+fn one_password_csv_keeps_unknown_columns() {
+    let csv = "Title,Username,Password,URL,Notes,Security question\nBank,carol,pw,https://bank.example.com,,first pet\n";
+
+    let records = parse(ImportFormat::OnePasswordCsv, csv).unwrap();
+
+    assert_eq!(records[0].to_pass_format(), "pw\nlogin: carol\nurl: https://bank.example.com\nsecurity question: first pet\n");
+}
+
+#[test]
+fn plan_handles_conflicts() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("mail.gpg"), "").unwrap();
+    let store = Arc::new(Some(dir.path().to_string_lossy().into_owned()));
+
+    let records = vec![ImportRecord::new(&[], "mail"), ImportRecord::new(&[], "bank"), ImportRecord::new(&[], "bank")];
+
+    assert_eq!(plan(&records, ConflictStrategy::Skip, store.clone()).unwrap(),
+               vec![ImportAction::Skip("mail".to_string()), ImportAction::Create("bank".to_string()), ImportAction::Skip("bank".to_string())]);
+    assert_eq!(plan(&records, ConflictStrategy::Overwrite, store.clone()).unwrap(),
+               vec![ImportAction::Overwrite("mail".to_string()), ImportAction::Create("bank".to_string()), ImportAction::Rename { from: "bank".to_string(), to: "bank-2".to_string() }]);
+    assert_eq!(plan(&records, ConflictStrategy::Rename, store).unwrap()[0],
+               ImportAction::Rename { from: "mail".to_string(), to: "mail-2".to_string() });
+}
+
+#[test]
+fn names_are_sanitized() {
+    assert_eq!(ImportRecord::new(&["..hidden".to_string(), "".to_string()], " a/b ").name, "hidden/a-b");
+    assert_eq!(ImportRecord::new(&[], "").name, "unnamed");
+}
+
+#[test]
+fn to_pass_format_keeps_every_password_line() {
+    let mut record = ImportRecord::new(&[], "vpn");
+    record.password = SecretString::from("first\nsecond\nthird");
+    record.add_field("totp", "otpauth://totp/vpn\nsecret");
+
+    assert_eq!(record.to_pass_format(), "first\npassword-2: second\npassword-3: third\ntotp: otpauth://totp/vpn secret\n");
+}
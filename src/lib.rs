@@ -11,6 +11,38 @@ extern crate log;
 /// This is the library part of ripasso, it implements the functions needed to manipulate a pass
 /// directory.
 pub mod pass;
+/// This is the library that caches the git metadata of the password list between runs.
+pub mod cache;
 /// This is the library that handles password generation, based on the long word list from EFF
 /// https://www.eff.org/sv/deeplinks/2016/07/new-wordlists-random-passphrases
 pub mod words;
+/// This is the library that generates passwords from character classes, for sites that don't
+/// accept passphrases.
+pub mod generator;
+/// This is the library that estimates how hard a password is to guess.
+pub mod strength;
+/// This is the library that reads the password policies of folders in the store.
+pub mod policy;
+/// This is the library that audits the password store for reused, weak and old passwords.
+pub mod audit;
+/// This is the library that checks passwords against a local copy of the Pwned Passwords dataset.
+pub mod breach;
+/// This is the library that imports the exports of other password managers into the store.
+pub mod import;
+/// This is the library that exports the store to other formats, for audits and disaster recovery.
+pub mod export;
+/// This is the library that shares single entries with people outside of the team.
+pub mod share;
+/// This is the library that makes signed backups of the store and restores them.
+pub mod backup;
+/// This is the library that keeps decrypted and generated secrets in memory that is wiped when
+/// they are dropped.
+pub mod secret;
+/// This is the library that copies secrets to the clipboard and removes them again.
+pub mod clipboard;
+/// This is the library that reads and writes `.gpg-id` files, without losing comments or the
+/// order of the lines.
+pub mod gpg_id;
+
+#[cfg(test)]
+mod test_helpers;
@@ -15,11 +15,13 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::fmt;
 use std::fs;
 use std::fs::File;
 use std::path;
 use std::str;
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
 use std::thread;
 use std::time::Duration;
 
@@ -36,9 +38,16 @@ extern crate dirs;
 use std;
 use std::io;
 use std::string;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use git2::{Oid, Repository};
 
+use crate::cache::{CachedMetadata, MetadataCache};
+use crate::gpg_id;
+use crate::gpg_id::{GpgIdFile, IdKind};
+use crate::policy::{Policy, Violation};
+use crate::secret::SecretString;
+use zeroize::Zeroize;
+
 /// Convenience type for Results
 type Result<T> = std::result::Result<T, Error>;
 
@@ -48,6 +57,13 @@ pub type PasswordList = Arc<Mutex<Vec<PasswordEntry>>>;
 /// The type for how we handle git repositories
 pub type GitRepo = Arc<Option<Mutex<git2::Repository>>>;
 
+/// All commits that only reencrypts the store starts with this message, so that they can be told
+/// apart from commits that changes the content of the passwords.
+const REENCRYPT_COMMIT_PREFIX: &str = "Reencrypt password store";
+
+/// The number of threads that decrypts and encrypts entries during a reencryption.
+const REENCRYPT_WORKERS: usize = 4;
+
 /// A enum that contains the different types of errors that the library returns as part of Result's.
 #[derive(Debug)]
 pub enum Error {
@@ -62,6 +78,239 @@ pub enum Error {
     PatternError(glob::PatternError),
     GlobError(glob::GlobError),
     Utf8Error(std::str::Utf8Error),
+    /// The password store directory doesn't exist.
+    StoreNotFound {
+        /// Where the store was expected.
+        path: path::PathBuf,
+    },
+    /// The folder doesn't exist in the password store.
+    FolderNotFound {
+        /// The folder, relative to the store.
+        path: path::PathBuf,
+    },
+    /// No key in the keyring matches the key id.
+    KeyNotFound {
+        /// The key id, fingerprint or email address that was looked up.
+        key_id: String,
+    },
+    /// The key is in the keyring, but it can't be used for encryption, for example because it
+    /// has expired or is revoked.
+    KeyCannotEncrypt {
+        /// The key id, fingerprint or email address that was looked up.
+        key_id: String,
+    },
+    /// The key id matches more than one key in the keyring.
+    AmbiguousKey {
+        /// The key id, fingerprint or email address that was looked up.
+        key_id: String,
+        /// The number of keys that match.
+        matches: usize,
+    },
+    /// There is no `.gpg-id` file in the folder of the entry or in any of its parent folders.
+    RecipientsNotFound {
+        /// The password file.
+        path: path::PathBuf,
+    },
+    /// There is already an entry with the same name.
+    EntryExists {
+        /// The password file of the existing entry.
+        path: path::PathBuf,
+    },
+    /// The path would end up outside of the password store.
+    PathEscape {
+        /// The path that was refused.
+        path: path::PathBuf,
+    },
+    /// The changes from the remote couldn't be merged.
+    MergeConflict {
+        /// The files with conflicts, relative to the store.
+        paths: Vec<path::PathBuf>,
+    },
+    /// The git repository doesn't have the remote.
+    NoRemote {
+        /// The name of the remote.
+        name: String,
+    },
+    /// The password breaks the policy of the folder, and the policy refuses such passwords.
+    PolicyViolation {
+        /// The requirements that the password doesn't fulfill.
+        violations: Vec<Violation>,
+    },
+    /// The git repository is used by another operation at the moment.
+    RepositoryBusy,
+    /// The person isn't one of the recipients in the `.gpg-id` file.
+    NotARecipient {
+        /// The key id of the person.
+        key_id: String,
+    },
+    /// The person is already one of the recipients in the `.gpg-id` file.
+    AlreadyARecipient {
+        /// The key id of the person.
+        key_id: String,
+    },
+    /// Removing the person would leave a `.gpg-id` file without any recipients.
+    LastRecipient {
+        /// The `.gpg-id` file.
+        path: path::PathBuf,
+    },
+    /// None of the keys in a `.gpg-id` file can be used for encryption.
+    NoUsableKeys {
+        /// The `.gpg-id` file.
+        path: path::PathBuf,
+    },
+    /// A key can't decrypt, because the secret key or the smartcard isn't available.
+    SecretKeyUnavailable {
+        /// The key id of the key.
+        key_id: String,
+    },
+    /// A key file doesn't contain any public keys.
+    NoKeysInFile {
+        /// The key file.
+        path: path::PathBuf,
+    },
+    /// An entry wasn't encrypted for a key, after it was reencrypted for it.
+    NotEncryptedFor {
+        /// The name of the entry.
+        name: String,
+        /// The key id of the key.
+        key_id: String,
+    },
+    /// A key file contains several public keys, where only one is expected.
+    SeveralKeysInFile {
+        /// The key file.
+        path: path::PathBuf,
+        /// The number of keys in the file.
+        keys: usize,
+    },
+}
+
+/// The kind of an `Error`, which stays the same when the context or the wording of the error
+/// changes, so that frontends and tests can match on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    Io,
+    Git,
+    Gpg,
+    Encoding,
+    Watch,
+    Path,
+    StoreNotFound,
+    FolderNotFound,
+    KeyNotFound,
+    KeyCannotEncrypt,
+    AmbiguousKey,
+    RecipientsNotFound,
+    EntryExists,
+    PathEscape,
+    MergeConflict,
+    NoRemote,
+    PolicyViolation,
+    RepositoryBusy,
+    NotARecipient,
+    AlreadyARecipient,
+    LastRecipient,
+    NoUsableKeys,
+    SecretKeyUnavailable,
+    NotEncryptedFor,
+    NoKeysInFile,
+    SeveralKeysInFile,
+    Other,
+}
+
+impl Error {
+    /// Returns the kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::IO(_) => ErrorKind::Io,
+            Error::Git(_) => ErrorKind::Git,
+            Error::GPG(_) => ErrorKind::Gpg,
+            Error::UTF8(_) | Error::Utf8Error(_) => ErrorKind::Encoding,
+            Error::Notify(_) => ErrorKind::Watch,
+            Error::PathError(_) | Error::PatternError(_) | Error::GlobError(_) => ErrorKind::Path,
+            Error::Generic(_) | Error::GenericDyn(_) => ErrorKind::Other,
+            Error::StoreNotFound { .. } => ErrorKind::StoreNotFound,
+            Error::FolderNotFound { .. } => ErrorKind::FolderNotFound,
+            Error::KeyNotFound { .. } => ErrorKind::KeyNotFound,
+            Error::KeyCannotEncrypt { .. } => ErrorKind::KeyCannotEncrypt,
+            Error::AmbiguousKey { .. } => ErrorKind::AmbiguousKey,
+            Error::RecipientsNotFound { .. } => ErrorKind::RecipientsNotFound,
+            Error::EntryExists { .. } => ErrorKind::EntryExists,
+            Error::PathEscape { .. } => ErrorKind::PathEscape,
+            Error::MergeConflict { .. } => ErrorKind::MergeConflict,
+            Error::NoRemote { .. } => ErrorKind::NoRemote,
+            Error::PolicyViolation { .. } => ErrorKind::PolicyViolation,
+            Error::RepositoryBusy => ErrorKind::RepositoryBusy,
+            Error::NotARecipient { .. } => ErrorKind::NotARecipient,
+            Error::AlreadyARecipient { .. } => ErrorKind::AlreadyARecipient,
+            Error::LastRecipient { .. } => ErrorKind::LastRecipient,
+            Error::NoUsableKeys { .. } => ErrorKind::NoUsableKeys,
+            Error::SecretKeyUnavailable { .. } => ErrorKind::SecretKeyUnavailable,
+            Error::NotEncryptedFor { .. } => ErrorKind::NotEncryptedFor,
+            Error::NoKeysInFile { .. } => ErrorKind::NoKeysInFile,
+            Error::SeveralKeysInFile { .. } => ErrorKind::SeveralKeysInFile,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::IO(err) => write!(f, "{}", err),
+            Error::Git(err) => write!(f, "git: {}", err.message()),
+            Error::GPG(err) => write!(f, "gpg: {}", err),
+            Error::UTF8(err) => write!(f, "{}", err),
+            Error::Notify(err) => write!(f, "{}", err),
+            Error::Generic(message) => write!(f, "{}", message),
+            Error::GenericDyn(message) => write!(f, "{}", message),
+            Error::PathError(err) => write!(f, "{}", err),
+            Error::PatternError(err) => write!(f, "{}", err),
+            Error::GlobError(err) => write!(f, "{}", err),
+            Error::Utf8Error(err) => write!(f, "{}", err),
+            Error::StoreNotFound { path } => write!(f, "the password store {} doesn't exist", path.display()),
+            Error::FolderNotFound { path } => write!(f, "{} is not a folder in the password store", path.display()),
+            Error::KeyNotFound { key_id } => write!(f, "can't find the key {} in the keyring, please import it first", key_id),
+            Error::KeyCannotEncrypt { key_id } => write!(f, "the key {} can't be used for encryption, it might be expired or revoked", key_id),
+            Error::AmbiguousKey { key_id, matches } => write!(f, "the key id {} is ambiguous, it matches {} keys", key_id, matches),
+            Error::RecipientsNotFound { path } => write!(f, "no .gpg-id file found for {}", path.display()),
+            Error::EntryExists { path } => write!(f, "{} already exists", path.display()),
+            Error::PathEscape { path } => write!(f, "{} is outside of the password store", path.display()),
+            Error::MergeConflict { paths } => {
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "the changes from the remote conflict with local changes in {}", paths.join(", "))
+            },
+            Error::NoRemote { name } => write!(f, "the git repository has no remote called {}", name),
+            Error::PolicyViolation { violations } => {
+                let descriptions: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                write!(f, "the password breaks the folder policy: {}", descriptions.join(", "))
+            },
+            Error::RepositoryBusy => write!(f, "the git repository is busy with another operation"),
+            Error::NotARecipient { key_id } => write!(f, "{} isn't in the list of key ids", key_id),
+            Error::AlreadyARecipient { key_id } => write!(f, "{} is already in the list of key ids", key_id),
+            Error::LastRecipient { path } => write!(f, "can't delete the last encryption key in {}", path.display()),
+            Error::NoUsableKeys { path } => write!(f, "none of the keys in {} are in the keyring", path.display()),
+            Error::SecretKeyUnavailable { key_id } => write!(f, "the key {} can't decrypt, is the secret key or smartcard available?", key_id),
+            Error::NotEncryptedFor { name, key_id } => write!(f, "{} wasn't encrypted for the key {}", name, key_id),
+            Error::NoKeysInFile { path } => write!(f, "no public keys found in {}", path.display()),
+            Error::SeveralKeysInFile { path, keys } => write!(f, "{} contains {} public keys, it should contain one", path.display(), keys),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IO(err) => Some(err),
+            Error::Git(err) => Some(err),
+            Error::GPG(err) => Some(err),
+            Error::UTF8(err) => Some(err),
+            Error::Notify(err) => Some(err),
+            Error::PathError(err) => Some(err),
+            Error::PatternError(err) => Some(err),
+            Error::GlobError(err) => Some(err),
+            Error::Utf8Error(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 impl From<io::Error> for Error {
@@ -120,7 +369,7 @@ impl From<std::str::Utf8Error> for Error {
 
 /// A git commit for a password might be signed by a gpg key, and this signature's verification
 /// state is one of these values.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum SignatureStatus {
     /// Everything is fine with the signature, corresponds to the gpg status of GREEN
     GoodSignature,
@@ -176,79 +425,87 @@ impl PasswordEntry {
 
     /// creates a `PasswordEntry` by running git blame on the specified path
     pub fn load_from_git(base: &path::PathBuf, path: &path::PathBuf, repo_opt: GitRepo) -> Result<PasswordEntry> {
-        let (update_time, committed_by, signature_status) = read_git_meta_data(base, path, repo_opt.clone());
+        let (updated, committed_by, signature_status) = match read_git_meta_data(base, path, repo_opt.clone()) {
+            Ok((update_time, committed_by, signature_status)) => (Some(update_time), committed_by.ok(), signature_status.ok()),
+            Err(_) => (None, None, None),
+        };
 
         Ok(PasswordEntry {
             name: to_name(base, path),
             meta: "".to_string(),
             base: base.to_path_buf(),
             path: path.to_path_buf(),
-            updated: match update_time {
-                Ok(p) => Some(p),
-                Err(_) => None,
-            },
-            committed_by: match committed_by {
-                Ok(p) => Some(p),
-                Err(_) => None,
-            },
-            signature_status: match signature_status {
-                Ok(ss) => Some(ss),
-                Err(_) => None,
-            },
+            updated,
+            committed_by,
+            signature_status,
             filename: path.to_string_lossy().into_owned().clone(),
         })
     }
 
     /// Decrypts and returns the full content of the PasswordEntry
-    pub fn secret(&self) -> Result<String> {
+    pub fn secret(&self) -> Result<SecretString> {
         let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
         let mut input = File::open(&self.filename)?;
         let mut output = Vec::new();
-        ctx.decrypt(&mut input, &mut output)?;
-        Ok(String::from_utf8(output)?)
+        if let Err(err) = ctx.decrypt(&mut input, &mut output) {
+            output.zeroize();
+            return Err(Error::GPG(err));
+        }
+        SecretString::from_utf8(output)
     }
 
     /// Decrypts and returns the first line of the PasswordEntry
-    pub fn password(&self) -> Result<String> {
-        Ok(self.secret()?.split('\n').take(1).collect())
+    pub fn password(&self) -> Result<SecretString> {
+        Ok(self.secret()?.first_line())
     }
 
-    fn update_internal(&self, secret: String, password_store_dir: Arc<Option<String>>) -> Result<()> {
-        let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
-
-        let mut keys = Vec::new();
+    fn update_internal(&self, secret: &str, password_store_dir: Arc<Option<String>>) -> Result<()> {
+        let ciphertext = encrypt_for_path(&password_dir(password_store_dir)?, &self.path, secret)?;
 
-        for recipient in Recipient::all_recipients(password_store_dir)? {
-            keys.push(ctx.get_key(recipient.key_id)?);
-        }
-
-        let mut ciphertext = Vec::new();
-        ctx.encrypt(&keys, secret, &mut ciphertext)?;
+        return write_atomically(&self.path, &ciphertext);
+    }
 
-        let mut output = File::create(&self.filename)?;
-        output.write_all(&ciphertext)?;
-        Ok(())
+    /// Returns the password policy of the folder that the entry is in, if there is one.
+    pub fn policy(&self, password_store_dir: Arc<Option<String>>) -> Result<Option<Policy>> {
+        return Policy::for_path(&password_dir(password_store_dir)?, &self.path);
     }
 
     /// Updates the password store entry with new content, and commits those to git if a repository
-    /// is supplied.
-    pub fn update(&self, secret: String, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<()> {
-        self.update_internal(secret, password_store_dir)?;
+    /// is supplied. If the commit fails, the entry is restored to its previous content.
+    ///
+    /// If the folder has a password policy, the new password is checked against it. Violations
+    /// are either returned as warnings, or make the update fail, depending on the policy.
+    pub fn update(&self, secret: SecretString, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<Vec<Violation>> {
+        let violations = match self.policy(password_store_dir.clone())? {
+            Some(policy) => policy.enforce(&secret)?,
+            None => vec![],
+        };
+
+        let original = fs::read(&self.path)?;
+
+        self.update_internal(&secret, password_store_dir)?;
 
         if repo_opt.is_none() {
-            return Ok(());
+            return Ok(violations);
         }
 
         let message = format!("Edit password for {} using ripasso", &self.name);
 
-        add_and_commit(repo_opt, &vec![format!("{}.gpg", &self.name)], &message)?;
+        let res = add_and_commit(repo_opt, &vec![format!("{}.gpg", &self.name)], &message);
+        if res.is_err() {
+            write_atomically(&self.path, &original)?;
+            return Err(res.unwrap_err());
+        }
 
-        return Ok(());
+        return Ok(violations);
     }
 
     /// Removes this entry from the filesystem and commit that to git if a repository is supplied.
+    /// If the commit fails, the file is restored.
     pub fn delete_file(&self, repo_opt: GitRepo) -> Result<()> {
-        let res = Ok(std::fs::remove_file(&self.filename)?);
+        let original = fs::read(&self.path)?;
+
+        std::fs::remove_file(&self.filename)?;
 
         if repo_opt.is_none() {
             return Ok(());
@@ -256,9 +513,13 @@ impl PasswordEntry {
 
         let message = format!("Removed password file for {} using ripasso", &self.name);
 
-        remove_and_commit(repo_opt, &vec![format!("{}.gpg", &self.name)], &message)?;
+        let res = remove_and_commit(repo_opt, &vec![format!("{}.gpg", &self.name)], &message);
+        if res.is_err() {
+            write_atomically(&self.path, &original)?;
+            return Err(res.unwrap_err());
+        }
 
-        return res;
+        return Ok(());
     }
 
     /// Returns a list of all password entries in the store.
@@ -283,11 +544,18 @@ impl PasswordEntry {
     /// Reencrypt all the entries in the store, for example when a new collaborator is added
     /// to the team.
     pub fn reencrypt_all_password_entries(repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<()> {
-        let mut names: Vec<String> = Vec::new();
-        for entry in PasswordEntry::all_password_entries(repo_opt.clone(), password_store_dir.clone())? {
-            entry.update_internal(entry.secret()?, password_store_dir.clone())?;
-            names.push(format!("{}.gpg", &entry.name));
-        }
+        return PasswordEntry::reencrypt_all_password_entries_with_progress(repo_opt, password_store_dir, &mut |_| {});
+    }
+
+    /// Reencrypt all the entries in the store that aren't already encrypted for the right
+    /// recipients, and report the progress to `progress` while doing it. See `reencrypt_entries`.
+    pub fn reencrypt_all_password_entries_with_progress(repo_opt: GitRepo, password_store_dir: Arc<Option<String>>, progress: &mut dyn FnMut(ReencryptionProgress)) -> Result<()> {
+        let entries = PasswordEntry::all_password_entries(repo_opt.clone(), password_store_dir.clone())?;
+
+        let mut names: Vec<String> = reencrypt_entries(&entries, password_store_dir.clone(), progress)?
+            .into_iter()
+            .map(|name| format!("{}.gpg", name))
+            .collect();
         names.push(".gpg-id".to_string());
 
         if repo_opt.is_none() {
@@ -295,7 +563,7 @@ impl PasswordEntry {
         }
 
         let keys = Recipient::all_recipients(password_store_dir)?.into_iter().map(|s| format!("0x{}, ", s.key_id)).collect::<String>();
-        let message = format!("Reencrypt password store with new GPG ids {}", keys);
+        let message = format!("{} with new GPG ids {}", REENCRYPT_COMMIT_PREFIX, keys);
 
         add_and_commit(repo_opt, &names, &message)?;
 
@@ -379,7 +647,7 @@ fn commit(repo: &git2::Repository, signature: &git2::Signature, message: &String
 pub fn add_and_commit(repo_opt: GitRepo, paths: &Vec<String>, message: &str) -> Result<git2::Oid> {
     let repo_res = (*repo_opt).as_ref().unwrap().try_lock();
     if repo_res.is_err() {
-        return Err(Error::GenericDyn(format!("{:?}", repo_res.err())))
+        return Err(Error::RepositoryBusy);
     }
     let repo = repo_res.unwrap();
 
@@ -387,29 +655,15 @@ pub fn add_and_commit(repo_opt: GitRepo, paths: &Vec<String>, message: &str) ->
     for path in paths {
         index.add_path(path::Path::new(path))?;
     }
-    let oid = index.write_tree()?;
-    let signature = repo.signature()?;
-    let parent_commit_res = find_last_commit(&repo);
-    let mut parents = vec![];
-    let parent_commit;
-    if !parent_commit_res.is_err() {
-        parent_commit = parent_commit_res?;
-        parents.push(&parent_commit);
-    }
-    let tree = repo.find_tree(oid)?;
-
-    let oid = commit(&repo, &signature, &message.to_string(), &tree, &parents)?;
-    let obj = repo.find_object(oid, None)?;
-    repo.reset(&obj, git2::ResetType::Hard, None)?;
 
-    return Ok(oid);
+    return commit_index(&repo, &mut index, message);
 }
 
 /// Remove a file from the store, and commit the deletion to the supplied git repository.
 fn remove_and_commit(repo_opt: GitRepo, paths: &Vec<String>, message: &str) -> Result<git2::Oid> {
     let repo_res = (*repo_opt).as_ref().unwrap().try_lock();
     if repo_res.is_err() {
-        return Err(Error::GenericDyn(format!("{:?}", repo_res.err())))
+        return Err(Error::RepositoryBusy);
     }
     let repo = repo_res.unwrap();
 
@@ -417,6 +671,21 @@ fn remove_and_commit(repo_opt: GitRepo, paths: &Vec<String>, message: &str) -> R
     for path in paths {
         index.remove_path(path::Path::new(path))?;
     }
+
+    return commit_index(&repo, &mut index, message);
+}
+
+/// Commits the staged content of `index`. If the commit can't be made, the staged changes are
+/// thrown away so that the index matches what is on disk again.
+fn commit_index(repo: &git2::Repository, index: &mut git2::Index, message: &str) -> Result<git2::Oid> {
+    let res = commit_index_internal(repo, index, message);
+    if res.is_err() {
+        let _ = index.read(true);
+    }
+    return res;
+}
+
+fn commit_index_internal(repo: &git2::Repository, index: &mut git2::Index, message: &str) -> Result<git2::Oid> {
     let oid = index.write_tree()?;
     let signature = repo.signature()?;
     let parent_commit_res = find_last_commit(&repo);
@@ -443,12 +712,12 @@ pub fn push(repo_opt: GitRepo) -> Result<()> {
 
     let repo_res = (*repo_opt).as_ref().unwrap().try_lock();
     if repo_res.is_err() {
-        return Err(Error::GenericDyn(format!("{:?}", repo_res.err())))
+        return Err(Error::RepositoryBusy);
     }
     let repo = repo_res.unwrap();
 
     let mut ref_status = None;
-    let mut origin = repo.find_remote("origin")?;
+    let mut origin = find_origin(&repo)?;
     let res = {
         let mut callbacks = git2::RemoteCallbacks::new();
         callbacks.credentials(|_url, username, allowed| {
@@ -480,19 +749,28 @@ pub fn push(repo_opt: GitRepo) -> Result<()> {
     }
 }
 
-/// Pull new changes from the remote git repository.
-pub fn pull(repo_opt: GitRepo) -> Result<()> {
+/// Returns the remote called origin, that push and pull use.
+fn find_origin(repo: &git2::Repository) -> Result<git2::Remote> {
+    return repo.find_remote("origin").map_err(|err| match err.code() {
+        git2::ErrorCode::NotFound => Error::NoRemote { name: "origin".to_string() },
+        _ => Error::Git(err),
+    });
+}
+
+/// Pull new changes from the remote git repository. Returns the HEAD before and after the pull,
+/// which are the same if there was nothing new, or None if the store isn't a git repository.
+pub fn pull(repo_opt: GitRepo) -> Result<Option<(Oid, Oid)>> {
     if repo_opt.is_none() {
-        return Ok(());
+        return Ok(None);
     }
 
     let repo_res = (*repo_opt).as_ref().unwrap().try_lock();
     if repo_res.is_err() {
-        return Err(Error::GenericDyn(format!("{:?}", repo_res.err())))
+        return Err(Error::RepositoryBusy);
     }
     let repo = repo_res.unwrap();
 
-    let mut remote = repo.find_remote("origin")?;
+    let mut remote = find_origin(&repo)?;
 
     let mut cb = git2::RemoteCallbacks::new();
     cb.credentials(|_url, username, allowed| {
@@ -519,21 +797,36 @@ pub fn pull(repo_opt: GitRepo) -> Result<()> {
     let (_, behind) = repo.graph_ahead_behind(head_oid, remote_oid)?;
 
     if behind == 0 {
-        return Ok(());
+        return Ok(Some((head_oid, head_oid)));
     }
 
     let remote_annotated_commit = repo.find_annotated_commit(remote_oid)?;
     let remote_commit = repo.find_commit(remote_oid)?;
     repo.merge(&vec![&remote_annotated_commit], None, None)?;
 
-    //commit it
     let mut index = repo.index()?;
+    if index.has_conflicts() {
+        let mut paths = vec![];
+        for conflict in index.conflicts()? {
+            let conflict = conflict?;
+            if let Some(entry) = conflict.our.or(conflict.their).or(conflict.ancestor) {
+                paths.push(path::PathBuf::from(String::from_utf8_lossy(&entry.path).into_owned()));
+            }
+        }
+
+        // leave the store as it was before the pull, like git merge --abort
+        repo.cleanup_state()?;
+        repo.reset(find_last_commit(&repo)?.as_object(), git2::ResetType::Hard, None)?;
+        return Err(Error::MergeConflict { paths });
+    }
+
+    //commit it
     let oid = index.write_tree()?;
     let signature = repo.signature()?;
     let parent_commit = find_last_commit(&repo)?;
     let tree = repo.find_tree(oid)?;
     let message = "pull and merge by ripasso";
-    let _commit = repo.commit(Some("HEAD"), //  point HEAD to our new commit
+    let new_head = repo.commit(Some("HEAD"), //  point HEAD to our new commit
                              &signature, // author
                              &signature, // committer
                              message, // commit message
@@ -542,24 +835,234 @@ pub fn pull(repo_opt: GitRepo) -> Result<()> {
 
     //cleanup
     repo.cleanup_state()?;
-    return Ok(());
+    return Ok(Some((head_oid, new_head)));
+}
+
+/// A change to a file between two commits, the paths are relative to the repository.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathChange {
+    /// The file was added.
+    Added(path::PathBuf),
+    /// The content of the file changed.
+    Modified(path::PathBuf),
+    /// The file was deleted.
+    Deleted(path::PathBuf),
+    /// The file was moved from the first path to the second.
+    Renamed(path::PathBuf, path::PathBuf),
+}
+
+/// Returns the files that differ between the commits `old` and `new`.
+pub fn changed_paths(repo: &git2::Repository, old: Oid, new: Oid) -> Result<Vec<PathChange>> {
+    let old_tree = repo.find_commit(old)?.tree()?;
+    let new_tree = repo.find_commit(new)?.tree()?;
+
+    let mut diff = repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+    diff.find_similar(Some(git2::DiffFindOptions::new().renames(true)))?;
+
+    let mut changes = vec![];
+    for delta in diff.deltas() {
+        let old_path = delta.old_file().path().map(|p| p.to_path_buf());
+        let new_path = delta.new_file().path().map(|p| p.to_path_buf());
+
+        let change = match (delta.status(), old_path, new_path) {
+            (git2::Delta::Added, _, Some(p)) | (git2::Delta::Copied, _, Some(p)) => PathChange::Added(p),
+            (git2::Delta::Deleted, Some(p), _) => PathChange::Deleted(p),
+            (git2::Delta::Renamed, Some(from), Some(to)) => PathChange::Renamed(from, to),
+            (_, _, Some(p)) => PathChange::Modified(p),
+            _ => continue,
+        };
+        changes.push(change);
+    }
+
+    return Ok(changes);
+}
+
+/// Updates the entries in the password list that changed between the commits `old_head` and
+/// `new_head`, for example the heads before and after a `pull`, instead of reading the whole
+/// history again. Returns the events that describe the changes.
+pub fn update_password_list(passwords: &PasswordList, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>, old_head: Oid, new_head: Oid) -> Result<Vec<PasswordEvent>> {
+    if old_head == new_head {
+        return Ok(vec![]);
+    }
+    let dir = password_dir(password_store_dir)?;
+
+    // the repository is locked again when the entries are loaded, so the lock is released here
+    let changes = match &*repo_opt {
+        Some(repo) => match repo.try_lock() {
+            Ok(repo) => changed_paths(&repo, old_head, new_head)?,
+            Err(_) => return Err(Error::RepositoryBusy),
+        },
+        None => return Ok(vec![]),
+    };
+
+    let mut events = vec![];
+    for change in changes {
+        match change {
+            PathChange::Added(p) | PathChange::Modified(p) => {
+                let path = dir.join(p);
+                if is_recipients_file(&dir, &path) {
+                    events.push(PasswordEvent::RecipientsChanged(path));
+                } else if is_password_file(&dir, &path) {
+                    events.push(upsert_entry(passwords, PasswordEntry::load_from_git(&dir, &path, repo_opt.clone())?));
+                }
+            },
+            PathChange::Deleted(p) => {
+                let path = dir.join(p);
+                if is_recipients_file(&dir, &path) {
+                    events.push(PasswordEvent::RecipientsChanged(path));
+                } else {
+                    events.extend(remove_entries(passwords, &path));
+                }
+            },
+            PathChange::Renamed(from, to) => {
+                let (from, to) = (dir.join(from), dir.join(to));
+                if is_recipients_file(&dir, &from) || is_recipients_file(&dir, &to) {
+                    events.push(PasswordEvent::RecipientsChanged(to));
+                } else if is_password_file(&dir, &to) {
+                    let renamed = rename_entries(passwords, &dir, &from, &to, repo_opt.clone());
+                    if renamed.is_empty() {
+                        events.push(upsert_entry(passwords, PasswordEntry::load_from_git(&dir, &to, repo_opt.clone())?));
+                    }
+                    events.extend(renamed);
+                } else {
+                    events.extend(remove_entries(passwords, &from));
+                }
+            },
+        }
+    }
+
+    return Ok(events);
+}
+
+/// If the key of a Recipient can be used to encrypt passwords for them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyRingStatus {
+    /// The key is in the keyring and can encrypt.
+    InKeyRing,
+    /// The key is in the keyring, but it can't encrypt, for example because it has expired or
+    /// is revoked.
+    CannotEncrypt,
+    /// No key in the keyring matches the id in the `.gpg-id` file.
+    NotInKeyRing,
 }
 
 /// Represents one person on the team.
 ///
 /// All secrets are encrypted with the key_id of the recipients.
+#[derive(Clone, Debug)]
 pub struct Recipient {
     /// Human readable name of the person.
     pub name: String,
-    /// Machine readable identity, in the form of a gpg key id.
+    /// Machine readable identity, in the form of a gpg key id. For a key that isn't in the
+    /// keyring, this is the id as it's written in the `.gpg-id` file.
     pub key_id: String,
+    /// The full fingerprint of the gpg key, if it's in the keyring.
+    pub fingerprint: Option<String>,
+    /// If the key is in the keyring and can be used for encryption.
+    pub key_ring_status: KeyRingStatus,
 }
 
-fn build_recipient(name: String, key_id: String) -> Recipient {
+fn build_recipient(name: String, key_id: String, fingerprint: Option<String>, key_ring_status: KeyRingStatus) -> Recipient {
     Recipient {
         name,
         key_id,
+        fingerprint,
+        key_ring_status,
+    }
+}
+
+/// A public key that a `.gpg-id` file refers to and that is missing from the local keyring, but
+/// which has an exported copy in the `.public-keys` folder of the store, in the same way as gopass
+/// stores them.
+#[derive(Clone, Debug)]
+pub struct PublicKeyFile {
+    /// The key id as it's written in the `.gpg-id` file.
+    pub key_id: String,
+    /// The file in the `.public-keys` folder containing the armored public key.
+    pub path: path::PathBuf,
+}
+
+impl PublicKeyFile {
+    /// Reads the key for `key_id` from the file, without importing it. Fails if the file doesn't
+    /// contain a key that the id refers to, so that a key file can't sneak in another key.
+    pub fn read_key(&self) -> Result<Recipient> {
+        let (_keyring, keys) = keys_in_file(&self.path)?;
+        let normalized = gpg_id::normalize(&self.key_id);
+
+        let key = keys.iter().find(|k| {
+            let fingerprint_matches = k.fingerprint().map(|f| same_key_id(f, &self.key_id)).unwrap_or(false);
+            let email_matches = k.user_ids().any(|u| u.email().map(|e| e.eq_ignore_ascii_case(&normalized)).unwrap_or(false));
+            fingerprint_matches || email_matches
+        });
+
+        return match key {
+            Some(k) => Ok(recipient_from_key(k)),
+            None => Err(Error::KeyNotFound { key_id: self.key_id.clone() }),
+        };
+    }
+}
+
+/// A keyring in a temporary folder, for reading key files without adding the keys to the keyring
+/// of the user. The folder is removed when it's dropped.
+pub(crate) struct TemporaryKeyring {
+    home: path::PathBuf,
+    pub(crate) ctx: gpgme::Context,
+}
+
+impl TemporaryKeyring {
+    pub(crate) fn new() -> Result<TemporaryKeyring> {
+        let home = std::env::temp_dir().join(format!("ripasso-keyring-{}-{}", std::process::id(), Local::now().timestamp_nanos()));
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder.create(&home)?;
+
+        let keyring_res = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)
+            .and_then(|mut ctx| {
+                ctx.set_engine_home_dir(home.to_string_lossy().as_ref())?;
+                Ok(ctx)
+            });
+        return match keyring_res {
+            Ok(ctx) => Ok(TemporaryKeyring { home, ctx }),
+            Err(e) => {
+                let _ = fs::remove_dir_all(&home);
+                Err(Error::from(e))
+            },
+        };
+    }
+}
+
+impl Drop for TemporaryKeyring {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.home);
+    }
+}
+
+/// Reads the armored public keys in `key_file` into a temporary keyring. The keys are only valid
+/// as long as the keyring is kept.
+pub(crate) fn keys_in_file(key_file: &path::Path) -> Result<(TemporaryKeyring, Vec<gpgme::Key>)> {
+    let key_data = fs::read(key_file)?;
+
+    let mut keyring = TemporaryKeyring::new()?;
+    let result = keyring.ctx.import(key_data)?;
+
+    let mut keys = vec![];
+    for import in result.imports() {
+        let fingerprint = match import.fingerprint() {
+            Ok(f) => f.to_string(),
+            Err(_) => continue,
+        };
+        keys.push(keyring.ctx.get_key(fingerprint)?);
+    }
+
+    if keys.is_empty() {
+        return Err(Error::NoKeysInFile { path: key_file.to_path_buf() });
     }
+
+    return Ok((keyring, keys));
 }
 
 impl Recipient {
@@ -567,269 +1070,1364 @@ impl Recipient {
     pub fn new(key_id: String) -> Result<Recipient> {
         let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
 
-        let key_option = ctx.get_key(key_id.clone());
-        if key_option.is_err() {
-            return Err(Error::Generic("Can't find key in keyring, please import it first"));
-        }
+        let real_key = match resolve_key(&mut ctx, &key_id)? {
+            Some(k) => k,
+            None => return Err(Error::KeyNotFound { key_id }),
+        };
 
-        let real_key = key_option?;
+        return Ok(recipient_from_key(&real_key));
+    }
 
-        let mut name = "?";
-        for user_id in real_key.user_ids() {
-            name = user_id.name().unwrap_or("?");
-        }
+    /// Reads the armored public keys in `key_file` and returns them as Recipients, without
+    /// importing them, so that the user can verify the fingerprints before they are trusted.
+    pub fn from_key_file(key_file: &path::Path) -> Result<Vec<Recipient>> {
+        let (_keyring, keys) = keys_in_file(key_file)?;
+
+        return Ok(keys.iter().map(recipient_from_key).collect());
+    }
 
-        return Ok(build_recipient(name.to_string(), key_id));
+    /// Imports the public key with `fingerprint` from `key_file` into the keyring, once the user
+    /// has verified it. Other keys in the file are not imported.
+    pub fn import_from_key_file(key_file: &path::Path, fingerprint: &str) -> Result<Recipient> {
+        let (mut keyring, keys) = keys_in_file(key_file)?;
+
+        let key = keys.iter()
+            .find(|k| k.fingerprint().map(|f| same_key_id(f, fingerprint)).unwrap_or(false))
+            .ok_or(Error::KeyNotFound { key_id: fingerprint.to_string() })?;
+
+        let mut exported = Vec::new();
+        keyring.ctx.export_keys(vec![key], gpgme::ExportMode::empty(), &mut exported)?;
+
+        let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+        ctx.import(exported)?;
+        let real_key = ctx.get_key(key.fingerprint().unwrap_or(fingerprint).to_string())?;
+
+        return Ok(recipient_from_key(&real_key));
     }
 
     /// Return a list of all the Recipients in the `$PASSWORD_STORE_DIR/.gpg-id` file.
     pub fn all_recipients(password_store_dir: Arc<Option<String>>) -> Result<Vec<Recipient>> {
-
         let mut recipient_file = password_dir(password_store_dir)?;
         recipient_file.push(".gpg-id");
-        let contents = fs::read_to_string(recipient_file)
-            .expect("Something went wrong reading the file");
 
-        let mut recipients : Vec<Recipient> = Vec::new();
-        let mut unique_recipients_keys : HashSet<String> = HashSet::new();
-        for key in contents.split("\n") {
-            if key.len() > 1 {
-                unique_recipients_keys.insert(key.to_string());
-            }
-        }
+        return Recipient::all_recipients_from_file(&recipient_file);
+    }
+
+    /// Return a list of all the Recipients in the supplied `.gpg-id` file, for example one in a
+    /// sub folder of the store. Ids without a key in the keyring are returned with the status
+    /// `KeyRingStatus::NotInKeyRing`, so that they can still be shown, removed or replaced. Fails
+    /// if an id matches several keys.
+    pub fn all_recipients_from_file(recipient_file: &path::Path) -> Result<Vec<Recipient>> {
+        let gpg_id_file = GpgIdFile::parse(&fs::read_to_string(recipient_file)?);
 
         let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
 
-        for key in unique_recipients_keys {
-            let key_option = ctx.get_key(key.clone());
-            if key_option.is_err() {
-                continue;
+        let mut recipients : Vec<Recipient> = Vec::new();
+        let mut unique_fingerprints : HashSet<String> = HashSet::new();
+        for id in gpg_id_file.ids() {
+            let recipient = match resolve_key(&mut ctx, &id)? {
+                Some(real_key) => recipient_from_key(&real_key),
+                None => build_recipient("?".to_string(), id.to_string(), None, KeyRingStatus::NotInKeyRing),
+            };
+
+            if unique_fingerprints.insert(recipient.fingerprint.clone().unwrap_or(recipient.key_id.clone())) {
+                recipients.push(recipient);
             }
+        }
 
-            let real_key = key_option?;
+        return Ok(recipients);
+    }
 
-            let mut name = "?";
-            for user_id in real_key.user_ids() {
-                name = user_id.name().unwrap_or("?");
+    /// Returns if the identifier `id`, from a `.gpg-id` file, refers to the key of this Recipient.
+    fn is_referred_to_by(&self, ctx: &mut gpgme::Context, id: &str) -> bool {
+        if same_key_id(id, &self.key_id) {
+            return true;
+        }
+        if let Some(fingerprint) = &self.fingerprint {
+            if same_key_id(id, fingerprint) {
+                return true;
             }
-            recipients.push(build_recipient(name.to_string(), real_key.id().unwrap_or("?").to_string()));
         }
 
-        return Ok(recipients);
+        return match resolve_key(ctx, id) {
+            Ok(Some(key)) => same_key_id(key.id().unwrap_or(""), &self.key_id),
+            _ => false,
+        };
     }
 
-    fn write_recipients_file(recipients: &Vec<Recipient>, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<()> {
+    fn write_recipients_file(gpg_id_file: &GpgIdFile, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<()> {
         let mut recipient_file = password_dir(password_store_dir.clone())?;
         recipient_file.push(".gpg-id");
 
-        let mut file = std::fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(recipient_file)?;
-
-        for recipient in recipients {
-            if !recipient.key_id.starts_with("0x") {
-                file.write_all(b"0x")?;
-            }
-            file.write_all(recipient.key_id.as_bytes())?;
-            file.write_all(b"\n")?;
-        }
+        write_atomically(&recipient_file, gpg_id_file.to_string().as_bytes())?;
 
         PasswordEntry::reencrypt_all_password_entries(repo_opt, password_store_dir)?;
 
         return Ok(());
     }
 
+    fn read_recipients_file(password_store_dir: Arc<Option<String>>) -> Result<GpgIdFile> {
+        let mut recipient_file = password_dir(password_store_dir)?;
+        recipient_file.push(".gpg-id");
+
+        return Ok(GpgIdFile::parse(&fs::read_to_string(recipient_file)?));
+    }
+
     /// Delete one of the persons from the list of team members to encrypt the passwords for.
     pub fn remove_recipient_from_file(s: &Recipient, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<()> {
-        let mut recipients: Vec<Recipient> = Recipient::all_recipients(password_store_dir.clone())?;
+        let mut gpg_id_file = Recipient::read_recipients_file(password_store_dir.clone())?;
 
-        recipients.retain(|ref vs| vs.key_id != s.key_id);
+        let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+        let removed = gpg_id_file.retain_ids(|id| !s.is_referred_to_by(&mut ctx, id));
 
-        if recipients.len() < 1 {
-            return Err(Error::Generic("Can't delete the last encryption key"));
+        if removed == 0 {
+            return Err(Error::NotARecipient { key_id: s.key_id.clone() });
+        }
+        if gpg_id_file.ids().is_empty() {
+            return Err(Error::LastRecipient { path: password_dir(password_store_dir.clone())?.join(".gpg-id") });
         }
 
-        return Recipient::write_recipients_file(&recipients, repo_opt, password_store_dir);
+        return Recipient::write_recipients_file(&gpg_id_file, repo_opt, password_store_dir);
     }
 
     /// Add a new person to the list of team members to encrypt the passwords for.
     pub fn add_recipient_to_file(s: &Recipient, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<()> {
-        let mut recipients: Vec<Recipient> = Recipient::all_recipients(password_store_dir.clone())?;
+        let mut gpg_id_file = Recipient::read_recipients_file(password_store_dir.clone())?;
 
-        for recipient in &recipients {
-            if recipient.key_id == s.key_id {
-                return Err(Error::Generic("Team member is already in the list of key ids"));
+        let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+        for id in gpg_id_file.ids() {
+            if s.is_referred_to_by(&mut ctx, &id) {
+                return Err(Error::AlreadyARecipient { key_id: s.key_id.clone() });
             }
         }
 
-        recipients.push(build_recipient(s.name.clone(), s.key_id.clone()));
+        match &s.fingerprint {
+            Some(fingerprint) => gpg_id_file.add(fingerprint),
+            None => gpg_id_file.add(&format!("0x{}", s.key_id)),
+        }
 
-        return Recipient::write_recipients_file(&recipients, repo_opt, password_store_dir);
+        return Recipient::write_recipients_file(&gpg_id_file, repo_opt, password_store_dir);
     }
-}
 
-fn read_git_meta_data(base: &path::PathBuf, path: &path::PathBuf, repo_opt: GitRepo) -> (Result<DateTime<Local>>, Result<String>, Result<SignatureStatus>) {
-    if repo_opt.is_none() {
-        return (Err(Error::Generic("need repository to have meta information")),
-                Err(Error::Generic("need repository to have meta information")),
-                Err(Error::Generic("need repository to have meta information")));
-    }
+    /// Rewrites every `.gpg-id` file in the store so that all identifiers are full fingerprints,
+    /// keeping the comments. Identifiers that can't be found in the keyring are left as they are.
+    pub fn normalize_recipient_files(repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<()> {
+        let dir = password_dir(password_store_dir)?;
 
-    let repo_res = (*repo_opt).as_ref().unwrap().try_lock();
-    if repo_res.is_err() {
-        let e = repo_res.err().unwrap();
-        return (Err(Error::GenericDyn(format!("{:?}", e))),
-                Err(Error::GenericDyn(format!("{:?}", e))),
-                Err(Error::GenericDyn(format!("{:?}", e))));
-    }
-    let repo = repo_res.unwrap();
+        let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+        let mut changed_files = vec![];
+        for recipient_file in glob::glob(&dir.join("**/.gpg-id").to_string_lossy())? {
+            let recipient_file = recipient_file?;
+            let mut gpg_id_file = GpgIdFile::parse(&fs::read_to_string(&recipient_file)?);
+
+            let mut resolve_error = None;
+            let replaced = gpg_id_file.replace_ids(|id| {
+                match resolve_key(&mut ctx, id) {
+                    Ok(Some(key)) => match key.fingerprint() {
+                        Ok(f) if f != id => Some(f.to_string()),
+                        _ => None,
+                    },
+                    Ok(None) => None,
+                    Err(e) => {
+                        resolve_error = Some(e);
+                        None
+                    },
+                }
+            });
+            if let Some(e) = resolve_error {
+                return Err(e);
+            }
+            if replaced == 0 {
+                continue;
+            }
+
+            let duplicates = gpg_id_file.duplicates();
+            gpg_id_file.retain_ids({
+                let mut first_seen = HashSet::new();
+                move |id| !duplicates.contains(&id.to_string()) || first_seen.insert(id.to_string())
+            });
+
+            write_atomically(&recipient_file, gpg_id_file.to_string().as_bytes())?;
+            changed_files.push(format!("{}", recipient_file.strip_prefix(&dir)?.display()));
+        }
+
+        if repo_opt.is_none() || changed_files.is_empty() {
+            return Ok(());
+        }
 
-    let path_res = path.strip_prefix(base);
-    if path_res.is_err() {
-        let e = path_res.err().unwrap();
-        return (Err(Error::GenericDyn(format!("{:?}", e))),
-                Err(Error::GenericDyn(format!("{:?}", e))),
-                Err(Error::GenericDyn(format!("{:?}", e))));
+        add_and_commit(repo_opt, &changed_files, "Normalized .gpg-id files to full fingerprints using ripasso")?;
+
+        return Ok(());
     }
+}
 
-    let blame_res = repo.blame_file(path_res.unwrap(), None);
-    if blame_res.is_err() {
-        let e = blame_res.err().unwrap();
-        return (Err(Error::GenericDyn(format!("{:?}", e))),
-                Err(Error::GenericDyn(format!("{:?}", e))),
-                Err(Error::GenericDyn(format!("{:?}", e))));
+/// Creates a Recipient from a key in the keyring.
+fn recipient_from_key(real_key: &gpgme::Key) -> Recipient {
+    let mut name = "?";
+    for user_id in real_key.user_ids() {
+        name = user_id.name().unwrap_or("?");
     }
-    let blame = blame_res.unwrap();
-    let id_res = blame
-        .get_line(1)
-        .ok_or(Error::Generic("no git history found"));
 
-    if id_res.is_err() {
-        let e = id_res.err().unwrap();
-        return (Err(Error::GenericDyn(format!("{:?}", e))),
-                Err(Error::GenericDyn(format!("{:?}", e))),
-                Err(Error::GenericDyn(format!("{:?}", e))));
+    let key_ring_status = match real_key.can_encrypt() {
+        true => KeyRingStatus::InKeyRing,
+        false => KeyRingStatus::CannotEncrypt,
+    };
+
+    return build_recipient(name.to_string(),
+                           real_key.id().unwrap_or("?").to_string(),
+                           real_key.fingerprint().ok().map(|f| f.to_string()),
+                           key_ring_status);
+}
+
+/// Returns the keys to encrypt for, for the recipients in the `.gpg-id` file. Unlike
+/// `Recipient::all_recipients_from_file` this fails if a key isn't in the keyring or can't
+/// encrypt, so that nothing is ever encrypted for fewer people than the file lists.
+fn encryption_keys_from_file(ctx: &mut gpgme::Context, recipient_file: &path::Path) -> Result<Vec<gpgme::Key>> {
+    let mut keys = Vec::new();
+    for recipient in Recipient::all_recipients_from_file(recipient_file)? {
+        match recipient.key_ring_status {
+            KeyRingStatus::InKeyRing => {},
+            KeyRingStatus::CannotEncrypt => return Err(Error::KeyCannotEncrypt { key_id: recipient.key_id }),
+            KeyRingStatus::NotInKeyRing => return Err(Error::KeyNotFound { key_id: recipient.key_id }),
+        }
+        keys.push(ctx.get_key(recipient.fingerprint.unwrap_or(recipient.key_id))?);
     }
-    let id = id_res.unwrap().orig_commit_id();
 
-    let commit_res = repo.find_commit(id);
-    if commit_res.is_err() {
-        let e = commit_res.err().unwrap();
-        return (Err(Error::GenericDyn(format!("{:?}", e))),
-                Err(Error::GenericDyn(format!("{:?}", e))),
-                Err(Error::GenericDyn(format!("{:?}", e))));
+    if keys.is_empty() {
+        return Err(Error::NoUsableKeys { path: recipient_file.to_path_buf() });
     }
-    let commit = commit_res.unwrap();
 
-    let time = commit.time();
-    let time_return = Ok(Local.timestamp(time.seconds(), 0));
+    return Ok(keys);
+}
 
-    let name_return: Result<String> = match commit.committer().name() {
-        Some(s) => Ok(s.to_string()),
-        None => Err(Error::Generic("missing committer name"))
+/// Finds the key in the keyring that an identifier from a `.gpg-id` file refers to, also if
+/// it has expired or is revoked, so that it can be removed or replaced. Returns `None` if no key
+/// matches, and an error if the identifier is ambiguous. When several keys match, the ones that
+/// can't encrypt are ignored, so that an email address still refers to the new key of someone
+/// who has an expired one as well.
+pub(crate) fn resolve_key(ctx: &mut gpgme::Context, id: &str) -> Result<Option<gpgme::Key>> {
+    let normalized = gpg_id::normalize(id);
+
+    let pattern = match gpg_id::kind(id) {
+        IdKind::Fingerprint => {
+            return Ok(ctx.get_key(normalized).ok());
+        },
+        IdKind::KeyId => format!("0x{}", normalized),
+        IdKind::Email => format!("<{}>", normalized),
+        IdKind::Other => normalized,
     };
 
-    let signature_return = verify_git_signature(&repo, &id);
+    let mut keys: Vec<gpgme::Key> = ctx.find_keys(vec![pattern])?
+        .filter_map(|k| k.ok())
+        .collect();
+    if keys.len() > 1 {
+        keys.retain(|k| k.can_encrypt());
+    }
+
+    return match keys.len() {
+        0 => Ok(None),
+        1 => Ok(keys.into_iter().next()),
+        n => Err(Error::AmbiguousKey { key_id: id.to_string(), matches: n }),
+    };
+}
 
-    return (time_return, name_return, signature_return);
+/// Like `resolve_key`, but for encrypting to the key, so it fails if no key matches or if the
+/// key can't encrypt.
+pub(crate) fn resolve_encryption_key(ctx: &mut gpgme::Context, id: &str) -> Result<gpgme::Key> {
+    return match resolve_key(ctx, id)? {
+        Some(key) if key.can_encrypt() => Ok(key),
+        Some(_) => Err(Error::KeyCannotEncrypt { key_id: id.to_string() }),
+        None => Err(Error::KeyNotFound { key_id: id.to_string() }),
+    };
 }
 
-fn verify_git_signature(repo: &std::sync::MutexGuard<Repository>, id: &Oid) -> Result<SignatureStatus> {
-    let (signature, signed_data) = repo.extract_signature(&id, Some("gpgsig"))?;
+/// Progress report from `reencrypt_entries`, one is sent for every entry.
+#[derive(Clone, Debug)]
+pub struct ReencryptionProgress {
+    /// Name of the entry that was just handled
+    pub name: String,
+    /// If the entry already was encrypted for the right recipients and was left as it was
+    pub skipped: bool,
+    /// The number of entries that have been handled so far
+    pub done: usize,
+    /// The total number of entries
+    pub total: usize,
+}
 
-    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+/// Returns the key ids of the public key encrypted session key packets at the start of an
+/// OpenPGP message, that is the (sub)keys the message is encrypted for. Returns `None` if the
+/// message can't be parsed, for example if it's armored, or if a recipient is hidden.
+fn encrypted_for_key_ids(message: &[u8]) -> Option<Vec<String>> {
+    let mut key_ids = vec![];
+    let mut pos = 0;
+
+    while pos < message.len() {
+        let header = message[pos];
+        if header & 0x80 == 0 {
+            return None;
+        }
 
-    let signature_str = str::from_utf8(&signature)?.to_string();
-    let signed_data_str = str::from_utf8(&signed_data)?.to_string();
-    let result = ctx.verify_detached(signature_str, signed_data_str)?;
+        let (tag, body_start, body_len) = if header & 0x40 != 0 {
+            let tag = header & 0x3f;
+            let first = *message.get(pos + 1)? as usize;
+            if first < 192 {
+                (tag, pos + 2, first)
+            } else if first < 224 {
+                let second = *message.get(pos + 2)? as usize;
+                (tag, pos + 3, ((first - 192) << 8) + second + 192)
+            } else if first == 255 {
+                let b = message.get(pos + 2..pos + 6)?;
+                (tag, pos + 6, ((b[0] as usize) << 24) | ((b[1] as usize) << 16) | ((b[2] as usize) << 8) | b[3] as usize)
+            } else {
+                // partial body lengths are only used for data packets
+                (tag, pos, 0)
+            }
+        } else {
+            let tag = (header >> 2) & 0x0f;
+            match header & 0x03 {
+                0 => (tag, pos + 2, *message.get(pos + 1)? as usize),
+                1 => {
+                    let b = message.get(pos + 1..pos + 3)?;
+                    (tag, pos + 3, ((b[0] as usize) << 8) | b[1] as usize)
+                },
+                2 => {
+                    let b = message.get(pos + 1..pos + 5)?;
+                    (tag, pos + 5, ((b[0] as usize) << 24) | ((b[1] as usize) << 16) | ((b[2] as usize) << 8) | b[3] as usize)
+                },
+                _ => (tag, pos, 0),
+            }
+        };
 
-    let mut sig_sum = None;
+        if tag != 1 {
+            break;
+        }
 
-    for (i, sig) in result.signatures().enumerate() {
-        if i == 0 {
-            sig_sum = Some(sig.summary());
-        } else {
-            return Err(Error::Generic("If a git contains more than one signature, something is fishy"));
+        let body = message.get(body_start..body_start + body_len)?;
+        if body.len() < 9 || body[0] != 3 {
+            return None;
+        }
+        let key_id: String = body[1..9].iter().map(|b| format!("{:02X}", b)).collect();
+        if key_id == "0000000000000000" {
+            return None;
         }
+        key_ids.push(key_id);
+
+        pos = body_start + body_len;
     }
 
-    if sig_sum.is_none() {
-        return Err(Error::Generic("Missing signature"));
+    if key_ids.is_empty() {
+        return None;
     }
 
-    let sig_sum = sig_sum.unwrap();
+    return Some(key_ids);
+}
 
-    if sig_sum.contains(gpgme::SignatureSummary::GREEN) {
-        return Ok(SignatureStatus::GoodSignature);
-    } else if sig_sum.contains(gpgme::SignatureSummary::VALID) {
-        return Ok(SignatureStatus::AlmostGoodSignature);
+/// Returns if a message encrypted for `encrypted_for` is encrypted for exactly the recipients,
+/// that is at least one subkey of every recipient and no other keys.
+fn is_encrypted_for(encrypted_for: &Vec<String>, recipients_subkey_ids: &Vec<Vec<String>>) -> bool {
+    let all_subkeys_contains = |id: &String| recipients_subkey_ids.iter().flatten().any(|s| same_key_id(s, id));
+
+    return encrypted_for.iter().all(all_subkeys_contains)
+        && recipients_subkey_ids.iter().all(|subkeys| subkeys.iter().any(|s| encrypted_for.iter().any(|id| same_key_id(s, id))));
+}
+
+/// Returns the path of the temporary file that is used while writing `path`. It's in the same
+/// folder, so that it can be renamed over `path`, and it's hidden and without a `.gpg` extension
+/// so that it's never mistaken for an entry.
+fn temp_path_for(path: &path::Path) -> path::PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    return path.with_file_name(format!(".{}.ripasso-tmp", file_name));
+}
+
+/// Writes `content` to the temporary file of `path` and flushes it to disk.
+fn write_temp_file(path: &path::Path, content: &[u8]) -> Result<path::PathBuf> {
+    let temp_path = temp_path_for(path);
+
+    let res = File::create(&temp_path).and_then(|mut file| {
+        file.write_all(content)?;
+        file.sync_all()
+    });
+    if res.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return Err(Error::from(res.unwrap_err()));
+    }
+
+    return Ok(temp_path);
+}
+
+/// Flushes the folder containing `path` to disk, so that a rename in it survives a crash. Not all
+/// platforms can open folders as files, and there this does nothing.
+fn sync_parent_folder(path: &path::Path) {
+    if let Some(parent) = path.parent() {
+        if let Ok(folder) = File::open(parent) {
+            let _ = folder.sync_all();
+        }
+    }
+}
+
+/// Replaces the content of `path` so that it either has the old or the new content, even if
+/// the program crashes, by writing a temporary file in the same folder and renaming it.
+pub(crate) fn write_atomically(path: &path::Path, content: &[u8]) -> Result<()> {
+    let temp_path = write_temp_file(path, content)?;
+
+    let res = fs::rename(&temp_path, path);
+    if res.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return Err(Error::from(res.unwrap_err()));
+    }
+    sync_parent_folder(path);
+
+    return Ok(());
+}
+
+/// Encrypts `plaintext` for the recipients of an entry at `path`, as decided by its `.gpg-id` file.
+pub(crate) fn encrypt_for_path(dir: &path::Path, path: &path::Path, plaintext: &str) -> Result<Vec<u8>> {
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    ctx.set_armor(false);
+
+    let recipient_file = recipients_file_for(dir, path)?;
+    let keys = encryption_keys_from_file(&mut ctx, &recipient_file)?;
+
+    let mut ciphertext = Vec::new();
+    ctx.encrypt(&keys, plaintext.as_bytes(), &mut ciphertext)?;
+
+    return Ok(ciphertext);
+}
+
+/// Decrypts the entry and encrypts it again for the keys with the supplied fingerprints, into the
+/// temporary file of the entry.
+fn reencrypt_to_temp_file(filename: &path::Path, fingerprints: &Vec<String>) -> Result<path::PathBuf> {
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+
+    let mut keys = Vec::new();
+    for fingerprint in fingerprints {
+        keys.push(ctx.get_key(fingerprint.clone())?);
+    }
+
+    let mut input = File::open(filename)?;
+    let mut plaintext = Vec::new();
+    let res = ctx.decrypt(&mut input, &mut plaintext).and_then(|_| {
+        let mut ciphertext = Vec::new();
+        ctx.encrypt(&keys, &plaintext[..], &mut ciphertext).map(|_| ciphertext)
+    });
+    plaintext.zeroize();
+    let ciphertext = res?;
+
+    return write_temp_file(filename, &ciphertext);
+}
+
+/// Reencrypts the entries for the recipients in their `.gpg-id` files.
+///
+/// Entries that already are encrypted for the right recipients are skipped, and the rest are
+/// decrypted and encrypted by a pool of worker threads. The new content is written to temporary
+/// files, which are renamed over the entries only when all of them succeeded, so a failure leaves
+/// the store as it was.
+///
+/// Returns the names of the entries that were reencrypted.
+pub fn reencrypt_entries(entries: &Vec<PasswordEntry>, password_store_dir: Arc<Option<String>>, progress: &mut dyn FnMut(ReencryptionProgress)) -> Result<Vec<String>> {
+    return Ok(reencrypt_entries_keeping_originals(entries, password_store_dir, progress)?.0);
+}
+
+/// Like `reencrypt_entries`, but also returns the old content of the reencrypted entries, so
+/// that the caller can undo the reencryption with `restore_files`.
+pub(crate) fn reencrypt_entries_keeping_originals(entries: &Vec<PasswordEntry>, password_store_dir: Arc<Option<String>>, progress: &mut dyn FnMut(ReencryptionProgress)) -> Result<(Vec<String>, Vec<(path::PathBuf, Vec<u8>)>)> {
+    let dir = password_dir(password_store_dir)?;
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+
+    let total = entries.len();
+    let mut done = 0;
+
+    // recipient file -> (fingerprints, the subkey ids of each recipient)
+    let mut recipients_cache: HashMap<path::PathBuf, (Vec<String>, Vec<Vec<String>>)> = HashMap::new();
+    let mut jobs: Vec<(String, path::PathBuf, Vec<String>)> = vec![];
+    for entry in entries {
+        let recipient_file = recipients_file_for(&dir, &entry.path)?;
+        if !recipients_cache.contains_key(&recipient_file) {
+            let mut fingerprints: Vec<String> = vec![];
+            let mut subkey_ids: Vec<Vec<String>> = vec![];
+            for key in encryption_keys_from_file(&mut ctx, &recipient_file)? {
+                subkey_ids.push(key.subkeys()
+                    .filter(|k| k.can_encrypt())
+                    .filter_map(|k| k.id().ok().map(|i| i.to_string()))
+                    .collect());
+                fingerprints.push(key.fingerprint().or_else(|_| key.id()).unwrap_or_default().to_string());
+            }
+            recipients_cache.insert(recipient_file.clone(), (fingerprints, subkey_ids));
+        }
+        let (fingerprints, subkey_ids) = &recipients_cache[&recipient_file];
+
+        let up_to_date = match encrypted_for_key_ids(&fs::read(&entry.path)?) {
+            Some(key_ids) => is_encrypted_for(&key_ids, subkey_ids),
+            None => false,
+        };
+        if up_to_date {
+            done += 1;
+            progress(ReencryptionProgress {
+                name: entry.name.clone(),
+                skipped: true,
+                done,
+                total,
+            });
+        } else {
+            jobs.push((entry.name.clone(), entry.path.clone(), fingerprints.clone()));
+        }
+    }
+
+    let workers = std::cmp::min(REENCRYPT_WORKERS, jobs.len());
+    let queue = Arc::new(Mutex::new(jobs));
+    let (result_tx, result_rx) = channel();
+    for _ in 0..workers {
+        let queue = queue.clone();
+        let result_tx = result_tx.clone();
+        thread::spawn(move || {
+            loop {
+                let job = queue.lock().unwrap().pop();
+                let (name, filename, fingerprints) = match job {
+                    Some(j) => j,
+                    None => break,
+                };
+                let res = reencrypt_to_temp_file(&filename, &fingerprints);
+                if result_tx.send((name, filename, res)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(result_tx);
+
+    let mut temp_files = vec![];
+    let mut first_error = None;
+    for (name, filename, res) in result_rx {
+        done += 1;
+        match res {
+            Ok(temp_file) => {
+                temp_files.push((name.clone(), temp_file, filename));
+                progress(ReencryptionProgress {
+                    name,
+                    skipped: false,
+                    done,
+                    total,
+                });
+            },
+            Err(e) => {
+                if first_error.is_none() {
+                    first_error = Some(e);
+                    queue.lock().unwrap().clear();
+                }
+            },
+        }
+    }
+
+    if let Some(e) = first_error {
+        for (_, temp_file, _) in temp_files {
+            let _ = fs::remove_file(temp_file);
+        }
+        return Err(e);
+    }
+
+    let renames: Vec<(path::PathBuf, path::PathBuf)> = temp_files.iter().map(|(_, temp_file, filename)| (temp_file.clone(), filename.clone())).collect();
+    let originals = replace_all(&renames)?;
+    let names = temp_files.into_iter().map(|(name, _, _)| name).collect();
+
+    return Ok((names, originals));
+}
+
+/// Renames the temporary files over their targets, all or nothing. The targets are read first,
+/// so that if a rename fails, the targets that were already replaced get their old content back,
+/// and every temporary file that is left is removed.
+///
+/// Returns the old content of the targets, for callers that have more to do afterwards and need
+/// to undo the change with `restore_files` if that fails.
+pub(crate) fn replace_all(renames: &Vec<(path::PathBuf, path::PathBuf)>) -> Result<Vec<(path::PathBuf, Vec<u8>)>> {
+    let remove_temp_files = |renames: &[(path::PathBuf, path::PathBuf)]| {
+        for (temp_file, _) in renames {
+            let _ = fs::remove_file(temp_file);
+        }
+    };
+
+    let mut originals = vec![];
+    for (_, target) in renames {
+        match fs::read(target) {
+            Ok(content) => originals.push((target.clone(), content)),
+            Err(e) => {
+                remove_temp_files(renames);
+                return Err(Error::from(e));
+            },
+        }
+    }
+
+    for (i, (temp_file, target)) in renames.iter().enumerate() {
+        if let Err(e) = fs::rename(temp_file, target) {
+            let _ = restore_files(&originals[..i]);
+            remove_temp_files(&renames[i..]);
+            return Err(Error::from(e));
+        }
+        sync_parent_folder(target);
+    }
+
+    return Ok(originals);
+}
+
+/// Writes back the old content of files, as returned by `replace_all`. Every file is restored
+/// even if some of them fail, and the first error is returned.
+pub(crate) fn restore_files(originals: &[(path::PathBuf, Vec<u8>)]) -> Result<()> {
+    let mut first_error = None;
+    for (path, content) in originals {
+        if let Err(e) = write_atomically(path, content) {
+            if first_error.is_none() {
+                first_error = Some(e);
+            }
+        }
+    }
+
+    return match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    };
+}
+
+/// Puts files back the way they were before they were written, where `None` means that the file
+/// didn't exist and is removed. Every file is handled even if some of them fail, and the first
+/// error is returned.
+fn restore_or_remove_files(originals: &[(path::PathBuf, Option<Vec<u8>>)]) -> Result<()> {
+    let mut first_error = None;
+    for (path, content) in originals {
+        let res = match content {
+            Some(c) => write_atomically(path, c),
+            None => fs::remove_file(path).map_err(Error::from),
+        };
+        if let Err(e) = res {
+            if first_error.is_none() {
+                first_error = Some(e);
+            }
+        }
+    }
+
+    return match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    };
+}
+
+/// Finds the `.gpg-id` file that decides who the file at `path` is encrypted for. That is the
+/// closest one found when walking from the folder of `path` up to the root of the store.
+fn recipients_file_for(dir: &path::Path, path: &path::Path) -> Result<path::PathBuf> {
+    return match closest_file_for(dir, path, ".gpg-id") {
+        Some(file) => Ok(file),
+        None => Err(Error::RecipientsNotFound { path: path.to_path_buf() }),
+    };
+}
+
+/// Returns the file called `name` in the folder of `path` or the closest of its parent folders
+/// inside the store at `dir`, in the same way as pass finds the `.gpg-id` file.
+pub(crate) fn closest_file_for(dir: &path::Path, path: &path::Path, name: &str) -> Option<path::PathBuf> {
+    let mut current = path.parent();
+    while let Some(folder) = current {
+        if !folder.starts_with(dir) {
+            break;
+        }
+        let candidate = folder.join(name);
+        if candidate.exists() {
+            return Some(candidate);
+        }
+        current = folder.parent();
+    }
+
+    return None;
+}
+
+/// Returns if two gpg key ids refer to the same key, ignoring case, `0x` prefixes, and if one
+/// of them is a long key id and the other a fingerprint.
+pub(crate) fn same_key_id(a: &str, b: &str) -> bool {
+    let a = gpg_id::normalize(a);
+    let b = gpg_id::normalize(b);
+
+    let is_hex = |s: &str| s.len() >= 8 && s.chars().all(|c| c.is_ascii_hexdigit());
+    if !is_hex(&a) || !is_hex(&b) {
+        return !a.is_empty() && a == b;
+    }
+
+    return a.ends_with(&b) || b.ends_with(&a);
+}
+
+/// Returns the public keys that are referenced from any `.gpg-id` file in the store, that are
+/// missing from the local keyring but can be imported from the `.public-keys` folder.
+pub fn missing_public_keys(password_store_dir: Arc<Option<String>>) -> Result<Vec<PublicKeyFile>> {
+    let dir = password_dir(password_store_dir)?;
+    let public_keys_dir = dir.join(".public-keys");
+
+    let mut missing = vec![];
+    if !public_keys_dir.is_dir() {
+        return Ok(missing);
+    }
+
+    let mut key_files = vec![];
+    for file in fs::read_dir(&public_keys_dir)? {
+        key_files.push(file?.path());
+    }
+
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    let mut seen: HashSet<String> = HashSet::new();
+    for recipient_file in glob::glob(&dir.join("**/.gpg-id").to_string_lossy())? {
+        for id in GpgIdFile::parse(&fs::read_to_string(recipient_file?)?).ids() {
+            let key_id = id.as_str();
+            if !seen.insert(gpg_id::normalize(key_id)) {
+                continue;
+            }
+            if resolve_key(&mut ctx, key_id)?.is_some() {
+                continue;
+            }
+
+            let key_file = key_files.iter().find(|f| {
+                let file_name = f.file_name().unwrap_or_default().to_string_lossy();
+                file_name == key_id || same_key_id(&file_name, key_id)
+            });
+            if let Some(f) = key_file {
+                missing.push(PublicKeyFile {
+                    key_id: key_id.to_string(),
+                    path: f.clone(),
+                });
+            }
+        }
+    }
+
+    return Ok(missing);
+}
+
+/// One entry that a removed team member was able to decrypt.
+#[derive(Clone, Debug)]
+pub struct OffboardingItem {
+    /// Name of the entry
+    pub name: String,
+    /// if we have a git repo, then the time of the last change before the team member was removed
+    pub updated: Option<DateTime<Local>>,
+    /// If the entry has been changed since the team member was removed, reencryptions not counted
+    pub rotated: bool,
+}
+
+/// The checklist that is produced when a team member is removed, all the entries that the person
+/// has seen and must be rotated.
+#[derive(Clone, Debug)]
+pub struct Offboarding {
+    /// Machine readable identity of the removed person, in the form of a gpg key id.
+    pub key_id: String,
+    /// Human readable name of the removed person.
+    pub name: String,
+    /// When the person was removed.
+    pub time: DateTime<Local>,
+    /// if we have a git repo, then the commit that reencrypted the entries
+    commit: Option<Oid>,
+    /// The entries the person had access to, ordered by last change
+    pub items: Vec<OffboardingItem>,
+}
+
+impl Offboarding {
+    /// The number of entries that has been rotated since the person was removed.
+    pub fn rotated_count(&self) -> usize {
+        self.items.iter().filter(|i| i.rotated).count()
+    }
+
+    /// Recalculates which entries that have been rotated. With a git repository an entry counts
+    /// as rotated if a commit after the offboarding, that isn't just a reencryption, has touched
+    /// it. Without one the modification time of the file is used.
+    pub fn refresh(&mut self, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<()> {
+        let dir = password_dir(password_store_dir)?;
+
+        let changed = match (&*repo_opt, self.commit) {
+            (Some(repo_mutex), Some(oid)) => {
+                let repo_res = repo_mutex.try_lock();
+                if repo_res.is_err() {
+                    return Err(Error::RepositoryBusy);
+                }
+                Some(changed_since(&repo_res.unwrap(), oid)?)
+            },
+            _ => None,
+        };
+
+        for item in self.items.iter_mut() {
+            let file_name = format!("{}.gpg", item.name);
+            item.rotated = match &changed {
+                Some(c) => c.contains(&file_name),
+                None => match fs::metadata(dir.join(&file_name)).and_then(|m| m.modified()) {
+                    Ok(modified) => DateTime::<Local>::from(modified) > self.time,
+                    Err(_) => false,
+                },
+            };
+        }
+
+        return Ok(());
+    }
+
+    fn to_file_contents(&self) -> String {
+        let mut contents = format!("key_id {}\nname {}\ntime {}\n", self.key_id, self.name, self.time.timestamp());
+        if let Some(oid) = self.commit {
+            contents.push_str(&format!("commit {}\n", oid));
+        }
+        for item in &self.items {
+            let updated = match item.updated {
+                Some(u) => format!("{}", u.timestamp()),
+                None => "-".to_string(),
+            };
+            contents.push_str(&format!("entry {} {}\n", updated, item.name));
+        }
+        return contents;
+    }
+
+    fn from_file_contents(contents: &str) -> Result<Offboarding> {
+        let mut key_id = None;
+        let mut name = "?".to_string();
+        let mut time = None;
+        let mut commit = None;
+        let mut items = vec![];
+
+        for line in contents.lines() {
+            let mut parts = line.splitn(2, ' ');
+            match (parts.next(), parts.next()) {
+                (Some("key_id"), Some(v)) => key_id = Some(v.to_string()),
+                (Some("name"), Some(v)) => name = v.to_string(),
+                (Some("time"), Some(v)) => {
+                    let seconds = v.parse::<i64>().map_err(|_| Error::Generic("malformed time in offboarding checklist"))?;
+                    time = Some(Local.timestamp(seconds, 0));
+                },
+                (Some("commit"), Some(v)) => commit = Some(Oid::from_str(v)?),
+                (Some("entry"), Some(v)) => {
+                    let mut entry_parts = v.splitn(2, ' ');
+                    let updated = entry_parts.next().unwrap_or("-").parse::<i64>().ok().map(|s| Local.timestamp(s, 0));
+                    let entry_name = entry_parts.next().ok_or(Error::Generic("malformed entry in offboarding checklist"))?;
+                    items.push(OffboardingItem {
+                        name: entry_name.to_string(),
+                        updated,
+                        rotated: false,
+                    });
+                },
+                _ => {},
+            }
+        }
+
+        return Ok(Offboarding {
+            key_id: key_id.ok_or(Error::Generic("offboarding checklist is missing a key id"))?,
+            name,
+            time: time.ok_or(Error::Generic("offboarding checklist is missing a time"))?,
+            commit,
+            items,
+        });
+    }
+}
+
+/// Returns the paths of all files that have been changed by commits after `since`, not counting
+/// commits that only reencrypts the store.
+fn changed_since(repo: &git2::Repository, since: Oid) -> Result<HashSet<String>> {
+    let mut changed = HashSet::new();
+
+    let mut walk = repo.revwalk()?;
+    walk.push_head()?;
+    walk.hide(since)?;
+    for rev in walk {
+        let commit = repo.find_commit(rev?)?;
+        if commit.message().unwrap_or("").starts_with(REENCRYPT_COMMIT_PREFIX) {
+            continue;
+        }
+
+        let tree = commit.tree()?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+
+        let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+        for delta in diff.deltas() {
+            if let Some(p) = delta.new_file().path() {
+                changed.insert(format!("{}", p.display()));
+            }
+        }
+    }
+
+    return Ok(changed);
+}
+
+/// Where the offboarding checklists are saved, inside the git directory so that they are never
+/// committed. Without a git repository they are saved in the data folder of the user, in a
+/// folder named after the path of the store.
+fn offboarding_dir(repo_opt: &GitRepo, store_dir: &path::Path) -> Result<path::PathBuf> {
+    return match &**repo_opt {
+        None => {
+            let data_dir = dirs::data_dir().ok_or(Error::Generic("can't find a folder to save the offboarding checklist in"))?;
+            let store_name = format!("{}", std::fs::canonicalize(store_dir)?.display()).replace('/', "%");
+            Ok(data_dir.join("ripasso").join("offboarding").join(store_name))
+        },
+        Some(repo_mutex) => {
+            let repo_res = repo_mutex.try_lock();
+            if repo_res.is_err() {
+                return Err(Error::RepositoryBusy);
+            }
+            Ok(repo_res.unwrap().path().join("ripasso").join("offboarding"))
+        },
+    };
+}
+
+/// Removes a person from the team and reencrypts everything they had access to. If `folders` is
+/// empty the person is removed from every `.gpg-id` file in the store, otherwise only from the
+/// listed sub folders, which get their own `.gpg-id` file if they don't already have one.
+///
+/// Returns a checklist of the entries the person had access to, since those secrets must be
+/// rotated. The checklist is saved outside of the store, see `all_offboardings`.
+pub fn offboard_recipient(recipient: &Recipient, folders: &Vec<String>, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<Offboarding> {
+    let dir = password_dir(password_store_dir.clone())?;
+    let c_dir = std::fs::canonicalize(&dir)?;
+
+    // (file to write, file to read the current recipients from)
+    let mut candidates: Vec<(path::PathBuf, path::PathBuf)> = vec![];
+    if folders.is_empty() {
+        for file in glob::glob(&dir.join("**/.gpg-id").to_string_lossy())? {
+            let file = file?;
+            candidates.push((file.clone(), file));
+        }
     } else {
-        return Ok(SignatureStatus::BadSignature);
+        for folder in folders {
+            let folder_path = dir.join(folder.trim_matches('/'));
+            if !folder_path.is_dir() {
+                return Err(Error::FolderNotFound { path: path::PathBuf::from(folder) });
+            }
+            if !std::fs::canonicalize(&folder_path)?.starts_with(&c_dir) {
+                return Err(Error::PathEscape { path: folder_path });
+            }
+
+            let own_file = folder_path.join(".gpg-id");
+            if !own_file.exists() {
+                let inherited = recipients_file_for(&dir, &own_file)?;
+                candidates.push((own_file, inherited));
+            }
+            for file in glob::glob(&folder_path.join("**/.gpg-id").to_string_lossy())? {
+                let file = file?;
+                candidates.push((file.clone(), file));
+            }
+        }
+    }
+    candidates.sort();
+    candidates.dedup();
+
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    let mut new_contents: Vec<(path::PathBuf, String)> = vec![];
+    for (target, source) in &candidates {
+        let mut gpg_id_file = GpgIdFile::parse(&fs::read_to_string(source)?);
+        if gpg_id_file.retain_ids(|id| !recipient.is_referred_to_by(&mut ctx, id)) == 0 {
+            continue;
+        }
+        if gpg_id_file.ids().is_empty() {
+            return Err(Error::LastRecipient { path: target.clone() });
+        }
+        new_contents.push((target.clone(), gpg_id_file.to_string()));
+    }
+
+    if new_contents.is_empty() {
+        return Err(Error::NotARecipient { key_id: recipient.key_id.clone() });
+    }
+
+    let mut gpg_id_originals = vec![];
+    for (target, contents) in &new_contents {
+        let original = match target.exists() {
+            true => fs::read(target).map(Some),
+            false => Ok(None),
+        };
+        let res = original.map_err(Error::from).and_then(|o| {
+            gpg_id_originals.push((target.clone(), o));
+            write_atomically(target, contents.as_bytes())
+        });
+        if let Err(e) = res {
+            let _ = restore_or_remove_files(&gpg_id_originals);
+            return Err(e);
+        }
+    }
+    let changed_files: Vec<&path::PathBuf> = new_contents.iter().map(|(f, _)| f).collect();
+
+    let affected_res = entries_governed_by(&dir, &changed_files, repo_opt.clone(), password_store_dir.clone())
+        .and_then(|entries| {
+            let (_, entry_originals) = reencrypt_entries_keeping_originals(&entries, password_store_dir.clone(), &mut |_| {})?;
+            Ok((entries, entry_originals))
+        });
+    if affected_res.is_err() {
+        let _ = restore_or_remove_files(&gpg_id_originals);
+        return Err(affected_res.unwrap_err());
+    }
+    let (affected, entry_originals) = affected_res.unwrap();
+
+    let mut names = vec![];
+    let mut items = vec![];
+    for entry in affected {
+        names.push(format!("{}.gpg", &entry.name));
+        items.push(OffboardingItem {
+            name: entry.name.clone(),
+            updated: entry.updated,
+            rotated: false,
+        });
+    }
+    items.sort_by(|a, b| a.updated.cmp(&b.updated));
+
+    let mut commit = None;
+    if repo_opt.is_some() {
+        for file in &changed_files {
+            names.push(format!("{}", file.strip_prefix(&dir)?.display()));
+        }
+        let message = format!("{} without {} ({})", REENCRYPT_COMMIT_PREFIX, recipient.name, recipient.key_id);
+        match add_and_commit(repo_opt.clone(), &names, &message) {
+            Ok(oid) => commit = Some(oid),
+            Err(e) => {
+                let _ = restore_files(&entry_originals);
+                let _ = restore_or_remove_files(&gpg_id_originals);
+                return Err(e);
+            },
+        }
+    }
+
+    let offboarding = Offboarding {
+        key_id: recipient.key_id.clone(),
+        name: recipient.name.clone(),
+        time: Local::now(),
+        commit,
+        items,
+    };
+
+    let checklist_dir = offboarding_dir(&repo_opt, &dir)?;
+    fs::create_dir_all(&checklist_dir)?;
+    let file_name = format!("{}-{}", offboarding.key_id, offboarding.time.timestamp());
+    write_atomically(&checklist_dir.join(file_name), offboarding.to_file_contents().as_bytes())?;
+
+    return Ok(offboarding);
+}
+
+/// Returns all entries whose recipients are decided by one of the `recipient_files`.
+fn entries_governed_by(dir: &path::Path, recipient_files: &Vec<&path::PathBuf>, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<Vec<PasswordEntry>> {
+    let mut affected = vec![];
+    for entry in PasswordEntry::all_password_entries(repo_opt, password_store_dir)? {
+        if recipient_files.contains(&&recipients_file_for(dir, &entry.path)?) {
+            affected.push(entry);
+        }
+    }
+    return Ok(affected);
+}
+
+/// Verifies that the key of `recipient` can actually decrypt, by encrypting a message only for it
+/// and decrypting that. For a key on a smartcard this requires the card to be inserted.
+fn verify_can_decrypt(recipient: &Recipient) -> Result<()> {
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+
+    let key_id = match &recipient.fingerprint {
+        Some(f) => f.clone(),
+        None => recipient.key_id.clone(),
+    };
+    let keys = vec![ctx.get_key(key_id)?];
+
+    let mut ciphertext = Vec::new();
+    ctx.encrypt(&keys, "ripasso key verification", &mut ciphertext)?;
+
+    let mut plaintext = Vec::new();
+    if ctx.decrypt(&ciphertext[..], &mut plaintext).is_err() {
+        return Err(Error::SecretKeyUnavailable { key_id: recipient.key_id.clone() });
+    }
+
+    return Ok(());
+}
+
+/// Verifies that each of the entries is encrypted for `recipient`. The recipients are read from
+/// the headers of the entry, and only if those can't be parsed is the entry decrypted.
+fn verify_encrypted_for(entries: &Vec<PasswordEntry>, recipient: &Recipient) -> Result<()> {
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+
+    let key_id = match &recipient.fingerprint {
+        Some(f) => f.clone(),
+        None => recipient.key_id.clone(),
+    };
+    let subkey_ids: Vec<String> = ctx.get_key(key_id)?
+        .subkeys()
+        .filter_map(|k| k.id().ok().map(|i| i.to_string()))
+        .collect();
+
+    for entry in entries {
+        let ciphertext = fs::read(&entry.filename)?;
+        let encrypted_for = match encrypted_for_key_ids(&ciphertext) {
+            Some(key_ids) => key_ids,
+            None => {
+                let mut output = Vec::new();
+                let result = ctx.decrypt(&ciphertext[..], &mut output);
+                output.zeroize();
+                result?.recipients().filter_map(|r| r.key_id().ok().map(|i| i.to_string())).collect()
+            },
+        };
+
+        let encrypted_for_recipient = encrypted_for.iter()
+            .any(|r| subkey_ids.iter().any(|id| same_key_id(r, id)));
+        if !encrypted_for_recipient {
+            return Err(Error::NotEncryptedFor { name: entry.name.clone(), key_id: recipient.key_id.clone() });
+        }
+    }
+
+    return Ok(());
+}
+
+/// Replaces the key of `old` with the key of `new` in every `.gpg-id` file in the store, and
+/// reencrypts only the entries that are affected. This is used when someone moves to a new key,
+/// for example because the old one expired or was moved to a smartcard.
+///
+/// The new key is verified to be able to decrypt before anything is changed, and every entry is
+/// verified to be encrypted for it before the change is committed. If anything fails, including
+/// the commit, the `.gpg-id` files and the entries are restored.
+///
+/// Returns the names of the reencrypted entries.
+pub fn replace_recipient(old: &Recipient, new: &Recipient, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<Vec<String>> {
+    let dir = password_dir(password_store_dir.clone())?;
+
+    verify_can_decrypt(new)?;
+
+    let new_id = match &new.fingerprint {
+        Some(f) => f.clone(),
+        None => format!("0x{}", new.key_id),
+    };
+
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    // (file, original contents, new contents)
+    let mut new_contents: Vec<(path::PathBuf, String, String)> = vec![];
+    for recipient_file in glob::glob(&dir.join("**/.gpg-id").to_string_lossy())? {
+        let recipient_file = recipient_file?;
+        let original = fs::read_to_string(&recipient_file)?;
+        let mut gpg_id_file = GpgIdFile::parse(&original);
+
+        let already_present = gpg_id_file.ids().iter().any(|id| new.is_referred_to_by(&mut ctx, id));
+        let changed = if already_present {
+            gpg_id_file.retain_ids(|id| !old.is_referred_to_by(&mut ctx, id))
+        } else {
+            gpg_id_file.replace_ids(|id| {
+                if old.is_referred_to_by(&mut ctx, id) {
+                    Some(new_id.clone())
+                } else {
+                    None
+                }
+            })
+        };
+        if changed == 0 {
+            continue;
+        }
+
+        new_contents.push((recipient_file, original, gpg_id_file.to_string()));
+    }
+
+    if new_contents.is_empty() {
+        return Err(Error::NotARecipient { key_id: old.key_id.clone() });
+    }
+
+    let changed_files: Vec<&path::PathBuf> = new_contents.iter().map(|(f, _, _)| f).collect();
+    let affected = entries_governed_by(&dir, &changed_files, repo_opt.clone(), password_store_dir.clone())?;
+
+    let mut gpg_id_originals = vec![];
+    for (file, original, contents) in &new_contents {
+        if let Err(e) = write_atomically(file, contents.as_bytes()) {
+            let _ = restore_files(&gpg_id_originals);
+            return Err(e);
+        }
+        gpg_id_originals.push((file.clone(), original.clone().into_bytes()));
+    }
+
+    let reencrypt_res = reencrypt_entries_keeping_originals(&affected, password_store_dir.clone(), &mut |_| {});
+    if reencrypt_res.is_err() {
+        let _ = restore_files(&gpg_id_originals);
+        return Err(reencrypt_res.unwrap_err());
+    }
+    let (_, entry_originals) = reencrypt_res.unwrap();
+
+    let undo = || {
+        let entries_res = restore_files(&entry_originals);
+        let gpg_ids_res = restore_files(&gpg_id_originals);
+        entries_res.and(gpg_ids_res)
+    };
+
+    if let Err(e) = verify_encrypted_for(&affected, new) {
+        let _ = undo();
+        return Err(e);
     }
 
+    let names: Vec<String> = affected.iter().map(|entry| entry.name.clone()).collect();
+
+    if repo_opt.is_none() {
+        return Ok(names);
+    }
+
+    let mut paths: Vec<String> = names.iter().map(|name| format!("{}.gpg", name)).collect();
+    for file in &changed_files {
+        paths.push(format!("{}", file.strip_prefix(&dir)?.display()));
+    }
+    let message = format!("{} replacing {} with {}", REENCRYPT_COMMIT_PREFIX, old.key_id, new.key_id);
+    if let Err(e) = add_and_commit(repo_opt, &paths, &message) {
+        let _ = undo();
+        return Err(e);
+    }
+
+    return Ok(names);
 }
 
-/// Creates a new password file in the store.
-pub fn new_password_file(path_end: std::rc::Rc<String>, content: std::rc::Rc<String>, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<()> {
-    let mut path = password_dir(password_store_dir.clone())?;
-    let c_path = std::fs::canonicalize(path.as_path())?;
+/// Returns all saved offboarding checklists, oldest first, with up to date rotation status.
+/// Checklists that can't be read are skipped with a warning.
+pub fn all_offboardings(repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<Vec<Offboarding>> {
+    let mut offboardings = vec![];
 
-    let path_deref = (*path_end).clone();
-    let path_iter = &mut path_deref.split("/").peekable();
+    let dir = password_dir(password_store_dir.clone())?;
+    let checklist_dir = offboarding_dir(&repo_opt, &dir)?;
+    if !checklist_dir.exists() {
+        return Ok(offboardings);
+    }
 
-    while let Some(p) = path_iter.next() {
-        if path_iter.peek().is_some() {
-            path.push(p);
-            let c_file = std::fs::canonicalize(path.as_path())?;
-            if !c_file.starts_with(c_path.as_path()) {
-                return Err(Error::Generic("trying to write outside of password store directory"));
-            }
-            if !path.exists() {
-                std::fs::create_dir(&path)?;
-            }
-        } else {
-            path.push(format!("{}.gpg", p));
-        }
+    for file in fs::read_dir(checklist_dir)? {
+        let path = file?.path();
+        let parsed = fs::read_to_string(&path).map_err(Error::from)
+            .and_then(|contents| Offboarding::from_file_contents(&contents));
+        let mut offboarding = match parsed {
+            Ok(o) => o,
+            Err(err) => {
+                warn!("skipping offboarding checklist {}: {}", path.display(), err);
+                continue;
+            },
+        };
+        offboarding.refresh(repo_opt.clone(), password_store_dir.clone())?;
+        offboardings.push(offboarding);
     }
+    offboardings.sort_by(|a, b| a.time.cmp(&b.time));
 
-    if path.exists() {
-        return Err(Error::Generic("file already exist"));
+    return Ok(offboardings);
+}
+
+/// Reads when the file was last changed, by whom and if that commit was signed. The outer error
+/// is for when there is no history for the file, the inner ones are for the committer name and
+/// the signature of the commit.
+fn read_git_meta_data(base: &path::PathBuf, path: &path::PathBuf, repo_opt: GitRepo) -> Result<(DateTime<Local>, Result<String>, Result<SignatureStatus>)> {
+    if repo_opt.is_none() {
+        return Err(Error::Generic("need repository to have meta information"));
     }
 
-    let mut file = match File::create(path) {
-        Err(why) => return Err(Error::from(why)),
-        Ok(file) => file,
+    let repo = (*repo_opt).as_ref().unwrap().try_lock().map_err(|_| Error::RepositoryBusy)?;
+
+    let blame = repo.blame_file(path.strip_prefix(base)?, None)?;
+    let id = blame
+        .get_line(1)
+        .ok_or(Error::Generic("no git history found"))?
+        .orig_commit_id();
+
+    let commit = repo.find_commit(id)?;
+
+    let time = commit.time();
+    let time_return = Local.timestamp(time.seconds(), 0);
+
+    let name_return: Result<String> = match commit.committer().name() {
+        Some(s) => Ok(s.to_string()),
+        None => Err(Error::Generic("missing committer name"))
     };
 
+    let signature_return = verify_git_signature(&repo, &id);
+
+    return Ok((time_return, name_return, signature_return));
+}
+
+fn verify_git_signature(repo: &Repository, id: &Oid) -> Result<SignatureStatus> {
+    let (signature, signed_data) = repo.extract_signature(&id, Some("gpgsig"))?;
 
     let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
-    ctx.set_armor(false);
 
-    let mut keys = Vec::new();
+    let signature_str = str::from_utf8(&signature)?.to_string();
+    let signed_data_str = str::from_utf8(&signed_data)?.to_string();
+    let result = ctx.verify_detached(signature_str, signed_data_str)?;
 
-    for recipient in Recipient::all_recipients(password_store_dir)? {
-        keys.push(ctx.get_key(recipient.key_id)?);
+    let mut sig_sum = None;
+
+    for (i, sig) in result.signatures().enumerate() {
+        if i == 0 {
+            sig_sum = Some(sig.summary());
+        } else {
+            return Err(Error::Generic("If a git contains more than one signature, something is fishy"));
+        }
     }
 
-    let mut output = Vec::new();
-    ctx.encrypt(&keys, (*content).clone(), &mut output)?;
+    if sig_sum.is_none() {
+        return Err(Error::Generic("Missing signature"));
+    }
 
-    match file.write_all(&output) {
-        Err(why) => return Err(Error::from(why)),
-        Ok(_) => (),
+    let sig_sum = sig_sum.unwrap();
+
+    if sig_sum.contains(gpgme::SignatureSummary::GREEN) {
+        return Ok(SignatureStatus::GoodSignature);
+    } else if sig_sum.contains(gpgme::SignatureSummary::VALID) {
+        return Ok(SignatureStatus::AlmostGoodSignature);
+    } else {
+        return Ok(SignatureStatus::BadSignature);
     }
 
+}
+
+/// Creates a new password file in the store. If the folder has a password policy, the password
+/// is checked against it, and the violations are returned as warnings or make the creation fail,
+/// depending on the policy.
+pub fn new_password_file(path_end: std::rc::Rc<String>, content: SecretString, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<Vec<Violation>> {
+    let dir = password_dir(password_store_dir)?;
+
+    let (path, violations) = write_new_password_file(&dir, &path_end, &content)?;
+
     if repo_opt.is_none() {
-        return Ok(());
+        return Ok(violations);
     }
 
     let message = format!("Add password for {} using ripasso", path_end);
 
-    add_and_commit(repo_opt, &vec![format!("{}.gpg", (*path_end).clone())], &message)?;
+    let res = add_and_commit(repo_opt, &vec![format!("{}.gpg", (*path_end).clone())], &message);
+    if res.is_err() {
+        fs::remove_file(&path)?;
+        return Err(res.unwrap_err());
+    }
 
-    return Ok(());
+    return Ok(violations);
+}
+
+/// Encrypts `content` and writes it to a new entry called `path_end` in the store at `dir`,
+/// without committing it. Returns the path of the new file and the policy violations to
+/// warn about.
+pub(crate) fn write_new_password_file(dir: &path::Path, path_end: &str, content: &str) -> Result<(path::PathBuf, Vec<Violation>)> {
+    let mut path = dir.to_path_buf();
+    let c_path = std::fs::canonicalize(path.as_path())?;
+
+    let mut target = dir.to_path_buf();
+    target.push(format!("{}.gpg", path_end));
+    if target.exists() {
+        return Err(Error::EntryExists { path: target });
+    }
+
+    let violations = match Policy::for_path(dir, &target)? {
+        Some(policy) => policy.enforce(content)?,
+        None => vec![],
+    };
+
+    // Encrypt before anything is created, so that a gpg error doesn't leave anything behind
+    let ciphertext = encrypt_for_path(dir, &target, content)?;
+
+    let path_iter = &mut path_end.split("/").peekable();
+
+    while let Some(p) = path_iter.next() {
+        if path_iter.peek().is_some() {
+            path.push(p);
+            // the parent folder is already checked, so a new folder can't end up outside the store
+            if !path.exists() {
+                std::fs::create_dir(&path)?;
+            }
+            let c_file = std::fs::canonicalize(path.as_path())?;
+            if !c_file.starts_with(c_path.as_path()) {
+                return Err(Error::PathEscape { path });
+            }
+        } else {
+            path.push(format!("{}.gpg", p));
+        }
+    }
+
+    if path.exists() {
+        return Err(Error::EntryExists { path });
+    }
+
+    write_atomically(&path, &ciphertext)?;
+
+    return Ok((path, violations));
 }
 
 /// Initialize a git repository for the store.
@@ -846,6 +2444,13 @@ pub enum PasswordEvent {
     NewPassword(PasswordEntry),
     /// A password file was removed.
     RemovedPassword(path::PathBuf),
+    /// The content of a password file was changed.
+    UpdatedPassword(PasswordEntry),
+    /// A password file was moved from the path to where the entry now is.
+    RenamedPassword(path::PathBuf, PasswordEntry),
+    /// A `.gpg-id` file was created, changed or removed, so the entries in its folder might not
+    /// be encrypted for the right recipients anymore.
+    RecipientsChanged(path::PathBuf),
     /// An error occured
     Error(Error),
 }
@@ -881,6 +2486,50 @@ pub fn populate_password_list(passwords: &PasswordList, repo_opt: GitRepo, passw
 
     let dir = password_dir(password_store_dir)?;
 
+    let repo_res = (*repo_opt).as_ref().unwrap().try_lock();
+    if repo_res.is_err() {
+        return Err(Error::RepositoryBusy);
+    }
+    let repo = repo_res.unwrap();
+
+    let mut found = vec![];
+    read_git_metadata(&repo, &dir, &AtomicBool::new(false), &mut |entry| found.push(entry))?;
+
+    // the same order as the history, whether the metadata came from the cache or not
+    found.sort_by(|a, b| b.updated.cmp(&a.updated).then(a.name.cmp(&b.name)));
+
+    let mut list = passwords.lock().unwrap();
+    list.clear();
+    list.extend(found);
+
+    Ok(())
+}
+
+/// Returns the password files in the store at `dir` without reading anything from git, so that
+/// the list can be shown before the metadata is loaded.
+pub fn list_password_files(dir: &path::PathBuf) -> Result<Vec<PasswordEntry>> {
+    let password_path_glob = dir.join("**/*.gpg");
+    let existing_iter = glob::glob(&password_path_glob.to_string_lossy())?;
+
+    let mut entries = vec![];
+    for existing_file in existing_iter {
+        let pbuf = existing_file?;
+        if is_in_git_folder(dir, &pbuf) {
+            continue;
+        }
+        entries.push(PasswordEntry::new(dir, &pbuf,
+                                        Err(Error::Generic("not loaded yet")),
+                                        Err(Error::Generic("not loaded yet")),
+                                        Err(Error::Generic("not loaded yet"))));
+    }
+
+    return Ok(entries);
+}
+
+/// Reads the git metadata of the password files in the store at `dir`, and calls `on_entry`
+/// with every entry as soon as its metadata is known. Entries that are in the metadata cache
+/// come first, the rest are found by walking the history, which ends early if `stop` is set.
+fn read_git_metadata(repo: &Repository, dir: &path::PathBuf, stop: &AtomicBool, on_entry: &mut dyn FnMut(PasswordEntry)) -> Result<()> {
     let password_path_glob = dir.join("**/*.gpg");
     let existing_iter = glob::glob(&password_path_glob.to_string_lossy())?;
 
@@ -891,130 +2540,464 @@ pub fn populate_password_list(passwords: &PasswordList, repo_opt: GitRepo, passw
         files_to_consider.push(filename.trim_start_matches("/").to_string());
     }
 
-    let repo_res = (*repo_opt).as_ref().unwrap().try_lock();
-    if repo_res.is_err() {
-        return Err(Error::GenericDyn(format!("{:?}", repo_res.err())))
-    }
-    let repo = repo_res.unwrap();
-
-    let mut walk = repo.revwalk()?;
-    walk.push(repo.head()?.target().unwrap())?;
-    let mut last_tree = repo.find_commit(repo.head()?.target().unwrap())?.tree()?;
-    for rev in walk {
-        let oid = rev?;
-
-        let commit = repo.find_commit(oid)?;
-        let tree = commit.tree()?;
+    let head = match repo.head()?.target() {
+        Some(oid) => oid,
+        None => return Err(Error::Generic("HEAD doesn't point to a commit")),
+    };
+    let head_tree = repo.find_commit(head)?.tree()?;
+
+    let mut cache = MetadataCache::load(&repo, head);
+    let mut blobs: HashMap<String, Oid> = HashMap::new();
+    let mut without_history = vec![];
+
+    files_to_consider.retain(|filename| {
+        let blob = match head_tree.get_path(path::Path::new(filename)) {
+            Ok(entry) => entry.id(),
+            Err(_) => {
+                // not committed yet
+                without_history.push(filename.clone());
+                return false;
+            }
+        };
+        blobs.insert(filename.clone(), blob);
 
-        let diff = repo.diff_tree_to_tree(Some(&last_tree), Some(&tree), None)?;
+        match cache.get(filename, blob) {
+            Some(meta) => {
+                on_entry(meta.to_entry(&dir, filename));
+                false
+            },
+            None => true,
+        }
+    });
 
-        diff.foreach(&mut |delta: git2::DiffDelta, _f: f32| {
-            let entry_name = format!("{}", delta.new_file().path().unwrap().display());
-            &files_to_consider.retain(|filename| {
-                if *filename == entry_name {
-                    let time = commit.time();
-                    let time_return = Ok(Local.timestamp(time.seconds(), 0));
+    if !files_to_consider.is_empty() {
+        // many files are changed by the same commit, so every signature is only verified once
+        let mut signatures: HashMap<Oid, Option<SignatureStatus>> = HashMap::new();
+
+        let mut walk = repo.revwalk()?;
+        walk.push(head)?;
+        let mut last_commit = repo.find_commit(head)?;
+        let mut last_tree = head_tree.clone();
+        for rev in walk {
+            if stop.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            let oid = rev?;
 
-                    let name_return: Result<String> = match commit.committer().name() {
-                        Some(s) => Ok(s.to_string()),
-                        None => Err(Error::Generic("missing committer name"))
-                    };
+            let commit = repo.find_commit(oid)?;
+            let tree = commit.tree()?;
 
-                    let signature_return = verify_git_signature(&repo, &oid);
+            // the files that differ between a commit and the one before it were changed by the
+            // later commit
+            let diff = repo.diff_tree_to_tree(Some(&last_tree), Some(&tree), None)?;
 
-                    let mut pbuf = dir.clone();
-                    pbuf.push(filename);
+            let mut changed = HashSet::new();
+            for delta in diff.deltas() {
+                if let Some(p) = delta.new_file().path() {
+                    changed.insert(format!("{}", p.display()));
+                }
+            }
 
-                    (passwords.lock().unwrap()).push(PasswordEntry::new(&dir, &pbuf, time_return, name_return, signature_return));
+            let mut newly_found = vec![];
+            files_to_consider.retain(|filename| {
+                if changed.contains(filename) {
+                    newly_found.push(filename.clone());
                     return false;
                 }
                 true
             });
-            true
-        }, None, None, None)?;
+            for filename in newly_found {
+                let meta = commit_metadata(&repo, &last_commit, blobs[&filename], &mut signatures);
+                on_entry(meta.to_entry(&dir, &filename));
+                cache.entries.insert(filename, meta);
+            }
+
+            last_tree = tree;
+            last_commit = commit;
+            if files_to_consider.is_empty() {
+                break;
+            }
+        }
+
+        // the files that are left were added in the first commit
+        for filename in files_to_consider {
+            if last_tree.get_path(path::Path::new(&filename)).is_ok() {
+                let meta = commit_metadata(&repo, &last_commit, blobs[&filename], &mut signatures);
+                on_entry(meta.to_entry(&dir, &filename));
+                cache.entries.insert(filename, meta);
+            } else {
+                without_history.push(filename);
+            }
+        }
+
+        let present: HashSet<&String> = blobs.keys().collect();
+        cache.entries.retain(|p, _| present.contains(p));
+        if let Err(err) = cache.save(&repo) {
+            warn!("couldn't write the metadata cache: {}", err);
+        }
+    }
 
-        last_tree = tree;
+    for filename in without_history {
+        let pbuf = dir.join(&filename);
+        on_entry(PasswordEntry::new(&dir, &pbuf,
+                                    Err(Error::Generic("not committed")),
+                                    Err(Error::Generic("not committed")),
+                                    Err(Error::Generic("not committed"))));
     }
 
     Ok(())
 }
 
-/// Subscribe to events, that happen when password files are added or removed
-pub fn watch(repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<(Receiver<PasswordEvent>, PasswordList)> {
-    let dir = password_dir(password_store_dir.clone())?;
+/// Returns the metadata that the password list shows for a file with the content `blob` that was
+/// last changed in `commit`.
+fn commit_metadata(repo: &Repository, commit: &git2::Commit, blob: Oid, signatures: &mut HashMap<Oid, Option<SignatureStatus>>) -> CachedMetadata {
+    let signature_status = signatures.entry(commit.id())
+        .or_insert_with(|| verify_git_signature(repo, &commit.id()).ok())
+        .clone();
+
+    CachedMetadata {
+        blob,
+        commit: commit.id(),
+        time: commit.time().seconds(),
+        committer: commit.committer().name().map(|n| n.to_string()),
+        signature_status,
+    }
+}
+
+/// How often the watcher thread checks if it should stop, and if a removed store has been
+/// recreated.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A handle to the background threads of `watch`. Dropping the handle leaves the threads running.
+pub struct WatchHandle {
+    stop: Arc<AtomicBool>,
+    thread: thread::JoinHandle<()>,
+    metadata_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl WatchHandle {
+    /// Asks the watcher thread and the thread that loads the git metadata to stop, they do so
+    /// within a fraction of a second, or after the commit they are reading. The password list
+    /// isn't updated after that.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
 
+    /// Stops the background threads and waits for them to finish.
+    pub fn join(self) -> Result<()> {
+        self.stop();
+        let watcher_res = self.thread.join();
+        if let Some(metadata_thread) = self.metadata_thread {
+            metadata_thread.join().map_err(|_| Error::Generic("the metadata thread panicked"))?;
+        }
+        return watcher_res.map_err(|_| Error::Generic("the watcher thread panicked"));
+    }
+}
+
+fn start_watcher(dir: &path::Path) -> Result<(notify::RecommendedWatcher, Receiver<notify::DebouncedEvent>)> {
     let (watcher_tx, watcher_rx) = channel();
 
+    // Automatically select the best implementation for your platform.
+    let mut watcher: notify::RecommendedWatcher = Watcher::new(watcher_tx, Duration::from_secs(1))?;
+
+    // Add a path to be watched. All files and directories at that path and
+    // below will be monitored for changes.
+    watcher.watch(dir, notify::RecursiveMode::Recursive)?;
+
+    return Ok((watcher, watcher_rx));
+}
+
+/// Lists the password files of the store and subscribes to events, that happen when password
+/// files are added, changed, moved or removed, or when `.gpg-id` files change. The list is kept
+/// up to date by a background thread, also after the receiver is dropped, until it's stopped
+/// through the returned handle.
+///
+/// The list is returned before the git metadata of the entries is read. It's filled in by
+/// another background thread, which sends an `UpdatedPassword` event for every entry that is
+/// still in the list and hasn't been updated by the watcher already.
+///
+/// If the store directory is removed, its entries are removed from the list and an error event
+/// is sent. When the directory is created again, it's watched again and the list is filled in.
+pub fn watch(repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<(Receiver<PasswordEvent>, PasswordList, WatchHandle)> {
+    let dir = password_dir(password_store_dir.clone())?;
+
     // Watcher iterator
     let (event_tx, event_rx): (
         Sender<PasswordEvent>,
         Receiver<PasswordEvent>,
     ) = channel();
 
-    let passwords = Arc::new(Mutex::new(Vec::<PasswordEntry>::new()));
+    let passwords = Arc::new(Mutex::new(list_password_files(&dir)?));
     let passwords_out = passwords.clone();
 
-    populate_password_list(&passwords_out, repo_opt, password_store_dir)?;
+    let (watcher, watcher_rx) = start_watcher(&dir)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_thread = stop.clone();
+
+    let mut metadata_thread = None;
+    if repo_opt.is_some() {
+        // the worker opens the repository itself, so that it doesn't keep the shared one locked
+        let (metadata_dir, metadata_passwords, metadata_tx, metadata_stop) = (dir.clone(), passwords.clone(), event_tx.clone(), stop.clone());
+        metadata_thread = Some(thread::spawn(move || {
+            let res = Repository::open(&metadata_dir).map_err(Error::from).and_then(|repo| {
+                read_git_metadata(&repo, &metadata_dir, &metadata_stop, &mut |entry| {
+                    if let Some(event) = fill_in_metadata(&metadata_passwords, entry) {
+                        let _ = metadata_tx.send(event);
+                    }
+                })
+            });
+            if let Err(err) = res {
+                let _ = metadata_tx.send(PasswordEvent::Error(err));
+            }
+        }));
+    }
 
-    thread::spawn(move || {
+    let thread = thread::spawn(move || {
         info!("Starting thread");
 
+        let mut watcher = Some(watcher);
+        let mut watcher_rx = watcher_rx;
+        let mut rewatch_failed = false;
 
-        // Automatically select the best implementation for your platform.
-        let mut watcher: notify::RecommendedWatcher = Watcher::new(watcher_tx, Duration::from_secs(1)).unwrap();
+        // the receiver is allowed to go away, the list is still kept up to date
+        let send = |event: PasswordEvent| {
+            let _ = event_tx.send(event);
+        };
+        let store_removed = |watcher: &mut Option<notify::RecommendedWatcher>| {
+            *watcher = None;
+            let removed = {
+                let mut list = passwords.lock().unwrap();
+                let events = diff_password_lists(&list, &vec![]);
+                list.clear();
+                events
+            };
+            for event in removed {
+                send(event);
+            }
+            send(PasswordEvent::Error(Error::StoreNotFound { path: dir.clone() }));
+        };
 
-        // Add a path to be watched. All files and directories at that path and
-        // below will be monitored for changes.
-        watcher.watch(&dir, notify::RecursiveMode::Recursive).unwrap();
+        while !stop_thread.load(Ordering::SeqCst) {
+            if watcher.is_none() {
+                thread::sleep(WATCH_POLL_INTERVAL);
+                if !dir.exists() {
+                    rewatch_failed = false;
+                    continue;
+                }
 
-        loop {
-            match watcher_rx.recv() {
-                Ok(event) => {
-                    let pass_event = match event {
-                        notify::DebouncedEvent::Create(p) => {
-                            let ext = p.extension();
-                            if ext == None || ext.unwrap() != "gpg" {
-                                continue;
-                            }
-                            let password_store_dir = Arc::new(match std::env::var("PASSWORD_STORE_DIR") {
-                                Ok(p) => Some(p),
-                                Err(_) => None
-                            });
-
-                            let repo_res = git2::Repository::open(password_dir(password_store_dir.clone()).unwrap());
-                            let mut repo_opt: GitRepo = Arc::new(None::<Mutex<git2::Repository>>);
-                            if repo_res.is_ok() {
-                                repo_opt = Arc::new(Some(Mutex::new(repo_res.unwrap())));
-                            }
-                            let p_e = PasswordEntry::load_from_git(&password_dir(password_store_dir).unwrap(), &p.clone(), repo_opt).unwrap();
-                            if !(passwords.lock().unwrap()).iter().any(|p| p.path == p_e.path) {
-                                (passwords.lock().unwrap()).push(p_e.clone());
+                match start_watcher(&dir) {
+                    Ok((w, rx)) => {
+                        watcher = Some(w);
+                        watcher_rx = rx;
+                        rewatch_failed = false;
+
+                        // the store might be a fresh clone, so the repository has to be opened again
+                        if let Some(repo) = &*repo_opt {
+                            if let Ok(fresh) = git2::Repository::open(&dir) {
+                                *repo.lock().unwrap() = fresh;
                             }
-                            PasswordEvent::NewPassword(p_e)
-                        },
-                        notify::DebouncedEvent::Remove(p) => {
-                            let index = (passwords.lock().unwrap()).iter().position(|x| *x.path == p).unwrap();
-                            (passwords.lock().unwrap()).remove(index);
-                            PasswordEvent::RemovedPassword(p)
-                        },
-                        notify::DebouncedEvent::Error(e, _) => {
-                            PasswordEvent::Error(Error::Notify(e))
-                        },
-                        _ => PasswordEvent::Error(Error::Generic("None")),
-                    };
-
-                    if let Err(_err) = event_tx.send(pass_event) {
-                        //error!("Error sending event {}", err)
+                        }
+                        for event in handle_fs_event(notify::DebouncedEvent::Rescan, &dir, &passwords, repo_opt.clone(), password_store_dir.clone()) {
+                            send(event);
+                        }
+                    },
+                    Err(err) => {
+                        if !rewatch_failed {
+                            send(PasswordEvent::Error(err));
+                        }
+                        rewatch_failed = true;
+                    }
+                }
+                continue;
+            }
+
+            match watcher_rx.recv_timeout(WATCH_POLL_INTERVAL) {
+                Ok(notify::DebouncedEvent::Remove(ref p)) if *p == dir => store_removed(&mut watcher),
+                Ok(event) => {
+                    for pass_event in handle_fs_event(event, &dir, &passwords, repo_opt.clone(), password_store_dir.clone()) {
+                        send(pass_event);
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    if !dir.exists() {
+                        store_removed(&mut watcher);
                     }
                 },
-                Err(e) => {
-                    eprintln!("watch error: {:?}", e);
-                    panic!("error")
+                Err(RecvTimeoutError::Disconnected) => {
+                    watcher = None;
+                    send(PasswordEvent::Error(Error::Generic("the file watcher stopped unexpectedly, watching the store again")));
                 },
             }
         }
     });
-    Ok((event_rx, passwords_out))
+
+    Ok((event_rx, passwords_out, WatchHandle { stop, thread, metadata_thread }))
+}
+
+fn is_in_git_folder(dir: &path::Path, path: &path::Path) -> bool {
+    path.strip_prefix(dir).map(|p| p.starts_with(".git")).unwrap_or(false)
+}
+
+/// Returns true if `path` is a password file in the store at `dir`.
+fn is_password_file(dir: &path::Path, path: &path::Path) -> bool {
+    path.extension().map_or(false, |e| e == "gpg") && !is_in_git_folder(dir, path)
+}
+
+/// Returns true if `path` is a `.gpg-id` file in the store at `dir`.
+fn is_recipients_file(dir: &path::Path, path: &path::Path) -> bool {
+    path.file_name().map_or(false, |n| n == ".gpg-id") && !is_in_git_folder(dir, path)
+}
+
+/// Adds the entry to the list, or replaces the entry with the same path.
+fn upsert_entry(passwords: &PasswordList, entry: PasswordEntry) -> PasswordEvent {
+    let mut list = passwords.lock().unwrap();
+    match list.iter().position(|p| p.path == entry.path) {
+        Some(index) => {
+            list[index] = entry.clone();
+            PasswordEvent::UpdatedPassword(entry)
+        },
+        None => {
+            list.push(entry.clone());
+            PasswordEvent::NewPassword(entry)
+        }
+    }
+}
+
+/// Replaces an entry from `list_password_files` with the same entry with its git metadata. Entries
+/// that have been removed, or updated by the watcher in the meantime, are left alone, since the
+/// metadata might be older than what is in the list.
+fn fill_in_metadata(passwords: &PasswordList, entry: PasswordEntry) -> Option<PasswordEvent> {
+    let mut list = passwords.lock().unwrap();
+    let index = list.iter().position(|p| p.path == entry.path)?;
+    if list[index].updated.is_some() || list[index].committed_by.is_some() {
+        return None;
+    }
+
+    list[index] = entry.clone();
+    return Some(PasswordEvent::UpdatedPassword(entry));
+}
+
+/// Removes the entry at `path`, or all entries below it if it's a folder.
+fn remove_entries(passwords: &PasswordList, path: &path::Path) -> Vec<PasswordEvent> {
+    let mut list = passwords.lock().unwrap();
+    let removed: Vec<path::PathBuf> = list.iter()
+        .filter(|p| p.path.starts_with(path))
+        .map(|p| p.path.clone())
+        .collect();
+    list.retain(|p| !p.path.starts_with(path));
+
+    removed.into_iter().map(PasswordEvent::RemovedPassword).collect()
+}
+
+/// Moves the entry at `from`, or all entries below it if it's a folder, to `to`.
+fn rename_entries(passwords: &PasswordList, dir: &path::PathBuf, from: &path::Path, to: &path::Path, repo_opt: GitRepo) -> Vec<PasswordEvent> {
+    let moved: Vec<path::PathBuf> = passwords.lock().unwrap().iter()
+        .filter(|p| p.path.starts_with(from))
+        .map(|p| p.path.clone())
+        .collect();
+
+    let mut events = vec![];
+    for old_path in moved {
+        let new_path = match old_path.strip_prefix(from) {
+            Ok(rest) if rest.as_os_str().is_empty() => to.to_path_buf(),
+            Ok(rest) => to.join(rest),
+            Err(_) => continue,
+        };
+        match PasswordEntry::load_from_git(dir, &new_path, repo_opt.clone()) {
+            Ok(entry) => {
+                let mut list = passwords.lock().unwrap();
+                list.retain(|p| p.path != old_path && p.path != new_path);
+                list.push(entry.clone());
+                events.push(PasswordEvent::RenamedPassword(old_path, entry));
+            },
+            Err(err) => events.push(PasswordEvent::Error(err)),
+        }
+    }
+
+    return events;
+}
+
+/// Returns the events that turn the list `old` into `new`, entries are matched on their path.
+fn diff_password_lists(old: &Vec<PasswordEntry>, new: &Vec<PasswordEntry>) -> Vec<PasswordEvent> {
+    let mut events = vec![];
+
+    for n in new {
+        match old.iter().find(|o| o.path == n.path) {
+            None => events.push(PasswordEvent::NewPassword(n.clone())),
+            Some(o) if o.updated != n.updated || o.committed_by != n.committed_by => events.push(PasswordEvent::UpdatedPassword(n.clone())),
+            Some(_) => {},
+        }
+    }
+    for o in old {
+        if !new.iter().any(|n| n.path == o.path) {
+            events.push(PasswordEvent::RemovedPassword(o.path.clone()));
+        }
+    }
+
+    return events;
+}
+
+/// Applies a file system event in the store at `dir` to the password list, and returns the
+/// events that describe the change.
+fn handle_fs_event(event: notify::DebouncedEvent, dir: &path::PathBuf, passwords: &PasswordList, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Vec<PasswordEvent> {
+    let load = |p: &path::PathBuf| match PasswordEntry::load_from_git(dir, p, repo_opt.clone()) {
+        Ok(entry) => upsert_entry(passwords, entry),
+        Err(err) => PasswordEvent::Error(err),
+    };
+
+    match event {
+        notify::DebouncedEvent::Create(p) | notify::DebouncedEvent::Write(p) => {
+            if is_recipients_file(dir, &p) {
+                vec![PasswordEvent::RecipientsChanged(p)]
+            } else if is_password_file(dir, &p) {
+                vec![load(&p)]
+            } else {
+                vec![]
+            }
+        },
+        notify::DebouncedEvent::Remove(p) => {
+            if is_recipients_file(dir, &p) {
+                vec![PasswordEvent::RecipientsChanged(p)]
+            } else if is_in_git_folder(dir, &p) {
+                vec![]
+            } else {
+                remove_entries(passwords, &p)
+            }
+        },
+        notify::DebouncedEvent::Rename(from, to) => {
+            if is_recipients_file(dir, &from) || is_recipients_file(dir, &to) {
+                return vec![PasswordEvent::RecipientsChanged(if is_recipients_file(dir, &to) { to } else { from })];
+            }
+            if is_in_git_folder(dir, &from) && is_in_git_folder(dir, &to) {
+                return vec![];
+            }
+
+            if to.is_dir() || (is_password_file(dir, &from) && is_password_file(dir, &to)) {
+                rename_entries(passwords, dir, &from, &to, repo_opt.clone())
+            } else if is_password_file(dir, &to) {
+                // a temporary file that replaces the entry, which is how most editors and
+                // ripasso itself save files
+                vec![load(&to)]
+            } else {
+                remove_entries(passwords, &from)
+            }
+        },
+        notify::DebouncedEvent::Rescan => {
+            let fresh = Arc::new(Mutex::new(Vec::<PasswordEntry>::new()));
+            if let Err(err) = populate_password_list(&fresh, repo_opt.clone(), password_store_dir) {
+                return vec![PasswordEvent::Error(err)];
+            }
+            let fresh = fresh.lock().unwrap().clone();
+
+            let mut list = passwords.lock().unwrap();
+            let events = diff_password_lists(&list, &fresh);
+            *list = fresh;
+            events
+        },
+        notify::DebouncedEvent::Error(e, _) => vec![PasswordEvent::Error(Error::Notify(e))],
+        _ => vec![],
+    }
 }
 
 fn to_name(base: &path::PathBuf, path: &path::PathBuf) -> String {
@@ -1030,7 +3013,7 @@ fn to_name(base: &path::PathBuf, path: &path::PathBuf) -> String {
 pub fn password_dir(password_store_dir: Arc<Option<String>>) -> Result<path::PathBuf> {
     let pass_home = password_dir_raw(password_store_dir);
     if !pass_home.exists() {
-        return Err(Error::Generic("failed to locate password directory"));
+        return Err(Error::StoreNotFound { path: pass_home });
     }
     Ok(pass_home.to_path_buf())
 }
@@ -10,12 +10,6 @@ use std::path::PathBuf;
 
 use std::env;
 
-impl std::cmp::PartialEq for Error {
-    fn eq(&self, other: &Error) -> bool {
-        format!("{:?}", self) == format!("{:?}", *other)
-    }
-}
-
 fn unpack_tar_gz(mut base_path: PathBuf, tar_gz_name: &str) -> Result<()> {
     let target = format!("{}", base_path.as_path().display());
     base_path.push(tar_gz_name);
@@ -46,7 +40,50 @@ fn get_password_dir_no_env() {
 
     let path = password_dir(Arc::new(None));
 
-    assert_eq!(path.unwrap_err(), Error::Generic("failed to locate password directory"));
+    match path.unwrap_err() {
+        Error::StoreNotFound { path } => assert_eq!(path, dir.path().join(".password-store")),
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn error_display_kind_and_source() {
+    let err = Error::EntryExists { path: path::PathBuf::from("/tmp/store/web/example.gpg") };
+    assert_eq!(err.to_string(), "/tmp/store/web/example.gpg already exists");
+    assert_eq!(err.kind(), ErrorKind::EntryExists);
+    assert_eq!(std::error::Error::source(&err).is_none(), true);
+
+    let err = Error::AmbiguousKey { key_id: "alice@example.com".to_string(), matches: 2 };
+    assert_eq!(err.to_string(), "the key id alice@example.com is ambiguous, it matches 2 keys");
+    assert_eq!(err.kind(), ErrorKind::AmbiguousKey);
+
+    let err = Error::MergeConflict { paths: vec![path::PathBuf::from("a.gpg"), path::PathBuf::from("b/c.gpg")] };
+    assert_eq!(err.to_string(), "the changes from the remote conflict with local changes in a.gpg, b/c.gpg");
+
+    let err = Error::LastRecipient { path: path::PathBuf::from("/tmp/store/.gpg-id") };
+    assert_eq!(err.to_string(), "can't delete the last encryption key in /tmp/store/.gpg-id");
+    assert_eq!(err.kind(), ErrorKind::LastRecipient);
+
+    let err = Error::FolderNotFound { path: path::PathBuf::from("team/ops") };
+    assert_eq!(err.to_string(), "team/ops is not a folder in the password store");
+    assert_eq!(err.kind(), ErrorKind::FolderNotFound);
+
+    let err = Error::from(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied"));
+    assert_eq!(err.kind(), ErrorKind::Io);
+    assert_eq!(err.to_string(), "denied");
+    assert_eq!(std::error::Error::source(&err).unwrap().to_string(), "denied");
+}
+
+#[test]
+fn read_git_meta_data_keeps_the_git_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = dir.path().to_path_buf();
+    let repo = git2::Repository::init(&base).unwrap();
+    std::fs::write(base.join("new.gpg"), "").unwrap();
+
+    let err = read_git_meta_data(&base, &base.join("new.gpg"), Arc::new(Some(Mutex::new(repo)))).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Git);
+    assert_eq!(std::error::Error::source(&err).is_some(), true);
 }
 
 #[test]
@@ -146,3 +183,376 @@ fn populate_password_list_directory_without_git() {
     assert_eq!((*(*results).lock().unwrap())[2].updated.is_none(), true);
     assert_eq!((*(*results).lock().unwrap())[2].signature_status.is_none(), true);
 }
+
+#[test]
+fn same_key_id_ignores_prefix_and_case() {
+    assert_eq!(same_key_id("0x7E068070D5EF794B", "7e068070d5ef794b"), true);
+    assert_eq!(same_key_id("7E068070D5EF794B", "E6A7D758338EC2EF2A8A9F4EE7E3DB4B3217482F7E068070D5EF794B"), true);
+    assert_eq!(same_key_id("7E068070D5EF794B", "1D108E6C07CBC406"), false);
+    assert_eq!(same_key_id("", "1D108E6C07CBC406"), false);
+}
+
+#[test]
+fn recipients_file_for_finds_closest_gpg_id() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = dir.path().to_path_buf();
+    std::fs::create_dir_all(base.join("team/ops")).unwrap();
+    std::fs::write(base.join(".gpg-id"), "0x7E068070D5EF794B\n").unwrap();
+    std::fs::write(base.join("team/.gpg-id"), "0x1D108E6C07CBC406\n").unwrap();
+
+    assert_eq!(recipients_file_for(&base, &base.join("first.gpg")).unwrap(), base.join(".gpg-id"));
+    assert_eq!(recipients_file_for(&base, &base.join("team/second.gpg")).unwrap(), base.join("team/.gpg-id"));
+    assert_eq!(recipients_file_for(&base, &base.join("team/ops/third.gpg")).unwrap(), base.join("team/.gpg-id"));
+}
+
+#[test]
+fn recipients_without_keys_are_listed_but_not_encrypted_for() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = dir.path().to_path_buf();
+    let missing = "0123456789ABCDEF0123456789ABCDEF01234567";
+    std::fs::write(base.join(".gpg-id"), format!("# left the team\n{}\n", missing)).unwrap();
+
+    let recipients = Recipient::all_recipients_from_file(&base.join(".gpg-id")).unwrap();
+    assert_eq!(recipients.len(), 1);
+    assert_eq!(recipients[0].key_id, missing);
+    assert_eq!(recipients[0].fingerprint, None);
+    assert_eq!(recipients[0].key_ring_status, KeyRingStatus::NotInKeyRing);
+
+    let err = encrypt_for_path(&base, &base.join("entry.gpg"), "secret").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::KeyNotFound);
+}
+
+#[test]
+fn offboarding_checklist_round_trip() {
+    let offboarding = Offboarding {
+        key_id: "7E068070D5EF794B".to_string(),
+        name: "Alexander Kjäll".to_string(),
+        time: Local.timestamp(1578000000, 0),
+        commit: Some(Oid::from_str("a9f0a0a6d0f6a5ad4e6b6e5d8a8e8e7e6f1b2c3d").unwrap()),
+        items: vec![
+            OffboardingItem { name: "old entry".to_string(), updated: None, rotated: true },
+            OffboardingItem { name: "team/new".to_string(), updated: Some(Local.timestamp(1577000000, 0)), rotated: false },
+        ],
+    };
+
+    let parsed = Offboarding::from_file_contents(&offboarding.to_file_contents()).unwrap();
+
+    assert_eq!(parsed.key_id, "7E068070D5EF794B");
+    assert_eq!(parsed.name, "Alexander Kjäll");
+    assert_eq!(parsed.time, Local.timestamp(1578000000, 0));
+    assert_eq!(parsed.commit, offboarding.commit);
+    assert_eq!(parsed.items.len(), 2);
+    assert_eq!(parsed.items[0].name, "old entry");
+    assert_eq!(parsed.items[0].updated.is_none(), true);
+    assert_eq!(parsed.items[0].rotated, false);
+    assert_eq!(parsed.items[1].name, "team/new");
+    assert_eq!(parsed.items[1].updated, Some(Local.timestamp(1577000000, 0)));
+}
+
+#[test]
+fn encrypted_for_key_ids_reads_session_key_packets() {
+    let message = vec![
+        0xc1, 12, 3, 0x7e, 0x06, 0x80, 0x70, 0xd5, 0xef, 0x79, 0x4b, 1, 0xaa, 0xbb,
+        0x84, 12, 3, 0x1d, 0x10, 0x8e, 0x6c, 0x07, 0xcb, 0xc4, 0x06, 1, 0xcc, 0xdd,
+        0xd2, 1, 1];
+
+    assert_eq!(encrypted_for_key_ids(&message), Some(vec!["7E068070D5EF794B".to_string(), "1D108E6C07CBC406".to_string()]));
+    assert_eq!(encrypted_for_key_ids(b"-----BEGIN PGP MESSAGE-----"), None);
+}
+
+#[test]
+fn is_encrypted_for_requires_exactly_the_recipients() {
+    let subkey_ids = vec![
+        vec!["AAAAAAAAAAAAAAAA".to_string(), "7E068070D5EF794B".to_string()],
+        vec!["1D108E6C07CBC406".to_string()]];
+
+    assert_eq!(is_encrypted_for(&vec!["7E068070D5EF794B".to_string(), "1D108E6C07CBC406".to_string()], &subkey_ids), true);
+    assert_eq!(is_encrypted_for(&vec!["7E068070D5EF794B".to_string()], &subkey_ids), false);
+    assert_eq!(is_encrypted_for(&vec!["7E068070D5EF794B".to_string(), "1D108E6C07CBC406".to_string(), "BBBBBBBBBBBBBBBB".to_string()], &subkey_ids), false);
+}
+
+#[test]
+fn temp_path_is_hidden_in_same_folder() {
+    let path = PathBuf::from("/home/user/.password-store/team/db.gpg");
+
+    assert_eq!(temp_path_for(&path), PathBuf::from("/home/user/.password-store/team/.db.gpg.ripasso-tmp"));
+}
+
+#[test]
+fn write_atomically_replaces_content_without_leftovers() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("db.gpg");
+    std::fs::write(&path, b"old").unwrap();
+
+    write_atomically(&path, b"new").unwrap();
+
+    assert_eq!(std::fs::read(&path).unwrap(), b"new".to_vec());
+    assert_eq!(temp_path_for(&path).exists(), false);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn replace_all_rolls_back_when_a_rename_fails() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // the permissions of the folder don't stop root from renaming in it
+    if unsafe { libc::geteuid() } == 0 {
+        return;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let writable = dir.path().join("a.gpg");
+    let read_only_dir = dir.path().join("locked");
+    let read_only = read_only_dir.join("b.gpg");
+    std::fs::create_dir(&read_only_dir).unwrap();
+    std::fs::write(&writable, b"old a").unwrap();
+    std::fs::write(&read_only, b"old b").unwrap();
+    let after = dir.path().join("c.gpg");
+    std::fs::write(&after, b"old c").unwrap();
+    let temp_a = write_temp_file(&writable, b"new a").unwrap();
+    let temp_b = write_temp_file(&read_only, b"new b").unwrap();
+    let temp_c = write_temp_file(&after, b"new c").unwrap();
+    std::fs::set_permissions(&read_only_dir, std::fs::Permissions::from_mode(0o555)).unwrap();
+
+    let res = replace_all(&vec![(temp_a.clone(), writable.clone()), (temp_b.clone(), read_only.clone()), (temp_c.clone(), after.clone())]);
+
+    std::fs::set_permissions(&read_only_dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(res.is_err(), true);
+    assert_eq!(std::fs::read(&writable).unwrap(), b"old a".to_vec());
+    assert_eq!(std::fs::read(&read_only).unwrap(), b"old b".to_vec());
+    assert_eq!(std::fs::read(&after).unwrap(), b"old c".to_vec());
+    assert_eq!(temp_a.exists(), false);
+    assert_eq!(temp_c.exists(), false);
+}
+
+#[test]
+fn restore_or_remove_files_undoes_changes_and_creations() {
+    let dir = tempfile::tempdir().unwrap();
+    let changed = dir.path().join(".gpg-id");
+    let created = dir.path().join("team").join(".gpg-id");
+    std::fs::create_dir(dir.path().join("team")).unwrap();
+    std::fs::write(&changed, b"new").unwrap();
+    std::fs::write(&created, b"new").unwrap();
+
+    restore_or_remove_files(&[(changed.clone(), Some(b"old".to_vec())), (created.clone(), None)]).unwrap();
+
+    assert_eq!(std::fs::read(&changed).unwrap(), b"old".to_vec());
+    assert_eq!(created.exists(), false);
+}
+
+fn entry_without_git(dir: &PathBuf, name: &str) -> PasswordEntry {
+    PasswordEntry::load_from_git(dir, &dir.join(name), Arc::new(None)).unwrap()
+}
+
+#[test]
+fn fill_in_metadata_only_updates_listed_entries_without_metadata() {
+    let dir = PathBuf::from("/tmp/store");
+    let with_metadata = |name: &str| PasswordEntry::new(&dir, &dir.join(name), Ok(Local::now()), Ok("Alice".to_string()), Err(Error::Generic("unsigned")));
+    let passwords: PasswordList = Arc::new(Mutex::new(vec![entry_without_git(&dir, "a.gpg"), with_metadata("b.gpg")]));
+
+    match fill_in_metadata(&passwords, with_metadata("a.gpg")) {
+        Some(PasswordEvent::UpdatedPassword(e)) => assert_eq!(e.name, "a"),
+        other => panic!("unexpected event {:?}", other),
+    }
+    assert_eq!(fill_in_metadata(&passwords, entry_without_git(&dir, "b.gpg")).is_none(), true);
+    assert_eq!(fill_in_metadata(&passwords, with_metadata("removed.gpg")).is_none(), true);
+
+    let list = passwords.lock().unwrap();
+    assert_eq!(list.len(), 2);
+    assert_eq!(list.iter().all(|p| p.committed_by == Some("Alice".to_string())), true);
+}
+
+#[test]
+fn handle_fs_event_keeps_list_up_to_date() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().to_path_buf();
+    let passwords: PasswordList = Arc::new(Mutex::new(vec![entry_without_git(&dir, "a.gpg"), entry_without_git(&dir, "team/b.gpg")]));
+    let handle = |event| handle_fs_event(event, &dir, &passwords, Arc::new(None), Arc::new(Some(format!("{}", dir.display()))));
+
+    match handle(notify::DebouncedEvent::Write(dir.join("a.gpg"))).as_slice() {
+        [PasswordEvent::UpdatedPassword(e)] => assert_eq!(e.name, "a"),
+        other => panic!("unexpected events {:?}", other),
+    }
+    match handle(notify::DebouncedEvent::Rename(dir.join(".c.gpg.ripasso-tmp"), dir.join("c.gpg"))).as_slice() {
+        [PasswordEvent::NewPassword(e)] => assert_eq!(e.name, "c"),
+        other => panic!("unexpected events {:?}", other),
+    }
+    match handle(notify::DebouncedEvent::Rename(dir.join("a.gpg"), dir.join("d.gpg"))).as_slice() {
+        [PasswordEvent::RenamedPassword(from, e)] => {
+            assert_eq!(*from, dir.join("a.gpg"));
+            assert_eq!(e.name, "d");
+        },
+        other => panic!("unexpected events {:?}", other),
+    }
+    match handle(notify::DebouncedEvent::Write(dir.join("team/.gpg-id"))).as_slice() {
+        [PasswordEvent::RecipientsChanged(p)] => assert_eq!(*p, dir.join("team/.gpg-id")),
+        other => panic!("unexpected events {:?}", other),
+    }
+
+    assert!(handle(notify::DebouncedEvent::Remove(dir.join("unknown.gpg"))).is_empty());
+    assert!(handle(notify::DebouncedEvent::Create(dir.join(".git/objects/x.gpg"))).is_empty());
+    assert!(handle(notify::DebouncedEvent::Chmod(dir.join("c.gpg"))).is_empty());
+
+    match handle(notify::DebouncedEvent::Remove(dir.join("team"))).as_slice() {
+        [PasswordEvent::RemovedPassword(p)] => assert_eq!(*p, dir.join("team/b.gpg")),
+        other => panic!("unexpected events {:?}", other),
+    }
+
+    let mut names: Vec<String> = passwords.lock().unwrap().iter().map(|p| p.name.clone()).collect();
+    names.sort();
+    assert_eq!(names, vec!["c".to_string(), "d".to_string()]);
+}
+
+#[test]
+fn diff_password_lists_finds_new_updated_and_removed() {
+    let dir = PathBuf::from("/tmp/store");
+    let mut changed = entry_without_git(&dir, "changed.gpg");
+    let old = vec![entry_without_git(&dir, "same.gpg"), changed.clone(), entry_without_git(&dir, "removed.gpg")];
+    changed.committed_by = Some("Alice".to_string());
+    let new = vec![entry_without_git(&dir, "same.gpg"), changed, entry_without_git(&dir, "added.gpg")];
+
+    let events: Vec<String> = diff_password_lists(&old, &new).iter().map(|e| match e {
+        PasswordEvent::NewPassword(e) => format!("new {}", e.name),
+        PasswordEvent::UpdatedPassword(e) => format!("updated {}", e.name),
+        PasswordEvent::RemovedPassword(p) => format!("removed {}", p.display()),
+        other => format!("{:?}", other),
+    }).collect();
+
+    assert_eq!(events, vec!["updated changed".to_string(), "new added".to_string(), "removed /tmp/store/removed.gpg".to_string()]);
+}
+
+fn wait_for_event(rx: &Receiver<PasswordEvent>, matches: &dyn Fn(&PasswordEvent) -> bool) -> bool {
+    let deadline = std::time::Instant::now() + Duration::from_secs(20);
+    while std::time::Instant::now() < deadline {
+        if let Ok(event) = rx.recv_timeout(Duration::from_millis(100)) {
+            if matches(&event) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+#[test]
+fn watch_follows_recreated_store_and_stops() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("store");
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::write(dir.join("a.gpg"), b"ciphertext").unwrap();
+
+    let (rx, passwords, handle) = watch(Arc::new(None), Arc::new(Some(format!("{}", dir.display())))).unwrap();
+    assert_eq!(passwords.lock().unwrap().len(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(wait_for_event(&rx, &|e| match e {
+        PasswordEvent::RemovedPassword(p) => *p == dir.join("a.gpg"),
+        _ => false,
+    }));
+
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::write(dir.join("b.gpg"), b"ciphertext").unwrap();
+    assert!(wait_for_event(&rx, &|e| match e {
+        PasswordEvent::NewPassword(entry) => entry.name == "b",
+        _ => false,
+    }));
+    assert_eq!(passwords.lock().unwrap().len(), 1);
+
+    handle.join().unwrap();
+}
+
+fn commit_all(repo: &git2::Repository, message: &str) -> git2::Oid {
+    let mut index = repo.index().unwrap();
+    index.add_all(&["*"], git2::IndexAddOption::DEFAULT, None).unwrap();
+    index.update_all(&["*"], None).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("Alice", "alice@example.com").unwrap();
+
+    let parents = match repo.head() {
+        Ok(head) => vec![repo.find_commit(head.target().unwrap()).unwrap()],
+        Err(_) => vec![],
+    };
+    let parents: Vec<&git2::Commit> = parents.iter().collect();
+
+    repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap()
+}
+
+#[test]
+fn update_password_list_applies_only_changed_entries() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().to_path_buf();
+    let repo = git2::Repository::init(&dir).unwrap();
+
+    std::fs::write(dir.join("modified.gpg"), b"one").unwrap();
+    std::fs::write(dir.join("deleted.gpg"), b"two").unwrap();
+    std::fs::write(dir.join("renamed.gpg"), b"a longer content that stays the same when it's moved").unwrap();
+    std::fs::write(dir.join("untouched.gpg"), b"four").unwrap();
+    let old_head = commit_all(&repo, "first");
+
+    std::fs::write(dir.join("modified.gpg"), b"changed").unwrap();
+    std::fs::remove_file(dir.join("deleted.gpg")).unwrap();
+    std::fs::create_dir(dir.join("team")).unwrap();
+    std::fs::rename(dir.join("renamed.gpg"), dir.join("team/moved.gpg")).unwrap();
+    std::fs::write(dir.join("added.gpg"), b"five").unwrap();
+    std::fs::write(dir.join("team/.gpg-id"), b"0x1D108E6C07CBC406\n").unwrap();
+    let new_head = commit_all(&repo, "second");
+
+    let store_dir = Arc::new(Some(format!("{}", dir.display())));
+    let repo_opt = Arc::new(Some(Mutex::new(repo)));
+    let passwords: PasswordList = Arc::new(Mutex::new(vec![
+        entry_without_git(&dir, "modified.gpg"),
+        entry_without_git(&dir, "deleted.gpg"),
+        entry_without_git(&dir, "renamed.gpg"),
+        entry_without_git(&dir, "untouched.gpg"),
+    ]));
+
+    let mut events: Vec<String> = update_password_list(&passwords, repo_opt, store_dir, old_head, new_head).unwrap().iter().map(|e| match e {
+        PasswordEvent::NewPassword(e) => format!("new {}", e.name),
+        PasswordEvent::UpdatedPassword(e) => format!("updated {} by {}", e.name, e.committed_by.clone().unwrap_or_default()),
+        PasswordEvent::RemovedPassword(p) => format!("removed {}", p.strip_prefix(&dir).unwrap().display()),
+        PasswordEvent::RenamedPassword(from, e) => format!("renamed {} to {}", from.strip_prefix(&dir).unwrap().display(), e.name),
+        PasswordEvent::RecipientsChanged(p) => format!("recipients {}", p.strip_prefix(&dir).unwrap().display()),
+        other => format!("{:?}", other),
+    }).collect();
+    events.sort();
+
+    assert_eq!(events, vec![
+        "new added".to_string(),
+        "recipients team/.gpg-id".to_string(),
+        "removed deleted.gpg".to_string(),
+        "renamed renamed.gpg to team/moved".to_string(),
+        "updated modified by Alice".to_string(),
+    ]);
+
+    let mut names: Vec<String> = passwords.lock().unwrap().iter().map(|p| p.name.clone()).collect();
+    names.sort();
+    assert_eq!(names, vec!["added".to_string(), "modified".to_string(), "team/moved".to_string(), "untouched".to_string()]);
+}
+
+#[test]
+fn watch_fills_in_metadata_in_the_background() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().to_path_buf();
+    let repo = git2::Repository::init(&dir).unwrap();
+    std::fs::write(dir.join("a.gpg"), b"one").unwrap();
+    std::fs::write(dir.join("b.gpg"), b"two").unwrap();
+    commit_all(&repo, "first");
+
+    let repo_opt = Arc::new(Some(Mutex::new(repo)));
+    let (rx, passwords, handle) = watch(repo_opt, Arc::new(Some(format!("{}", dir.display())))).unwrap();
+    assert_eq!(passwords.lock().unwrap().len(), 2);
+
+    let mut filled = HashSet::new();
+    while filled.len() < 2 {
+        match rx.recv_timeout(Duration::from_secs(20)).unwrap() {
+            PasswordEvent::UpdatedPassword(e) => {
+                assert_eq!(e.committed_by, Some("Alice".to_string()));
+                filled.insert(e.name);
+            },
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    assert!(passwords.lock().unwrap().iter().all(|e| e.committed_by == Some("Alice".to_string())));
+    handle.join().unwrap();
+}
@@ -0,0 +1,346 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fmt;
+use std::fs;
+use std::path;
+
+use crate::generator::{GeneratorOptions, Mode};
+use crate::pass::{closest_file_for, Error};
+use crate::strength;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The name of the policy file. Like `.gpg-id`, the closest file in the folder of an entry or
+/// one of its parent folders applies.
+pub const POLICY_FILE_NAME: &str = ".ripasso-policy";
+
+/// What to do when a password doesn't follow the policy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Enforcement {
+    /// Save the password, but tell the user about the violations.
+    Warn,
+    /// Don't save the password.
+    Refuse,
+}
+
+/// A requirement from a policy that a password didn't fulfill.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    /// The password is shorter than the minimum length.
+    TooShort {
+        /// The required length.
+        min_length: usize,
+    },
+    /// The password is longer than the maximum length.
+    TooLong {
+        /// The allowed length.
+        max_length: usize,
+    },
+    /// The password doesn't contain an upper case letter.
+    MissingUpper,
+    /// The password doesn't contain a lower case letter.
+    MissingLower,
+    /// The password doesn't contain a digit.
+    MissingDigit,
+    /// The password doesn't contain a symbol.
+    MissingSymbol,
+    /// The password contains a symbol, which the system can't handle.
+    ForbiddenSymbol,
+    /// The password is easier to guess than allowed.
+    TooWeak {
+        /// The required strength score, see `strength::Strength::score`.
+        min_score: u8,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::TooShort { min_length } => write!(f, "the password must be at least {} characters", min_length),
+            Violation::TooLong { max_length } => write!(f, "the password must be at most {} characters", max_length),
+            Violation::MissingUpper => write!(f, "the password must contain an upper case letter"),
+            Violation::MissingLower => write!(f, "the password must contain a lower case letter"),
+            Violation::MissingDigit => write!(f, "the password must contain a digit"),
+            Violation::MissingSymbol => write!(f, "the password must contain a symbol"),
+            Violation::ForbiddenSymbol => write!(f, "the password must not contain symbols"),
+            Violation::TooWeak { min_score } => write!(f, "the password must have a strength score of at least {}", min_score),
+        }
+    }
+}
+
+/// The password policy of a folder, with the settings for generating passwords and the
+/// requirements on passwords that are saved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Policy {
+    /// The settings for the Generate buttons.
+    pub generator: GeneratorOptions,
+    /// The shortest allowed password.
+    pub min_length: Option<usize>,
+    /// The longest allowed password.
+    pub max_length: Option<usize>,
+    /// Passwords must contain an upper case letter.
+    pub require_upper: bool,
+    /// Passwords must contain a lower case letter.
+    pub require_lower: bool,
+    /// Passwords must contain a digit.
+    pub require_digit: bool,
+    /// Passwords must contain a symbol.
+    pub require_symbol: bool,
+    /// Passwords must not contain symbols.
+    pub forbid_symbols: bool,
+    /// The lowest allowed strength score.
+    pub min_score: Option<u8>,
+    /// What to do with passwords that break the policy.
+    pub enforcement: Enforcement,
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy {
+            generator: GeneratorOptions::default(),
+            min_length: None,
+            max_length: None,
+            require_upper: false,
+            require_lower: false,
+            require_digit: false,
+            require_symbol: false,
+            forbid_symbols: false,
+            min_score: None,
+            enforcement: Enforcement::Warn,
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value {
+        "true" | "yes" => Ok(true),
+        "false" | "no" => Ok(false),
+        _ => Err(Error::GenericDyn(format!("expected true or false, got {}", value))),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T> {
+    value.parse::<T>().map_err(|_| Error::GenericDyn(format!("expected a number, got {}", value)))
+}
+
+fn is_symbol(c: char) -> bool {
+    !c.is_alphanumeric() && !c.is_whitespace()
+}
+
+impl Policy {
+    /// Parses a policy file. The file has one `key = value` setting per line, and everything
+    /// after a `#` is a comment. The generator settings are `mode` (characters or
+    /// pronounceable), `length`, `upper`, `lower`, `digits`, `symbols` and `exclude_ambiguous`.
+    /// The requirements are `min_length`, `max_length`, `require_upper`, `require_lower`,
+    /// `require_digit`, `require_symbol`, `forbid_symbols`, `min_score` and `enforcement`
+    /// (warn or refuse).
+    ///
+    /// Requirements adjust the generator settings that the file doesn't set, so that generated
+    /// passwords follow them. Policies that contradict themselves, like one that both requires
+    /// and forbids symbols, `symbols = false` together with `require_symbol = true`, or an
+    /// explicit `length` outside of `min_length` and `max_length`, are errors.
+    pub fn parse(contents: &str) -> Result<Policy> {
+        let mut policy = Policy::default();
+        let mut length_set = false;
+        let mut upper_set = false;
+        let mut lower_set = false;
+        let mut digits_set = false;
+        let mut symbols_set = false;
+
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.splitn(2, '#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap().trim();
+            let value = match parts.next() {
+                Some(v) => v.trim(),
+                None => return Err(Error::GenericDyn(format!("line {} of the policy file is missing a =", i + 1))),
+            };
+
+            let res: Result<()> = (|| {
+                match key {
+                    "mode" => policy.generator.mode = match value {
+                        "characters" => Mode::Characters,
+                        "pronounceable" => Mode::Pronounceable,
+                        _ => return Err(Error::GenericDyn(format!("unknown mode {}", value))),
+                    },
+                    "length" => {
+                        policy.generator.length = parse_number(value)?;
+                        length_set = true;
+                    },
+                    "upper" => {
+                        policy.generator.upper = parse_bool(value)?;
+                        upper_set = true;
+                    },
+                    "lower" => {
+                        policy.generator.lower = parse_bool(value)?;
+                        lower_set = true;
+                    },
+                    "digits" => {
+                        policy.generator.digits = parse_bool(value)?;
+                        digits_set = true;
+                    },
+                    "symbols" => {
+                        policy.generator.symbols = parse_bool(value)?;
+                        symbols_set = true;
+                    },
+                    "exclude_ambiguous" => policy.generator.exclude_ambiguous = parse_bool(value)?,
+                    "min_length" => policy.min_length = Some(parse_number(value)?),
+                    "max_length" => policy.max_length = Some(parse_number(value)?),
+                    "require_upper" => policy.require_upper = parse_bool(value)?,
+                    "require_lower" => policy.require_lower = parse_bool(value)?,
+                    "require_digit" => policy.require_digit = parse_bool(value)?,
+                    "require_symbol" => policy.require_symbol = parse_bool(value)?,
+                    "forbid_symbols" => policy.forbid_symbols = parse_bool(value)?,
+                    "min_score" => policy.min_score = Some(parse_number(value)?),
+                    "enforcement" => policy.enforcement = match value {
+                        "warn" => Enforcement::Warn,
+                        "refuse" => Enforcement::Refuse,
+                        _ => return Err(Error::GenericDyn(format!("unknown enforcement {}", value))),
+                    },
+                    _ => return Err(Error::GenericDyn(format!("unknown setting {}", key))),
+                }
+                Ok(())
+            })();
+            if let Err(err) = res {
+                return Err(Error::GenericDyn(format!("line {} of the policy file: {}", i + 1, err)));
+            }
+        }
+
+        if policy.forbid_symbols && policy.require_symbol {
+            return Err(Error::Generic("the policy both requires and forbids symbols"));
+        }
+        let generator_conflicts = [
+            (upper_set && !policy.generator.upper && policy.require_upper, "the policy requires an upper case letter, but generates passwords without them"),
+            (lower_set && !policy.generator.lower && policy.require_lower, "the policy requires a lower case letter, but generates passwords without them"),
+            (digits_set && !policy.generator.digits && policy.require_digit, "the policy requires a digit, but generates passwords without them"),
+            (symbols_set && !policy.generator.symbols && policy.require_symbol, "the policy requires a symbol, but generates passwords without them"),
+            (symbols_set && policy.generator.symbols && policy.forbid_symbols, "the policy forbids symbols, but generates passwords with them"),
+        ];
+        for &(conflict, message) in generator_conflicts.iter() {
+            if conflict {
+                return Err(Error::Generic(message));
+            }
+        }
+        if let (Some(min), Some(max)) = (policy.min_length, policy.max_length) {
+            if min > max {
+                return Err(Error::GenericDyn(format!("the policy's min_length {} is above its max_length {}", min, max)));
+            }
+        }
+        if length_set {
+            let length = policy.generator.length;
+            if policy.min_length.map_or(false, |min| length < min) || policy.max_length.map_or(false, |max| length > max) {
+                return Err(Error::GenericDyn(format!("the policy generates passwords of length {}, which it doesn't allow", length)));
+            }
+        }
+
+        if policy.forbid_symbols {
+            policy.generator.symbols = false;
+        }
+        if !length_set {
+            if let Some(min) = policy.min_length {
+                policy.generator.length = policy.generator.length.max(min);
+            }
+            if let Some(max) = policy.max_length {
+                policy.generator.length = policy.generator.length.min(max);
+            }
+        }
+        policy.generator.upper |= policy.require_upper;
+        policy.generator.lower |= policy.require_lower;
+        policy.generator.digits |= policy.require_digit;
+        policy.generator.symbols |= policy.require_symbol;
+
+        return Ok(policy);
+    }
+
+    /// Reads a policy file.
+    pub fn from_file(path: &path::Path) -> Result<Policy> {
+        return Policy::parse(&fs::read_to_string(path)?);
+    }
+
+    /// Returns the policy that applies to the password entry file at `path`, in the store at
+    /// `dir`, if there is one.
+    pub fn for_path(dir: &path::Path, path: &path::Path) -> Result<Option<Policy>> {
+        return match closest_file_for(dir, path, POLICY_FILE_NAME) {
+            Some(file) => Ok(Some(Policy::from_file(&file)?)),
+            None => Ok(None),
+        };
+    }
+
+    /// Returns the policy that applies to an entry with the name `name`, like `team/db`, in the
+    /// store at `dir`.
+    pub fn for_name(dir: &path::Path, name: &str) -> Result<Option<Policy>> {
+        return Policy::for_path(dir, &dir.join(format!("{}.gpg", name)));
+    }
+
+    /// Checks the password against the requirements, and returns what it breaks.
+    pub fn check(&self, password: &str) -> Vec<Violation> {
+        let mut violations = vec![];
+        let length = password.chars().count();
+
+        if let Some(min_length) = self.min_length {
+            if length < min_length {
+                violations.push(Violation::TooShort { min_length });
+            }
+        }
+        if let Some(max_length) = self.max_length {
+            if length > max_length {
+                violations.push(Violation::TooLong { max_length });
+            }
+        }
+        if self.require_upper && !password.chars().any(|c| c.is_uppercase()) {
+            violations.push(Violation::MissingUpper);
+        }
+        if self.require_lower && !password.chars().any(|c| c.is_lowercase()) {
+            violations.push(Violation::MissingLower);
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_numeric()) {
+            violations.push(Violation::MissingDigit);
+        }
+        if self.require_symbol && !password.chars().any(is_symbol) {
+            violations.push(Violation::MissingSymbol);
+        }
+        if self.forbid_symbols && password.chars().any(is_symbol) {
+            violations.push(Violation::ForbiddenSymbol);
+        }
+        if let Some(min_score) = self.min_score {
+            if strength::estimate(password).score < min_score {
+                violations.push(Violation::TooWeak { min_score });
+            }
+        }
+
+        return violations;
+    }
+
+    /// Checks the content of a password file, where the password is the first line, and
+    /// returns an error if the policy refuses it, or the violations that should be warned about.
+    pub fn enforce(&self, content: &str) -> Result<Vec<Violation>> {
+        let violations = self.check(content.lines().next().unwrap_or(""));
+
+        if self.enforcement == Enforcement::Refuse && !violations.is_empty() {
+            return Err(Error::PolicyViolation { violations });
+        }
+
+        return Ok(violations);
+    }
+}
+
+#[cfg(test)]
+mod test;
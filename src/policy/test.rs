@@ -0,0 +1,69 @@
+use super::*;
+
+#[test]
+fn parse_policy_and_adjust_generator() {
+    let policy = Policy::parse("# database servers\nmin_length = 32\nforbid_symbols = true # the driver can't escape them\nrequire_digit = true\nenforcement = refuse\n").unwrap();
+
+    assert_eq!(policy.min_length, Some(32));
+    assert_eq!(policy.enforcement, Enforcement::Refuse);
+    assert_eq!(policy.generator.length, 32);
+    assert_eq!(policy.generator.symbols, false);
+    assert_eq!(policy.generator.digits, true);
+}
+
+#[test]
+fn parse_errors_name_the_line() {
+    assert_eq!(Policy::parse("length = 20\nlength 20\n").is_err(), true);
+    assert_eq!(Policy::parse("colour = blue\n").is_err(), true);
+    assert_eq!(Policy::parse("upper = maybe\n").is_err(), true);
+}
+
+#[test]
+fn parse_rejects_contradictions() {
+    assert_eq!(Policy::parse("require_symbol = true\nforbid_symbols = true\n").is_err(), true);
+    assert_eq!(Policy::parse("min_length = 20\nmax_length = 10\n").is_err(), true);
+    assert_eq!(Policy::parse("length = 12\nmin_length = 16\n").is_err(), true);
+    assert_eq!(Policy::parse("length = 40\nmax_length = 32\n").is_err(), true);
+    assert_eq!(Policy::parse("length = 16\nmin_length = 16\nmax_length = 32\n").unwrap().generator.length, 16);
+}
+
+#[test]
+fn parse_rejects_generator_settings_that_break_requirements() {
+    assert_eq!(Policy::parse("upper = false\nrequire_upper = true\n").is_err(), true);
+    assert_eq!(Policy::parse("lower = false\nrequire_lower = true\n").is_err(), true);
+    assert_eq!(Policy::parse("digits = false\nrequire_digit = true\n").is_err(), true);
+    assert_eq!(Policy::parse("symbols = false\nrequire_symbol = true\n").is_err(), true);
+    assert_eq!(Policy::parse("symbols = true\nforbid_symbols = true\n").is_err(), true);
+
+    let policy = Policy::parse("symbols = true\nrequire_symbol = true\ndigits = false\n").unwrap();
+    assert_eq!(policy.generator.symbols, true);
+    assert_eq!(policy.generator.digits, false);
+}
+
+#[test]
+fn check_reports_violations() {
+    let policy = Policy::parse("min_length = 10\nrequire_digit = true\nforbid_symbols = true\n").unwrap();
+
+    assert_eq!(policy.check("short!"), vec![Violation::TooShort { min_length: 10 }, Violation::MissingDigit, Violation::ForbiddenSymbol]);
+    assert_eq!(policy.check("longenough1"), vec![]);
+}
+
+#[test]
+fn enforce_warns_or_refuses_on_first_line() {
+    let warn = Policy::parse("min_length = 10\n").unwrap();
+    let refuse = Policy::parse("min_length = 10\nenforcement = refuse\n").unwrap();
+
+    assert_eq!(warn.enforce("short\nuser: a very long username").unwrap(), vec![Violation::TooShort { min_length: 10 }]);
+    assert_eq!(refuse.enforce("short\nuser: a very long username").is_err(), true);
+    assert_eq!(refuse.enforce("long enough password\n").unwrap(), vec![]);
+}
+
+#[test]
+fn closest_policy_applies() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("servers/db")).unwrap();
+    std::fs::write(dir.path().join("servers").join(POLICY_FILE_NAME), "min_length = 32\n").unwrap();
+
+    assert_eq!(Policy::for_name(dir.path(), "servers/db/postgres").unwrap().unwrap().min_length, Some(32));
+    assert_eq!(Policy::for_name(dir.path(), "email").unwrap(), None);
+}
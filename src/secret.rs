@@ -0,0 +1,237 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fmt;
+use std::io;
+use std::ops::Deref;
+
+use zeroize::Zeroize;
+
+use crate::pass::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Decrypted or generated secret text, like the content of a password entry.
+///
+/// The memory is overwritten with zeroes when the value is dropped, and the buffer is locked in
+/// memory on unix, so that it isn't written to swap. Locking is best effort, it fails silently
+/// when the limit for locked memory is reached. The value can't be modified after it's created,
+/// so the buffer is never reallocated and leaves no copies behind.
+///
+/// The kernel locks and unlocks whole pages, and the locks aren't counted. When a secret is
+/// dropped, the pages of its buffer are unlocked even if another secret shares one of them, so
+/// that secret can be written to swap after that. Its memory is still zeroed when it's dropped.
+///
+/// `Debug` doesn't print the secret, and there is no `Display`, the content has to be reached
+/// through `expose_secret` or `Deref`.
+pub struct SecretString {
+    inner: String,
+}
+
+#[cfg(unix)]
+fn lock_memory(buffer: &str, capacity: usize) {
+    if capacity > 0 {
+        unsafe {
+            libc::mlock(buffer.as_ptr() as *const libc::c_void, capacity);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn unlock_memory(buffer: &str, capacity: usize) {
+    if capacity > 0 {
+        unsafe {
+            libc::munlock(buffer.as_ptr() as *const libc::c_void, capacity);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn lock_memory(_buffer: &str, _capacity: usize) {}
+
+#[cfg(not(unix))]
+fn unlock_memory(_buffer: &str, _capacity: usize) {}
+
+impl SecretString {
+    /// Takes ownership of `secret`, without copying it.
+    pub fn new(secret: String) -> SecretString {
+        lock_memory(&secret, secret.capacity());
+        SecretString { inner: secret }
+    }
+
+    /// Takes ownership of decrypted bytes. If they aren't valid UTF-8, they are wiped and an
+    /// error that doesn't contain them is returned.
+    pub fn from_utf8(bytes: Vec<u8>) -> Result<SecretString> {
+        return match String::from_utf8(bytes) {
+            Ok(secret) => Ok(SecretString::new(secret)),
+            Err(err) => {
+                err.into_bytes().zeroize();
+                Err(Error::Generic("the secret isn't valid UTF-8"))
+            },
+        };
+    }
+
+    /// Returns the secret.
+    pub fn expose_secret(&self) -> &str {
+        &self.inner
+    }
+
+    /// Returns the first line of the secret, which is the password in the pass format.
+    pub fn first_line(&self) -> SecretString {
+        SecretString::new(self.inner.split('\n').next().unwrap_or("").to_string())
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        let capacity = self.inner.capacity();
+        self.inner.zeroize();
+        unlock_memory(&self.inner, capacity);
+    }
+}
+
+impl Deref for SecretString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl AsRef<str> for SecretString {
+    fn as_ref(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl AsRef<[u8]> for SecretString {
+    fn as_ref(&self) -> &[u8] {
+        self.inner.as_bytes()
+    }
+}
+
+impl Clone for SecretString {
+    fn clone(&self) -> SecretString {
+        SecretString::new(self.inner.clone())
+    }
+}
+
+impl PartialEq for SecretString {
+    fn eq(&self, other: &SecretString) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl PartialEq<str> for SecretString {
+    fn eq(&self, other: &str) -> bool {
+        self.inner == other
+    }
+}
+
+impl PartialEq<&str> for SecretString {
+    fn eq(&self, other: &&str) -> bool {
+        self.inner == *other
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretString(***)")
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> SecretString {
+        SecretString::new(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> SecretString {
+        SecretString::new(secret.to_string())
+    }
+}
+
+impl Default for SecretString {
+    fn default() -> SecretString {
+        SecretString::new(String::new())
+    }
+}
+
+/// A growing buffer for text with secrets in it, like an export or the content of an entry.
+/// When the buffer has to grow, the old memory is zeroed before it's freed, and the buffer is
+/// zeroed when it's dropped, so that no copies are left behind.
+pub(crate) struct SecretBuffer {
+    inner: String,
+}
+
+impl SecretBuffer {
+    pub(crate) fn new() -> SecretBuffer {
+        SecretBuffer { inner: String::new() }
+    }
+
+    pub(crate) fn push_str(&mut self, text: &str) {
+        let needed = self.inner.len() + text.len();
+        if needed > self.inner.capacity() {
+            let mut grown = String::with_capacity(std::cmp::max(needed, self.inner.capacity() * 2));
+            lock_memory(&grown, grown.capacity());
+            grown.push_str(&self.inner);
+
+            let capacity = self.inner.capacity();
+            self.inner.zeroize();
+            unlock_memory(&self.inner, capacity);
+            self.inner = grown;
+        }
+        self.inner.push_str(text);
+    }
+
+    /// Turns the buffer into a secret, without copying it.
+    pub(crate) fn into_secret(mut self) -> SecretString {
+        // the memory stays locked, and is unlocked when the secret is dropped
+        return SecretString::new(std::mem::replace(&mut self.inner, String::new()));
+    }
+}
+
+impl Drop for SecretBuffer {
+    fn drop(&mut self) {
+        let capacity = self.inner.capacity();
+        self.inner.zeroize();
+        unlock_memory(&self.inner, capacity);
+    }
+}
+
+impl fmt::Write for SecretBuffer {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.push_str(text);
+        Ok(())
+    }
+}
+
+impl io::Write for SecretBuffer {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let text = std::str::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.push_str(text);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test;
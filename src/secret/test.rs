@@ -0,0 +1,41 @@
+use super::*;
+
+#[test]
+fn debug_doesnt_print_the_secret() {
+    let secret = SecretString::from("hunter2\nlogin: alice");
+
+    assert_eq!(format!("{:?}", secret), "SecretString(***)");
+    assert_eq!(format!("{:?}", Some(secret.clone())).contains("hunter2"), false);
+}
+
+#[test]
+fn first_line_and_deref() {
+    let secret = SecretString::from("hunter2\nlogin: alice\n");
+
+    assert_eq!(secret.first_line().expose_secret(), "hunter2");
+    assert_eq!(secret.lines().count(), 2);
+    assert_eq!(SecretString::default().first_line(), SecretString::default());
+}
+
+#[test]
+fn invalid_utf8_is_an_error_without_the_bytes() {
+    assert_eq!(SecretString::from_utf8(b"hunter2".to_vec()).unwrap(), SecretString::from("hunter2"));
+
+    let err = SecretString::from_utf8(vec![0x68, 0xff, 0x32]).unwrap_err();
+    assert!(matches!(err, Error::Generic("the secret isn't valid UTF-8")));
+}
+
+#[test]
+fn secret_buffer_keeps_everything_when_it_grows() {
+    use std::fmt::Write;
+
+    let mut buffer = SecretBuffer::new();
+    for i in 0..100 {
+        writeln!(buffer, "line {}", i).unwrap();
+    }
+    buffer.push_str("hunter2");
+
+    let secret = buffer.into_secret();
+    assert_eq!(secret.lines().count(), 101);
+    assert_eq!(secret.ends_with("line 99\nhunter2"), true);
+}
@@ -0,0 +1,115 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::io::Write;
+use std::path;
+use std::sync::Arc;
+
+use crate::pass::{keys_in_file, new_password_file, resolve_encryption_key, Error, GitRepo, PasswordEntry, TemporaryKeyring};
+use crate::policy::Violation;
+use crate::secret::SecretString;
+use zeroize::Zeroize;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const ARMOR_HEADER: &str = "-----BEGIN PGP MESSAGE-----";
+
+/// The person that a single entry is shared with, who doesn't have to be in `.gpg-id`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShareRecipient {
+    /// A key in the keyring, with this id, fingerprint or email address.
+    Key(String),
+    /// A file with one armored public key. The key is read into a temporary keyring and is never
+    /// imported into the keyring of the user.
+    KeyFile(path::PathBuf),
+}
+
+/// Reads the only key in `file` into a temporary keyring, and refuses files with several keys
+/// or with a key that can't encrypt.
+fn key_from_file(file: &path::Path) -> Result<(TemporaryKeyring, gpgme::Key)> {
+    let (keyring, mut keys) = keys_in_file(file)?;
+    if keys.len() > 1 {
+        return Err(Error::SeveralKeysInFile { path: file.to_path_buf(), keys: keys.len() });
+    }
+
+    let key = keys.remove(0);
+    if !key.can_encrypt() {
+        return Err(Error::KeyCannotEncrypt { key_id: key.fingerprint().or_else(|_| key.id()).unwrap_or_default().to_string() });
+    }
+
+    return Ok((keyring, key));
+}
+
+fn encrypt_armored(ctx: &mut gpgme::Context, key: &gpgme::Key, secret: &SecretString, output: &mut dyn Write) -> Result<()> {
+    ctx.set_armor(true);
+    let mut ciphertext = Vec::new();
+    ctx.encrypt_with_flags(vec![key], secret.as_bytes(), &mut ciphertext, gpgme::EncryptFlags::ALWAYS_TRUST)?;
+    output.write_all(&ciphertext)?;
+
+    return Ok(());
+}
+
+/// Decrypts the entry and writes its content to `output`, ASCII armored and encrypted only to
+/// the recipient. The key doesn't have to be certified, choosing it is trust enough.
+pub fn share_entry(entry: &PasswordEntry, recipient: &ShareRecipient, output: &mut dyn Write) -> Result<()> {
+    // find the key before decrypting anything, so that a bad key file doesn't waste a round of
+    // pin entry
+    return match recipient {
+        ShareRecipient::Key(id) => {
+            let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+            let key = resolve_encryption_key(&mut ctx, id)?;
+            encrypt_armored(&mut ctx, &key, &entry.secret()?, output)
+        },
+        ShareRecipient::KeyFile(file) => {
+            let (mut keyring, key) = key_from_file(file)?;
+            encrypt_armored(&mut keyring.ctx, &key, &entry.secret()?, output)
+        },
+    };
+}
+
+/// Returns true if `message` looks like an ASCII armored OpenPGP message.
+pub fn is_armored_message(message: &[u8]) -> bool {
+    String::from_utf8_lossy(message).trim_start().starts_with(ARMOR_HEADER)
+}
+
+/// Decrypts a message that was shared with the user, like the output of `share_entry`.
+pub fn decrypt_shared(message: &[u8]) -> Result<SecretString> {
+    if !is_armored_message(message) {
+        return Err(Error::Generic("not an armored OpenPGP message"));
+    }
+
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    let mut plaintext = Vec::new();
+    if let Err(err) = ctx.decrypt(message, &mut plaintext) {
+        plaintext.zeroize();
+        return Err(Error::GPG(err));
+    }
+
+    return SecretString::from_utf8(plaintext);
+}
+
+/// Decrypts a shared message and stores its content as a new entry called `name`, encrypted to
+/// the recipients of its folder and committed to git. Returns the password policy violations to
+/// warn about, like `new_password_file`.
+pub fn import_shared(message: &[u8], name: &str, repo_opt: GitRepo, password_store_dir: Arc<Option<String>>) -> Result<Vec<Violation>> {
+    let content = decrypt_shared(message)?;
+
+    return new_password_file(std::rc::Rc::new(name.to_string()), content, repo_opt, password_store_dir);
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,41 @@
+use super::*;
+
+use crate::pass::ErrorKind;
+use crate::test_helpers::testres;
+
+#[test]
+fn is_armored_message_accepts_leading_whitespace() {
+    assert!(is_armored_message(b"-----BEGIN PGP MESSAGE-----\n\nhQEMA\n-----END PGP MESSAGE-----\n"));
+    assert!(is_armored_message(b"\r\n  -----BEGIN PGP MESSAGE-----\n"));
+}
+
+#[test]
+fn is_armored_message_rejects_other_data() {
+    assert!(!is_armored_message(b"-----BEGIN PGP PUBLIC KEY BLOCK-----\n"));
+    assert!(!is_armored_message(&[0x85, 0x01, 0x0c, 0x03]));
+    assert!(!is_armored_message(b""));
+}
+
+#[test]
+fn decrypt_shared_refuses_binary_messages() {
+    let res = decrypt_shared(&[0x85, 0x01, 0x0c, 0x03]);
+
+    assert!(matches!(res, Err(Error::Generic("not an armored OpenPGP message"))));
+}
+
+#[test]
+fn key_files_with_several_keys_are_refused_without_importing_them() {
+    let fixture_keys = vec!["EBAC3C43F5F3D9C28131F19BA389A463AF33A79B", "CC1B8A355B4C9A3E8D497178753E96881E58CC95"];
+    let dir = tempfile::tempdir().unwrap();
+    let entry = PasswordEntry::load_from_git(&dir.path().to_path_buf(), &dir.path().join("entry.gpg"), Arc::new(None)).unwrap();
+
+    let mut output = Vec::new();
+    let err = share_entry(&entry, &ShareRecipient::KeyFile(testres("two_public_keys.asc")), &mut output).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::SeveralKeysInFile);
+    assert!(output.is_empty());
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp).unwrap();
+    for fingerprint in fixture_keys {
+        assert!(ctx.get_key(fingerprint).is_err());
+    }
+}
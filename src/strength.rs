@@ -0,0 +1,369 @@
+/*  Ripasso - a simple password manager
+    Copyright (C) 2019 Joakim Lundborg, Alexander Kjäll
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::words::WORDS;
+
+/// Commonly used passwords, most common first. A match is guessed in as many guesses as its
+/// position in the list.
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456", "password", "12345678", "qwerty", "123456789", "12345", "1234", "111111",
+    "1234567", "dragon", "123123", "baseball", "abc123", "football", "monkey", "letmein",
+    "696969", "shadow", "master", "666666", "qwertyuiop", "123321", "mustang", "1234567890",
+    "michael", "654321", "superman", "1qaz2wsx", "7777777", "121212", "000000", "qazwsx",
+    "123qwe", "killer", "trustno1", "jordan", "jennifer", "zxcvbnm", "asdfgh", "hunter",
+    "buster", "soccer", "harley", "batman", "andrew", "tigger", "sunshine", "iloveyou",
+    "2000", "charlie", "robert", "thomas", "hockey", "ranger", "daniel",
+    "starwars", "klaster", "112233", "george", "computer", "michelle", "jessica", "pepper",
+    "1111", "zxcvbn", "555555", "11111111", "131313", "freedom", "777777", "pass",
+    "maggie", "159753", "aaaaaa", "ginger", "princess", "joshua", "cheese", "amanda",
+    "summer", "love", "ashley", "nicole", "chelsea", "biteme", "matthew", "access",
+    "yankees", "987654321", "dallas", "austin", "thunder", "taylor", "matrix", "admin",
+    "welcome", "login", "passw0rd", "secret", "changeme", "root", "toor", "default",
+];
+
+const KEYBOARD_ROWS: &[&str] = &["1234567890-=", "qwertyuiop[]", "asdfghjkl;'", "zxcvbnm,./"];
+const SEQUENCES: &[&str] = &["abcdefghijklmnopqrstuvwxyz", "0123456789"];
+
+/// Patterns longer than this are found as several shorter patterns, this keeps the estimation
+/// fast enough to run on every key press.
+const MAX_PATTERN_LENGTH: usize = 32;
+
+/// The kind of pattern a part of a password was recognised as.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    /// A commonly used password, or a word from the bundled diceware list.
+    Dictionary {
+        /// The word, in lower case and without leet substitutions.
+        word: String,
+    },
+    /// Keys next to each other on a keyboard, like `qwerty` or `asdf`.
+    KeyboardWalk,
+    /// A year or a full date, like `1987` or `31-12-1999`.
+    Date,
+    /// The same character or group of characters repeated, like `aaaa` or `abcabc`.
+    Repeat,
+    /// Letters or digits in order, like `abcd` or `9876`.
+    Sequence,
+    /// Characters that didn't match any pattern, that has to be brute forced.
+    BruteForce,
+}
+
+/// A part of a password that was recognised as a pattern.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    /// The pattern that matched.
+    pub pattern: Pattern,
+    /// The matched part of the password.
+    pub token: String,
+    /// The base 2 logarithm of the number of guesses needed to find this part.
+    pub guesses_log2: f64,
+}
+
+/// The estimated strength of a password.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Strength {
+    /// The base 2 logarithm of the number of guesses an attacker that knows about the detected
+    /// patterns needs, this is also the entropy in bits.
+    pub guesses_log2: f64,
+    /// A score from 0 (too guessable) to 4 (very unguessable).
+    pub score: u8,
+    /// The parts that the password was split into when it was estimated.
+    pub matches: Vec<Match>,
+}
+
+/// The speed of an attack, used to estimate crack times.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Attack {
+    /// A rate limited online service, 10 guesses per second.
+    Online,
+    /// An offline attack against a slow hash, 10 000 guesses per second.
+    OfflineSlowHash,
+    /// An offline attack against a fast hash, 10 billion guesses per second.
+    OfflineFastHash,
+}
+
+impl Attack {
+    fn guesses_per_second(self) -> f64 {
+        match self {
+            Attack::Online => 10.0,
+            Attack::OfflineSlowHash => 1e4,
+            Attack::OfflineFastHash => 1e10,
+        }
+    }
+}
+
+impl Strength {
+    /// The number of seconds it would take to find the password on average, with the attack.
+    pub fn crack_time_seconds(&self, attack: Attack) -> f64 {
+        // on average, half of the guesses are needed
+        (self.guesses_log2 - 1.0).exp2() / attack.guesses_per_second()
+    }
+
+    /// The crack time in a form suitable to show to a user, like `3 hours` or `centuries`.
+    pub fn crack_time_display(&self, attack: Attack) -> String {
+        let seconds = self.crack_time_seconds(attack);
+
+        let units = [(60.0 * 60.0 * 24.0 * 365.0, "year"), (60.0 * 60.0 * 24.0 * 30.0, "month"), (60.0 * 60.0 * 24.0, "day"), (60.0 * 60.0, "hour"), (60.0, "minute"), (1.0, "second")];
+        if seconds < 1.0 {
+            return "less than a second".to_string();
+        }
+        if seconds >= 100.0 * units[0].0 {
+            return "centuries".to_string();
+        }
+        for (length, name) in units.iter() {
+            if seconds >= *length {
+                let count = (seconds / length).round() as u64;
+                return format!("{} {}{}", count, name, if count == 1 { "" } else { "s" });
+            }
+        }
+        return "less than a second".to_string();
+    }
+
+    /// A short description of the score.
+    pub fn score_display(&self) -> &'static str {
+        match self.score {
+            0 => "very weak",
+            1 => "weak",
+            2 => "fair",
+            3 => "strong",
+            _ => "very strong",
+        }
+    }
+}
+
+fn unleet(c: char) -> char {
+    match c {
+        '4' | '@' => 'a',
+        '3' => 'e',
+        '1' | '!' => 'i',
+        '0' => 'o',
+        '$' | '5' => 's',
+        '7' => 't',
+        _ => c.to_ascii_lowercase(),
+    }
+}
+
+/// The number of extra guesses needed for the upper case letters and leet substitutions
+/// in a dictionary word.
+fn variations_log2(token: &[char], leet: bool) -> f64 {
+    let upper = token.iter().filter(|c| c.is_uppercase()).count();
+    let substituted = if leet { token.iter().filter(|c| !c.is_alphabetic() && unleet(**c) != **c).count() } else { 0 };
+
+    let mut bits = 0.0;
+    if upper > 0 && !(upper == 1 && token[0].is_uppercase()) && upper != token.len() {
+        bits += token.len() as f64;
+    } else if upper > 0 {
+        bits += 1.0;
+    }
+    bits += substituted as f64;
+    return bits;
+}
+
+fn dictionary_match(token: &[char]) -> Option<Match> {
+    let lower: String = token.iter().map(|c| c.to_lowercase().next().unwrap()).collect();
+    let unleeted: String = token.iter().map(|c| unleet(*c)).collect();
+
+    let mut best: Option<(String, f64)> = None;
+    for (candidate, leet) in &[(lower, false), (unleeted, true)] {
+        let rank = if let Some(i) = COMMON_PASSWORDS.iter().position(|p| *p == candidate.as_str()) {
+            Some((i + 1) as f64)
+        } else if WORDS.binary_search(&candidate.as_str()).is_ok() {
+            Some(WORDS.len() as f64)
+        } else {
+            None
+        };
+        if let Some(rank) = rank {
+            let bits = rank.log2() + variations_log2(token, *leet);
+            if best.as_ref().map_or(true, |(_, b)| bits < *b) {
+                best = Some((candidate.clone(), bits));
+            }
+        }
+    }
+
+    return best.map(|(word, bits)| Match {
+        pattern: Pattern::Dictionary { word },
+        token: token.iter().collect(),
+        guesses_log2: bits.max(1.0),
+    });
+}
+
+fn is_keyboard_walk(token: &[char]) -> bool {
+    let position = |c: char| {
+        let c = c.to_ascii_lowercase();
+        KEYBOARD_ROWS.iter().enumerate()
+            .find_map(|(row, keys)| keys.chars().position(|k| k == c).map(|col| (row as i32, col as i32)))
+    };
+
+    for pair in token.windows(2) {
+        match (position(pair[0]), position(pair[1])) {
+            (Some((r1, c1)), Some((r2, c2))) => {
+                let (dr, dc) = (r2 - r1, c2 - c1);
+                if (dr == 0 && dc == 0) || dr.abs() > 1 || dc.abs() > 1 {
+                    return false;
+                }
+            },
+            _ => return false,
+        }
+    }
+    return true;
+}
+
+fn is_sequence(token: &[char]) -> bool {
+    let lower: String = token.iter().map(|c| c.to_ascii_lowercase()).collect();
+    let reversed: String = lower.chars().rev().collect();
+    SEQUENCES.iter().any(|s| s.contains(&lower) || s.contains(&reversed))
+}
+
+fn is_repeat(token: &[char]) -> Option<usize> {
+    for unit in 1..=token.len() / 2 {
+        if token.len() % unit == 0 && token.chunks(unit).all(|c| c == &token[..unit]) {
+            return Some(unit);
+        }
+    }
+    return None;
+}
+
+fn is_date(token: &[char]) -> bool {
+    let text: String = token.iter().collect();
+    let digits: String = token.iter().filter(|c| c.is_ascii_digit()).collect();
+    let separators = token.iter().filter(|c| !c.is_ascii_digit()).count();
+    if separators > 0 && !(separators == 2 && token.iter().all(|c| c.is_ascii_digit() || "-/._ ".contains(*c))) {
+        return false;
+    }
+
+    let year = |y: &str| y.parse::<u32>().map(|y| (1900..=2099).contains(&y)).unwrap_or(false);
+    let day_month = |dm: &str| {
+        let (a, b) = dm.split_at(2);
+        let (a, b) = (a.parse::<u32>().unwrap_or(0), b.parse::<u32>().unwrap_or(0));
+        (a >= 1 && a <= 31 && b >= 1 && b <= 12) || (b >= 1 && b <= 31 && a >= 1 && a <= 12)
+    };
+
+    return match digits.len() {
+        4 if separators == 0 => year(&digits),
+        8 => (year(&digits[..4]) && day_month(&digits[4..])) || (year(&digits[4..]) && day_month(&digits[..4])),
+        6 if separators == 2 => {
+            let parts: Vec<&str> = text.split(|c: char| !c.is_ascii_digit()).collect();
+            parts.iter().all(|p| p.len() == 2)
+        },
+        _ => false,
+    };
+}
+
+fn cardinality(c: char) -> f64 {
+    if c.is_ascii_lowercase() || c.is_ascii_uppercase() {
+        26.0
+    } else if c.is_ascii_digit() {
+        10.0
+    } else if c.is_ascii() {
+        33.0
+    } else {
+        100.0
+    }
+}
+
+/// Finds the best pattern for the part of the password from `i` to `j`, if there is one.
+fn best_pattern(password: &[char], i: usize, j: usize) -> Option<Match> {
+    let token = &password[i..j];
+    let text: String = token.iter().collect();
+    let mut candidates = vec![];
+
+    if token.len() >= 3 {
+        if let Some(m) = dictionary_match(token) {
+            candidates.push(m);
+        }
+    }
+    if token.len() >= 4 && is_keyboard_walk(token) {
+        candidates.push(Match { pattern: Pattern::KeyboardWalk, token: text.clone(), guesses_log2: (47.0 * 8.0 * token.len() as f64).log2() });
+    }
+    if token.len() >= 3 && is_sequence(token) {
+        candidates.push(Match { pattern: Pattern::Sequence, token: text.clone(), guesses_log2: (36.0 * 2.0 * token.len() as f64).log2() });
+    }
+    if token.len() >= 3 {
+        if let Some(unit) = is_repeat(token) {
+            let base = estimate(&text[..token[..unit].iter().map(|c| c.len_utf8()).sum()]).guesses_log2;
+            let repeats = (token.len() / unit) as f64;
+            candidates.push(Match { pattern: Pattern::Repeat, token: text.clone(), guesses_log2: base + repeats.log2() });
+        }
+    }
+    if token.len() >= 4 && is_date(token) {
+        let bits = if token.len() == 4 { 200f64.log2() } else { (200.0 * 366.0 * 2.0f64).log2() };
+        candidates.push(Match { pattern: Pattern::Date, token: text.clone(), guesses_log2: bits });
+    }
+
+    return candidates.into_iter().min_by(|a, b| a.guesses_log2.partial_cmp(&b.guesses_log2).unwrap());
+}
+
+/// Estimates how many guesses an attacker needs to find `password`, by splitting it into the
+/// parts that are easiest to guess: dictionary words (from common passwords and the bundled
+/// diceware list), keyboard walks, dates, repeats and sequences. Whatever is left is brute forced.
+pub fn estimate(password: &str) -> Strength {
+    let chars: Vec<char> = password.chars().collect();
+    let n = chars.len();
+
+    // best[j] is the cheapest way to guess the first j characters, and the matches used
+    let mut best: Vec<(f64, Vec<Match>)> = vec![(0.0, vec![])];
+    for j in 1..=n {
+        let mut candidate: Option<(f64, Vec<Match>)> = None;
+        for i in j.saturating_sub(MAX_PATTERN_LENGTH)..j {
+            let m = if j - i == 1 {
+                Some(Match { pattern: Pattern::BruteForce, token: chars[i].to_string(), guesses_log2: cardinality(chars[i]).log2() })
+            } else {
+                best_pattern(&chars, i, j)
+            };
+            if let Some(m) = m {
+                let cost = best[i].0 + m.guesses_log2;
+                if candidate.as_ref().map_or(true, |(c, _)| cost < *c) {
+                    let mut matches = best[i].1.clone();
+                    matches.push(m);
+                    candidate = Some((cost, matches));
+                }
+            }
+        }
+        best.push(candidate.unwrap());
+    }
+
+    let (bits, raw_matches) = best.pop().unwrap();
+
+    // merge consecutive brute forced characters, so that the matches are easier to show
+    let mut matches: Vec<Match> = vec![];
+    for m in raw_matches {
+        match matches.last_mut() {
+            Some(last) if last.pattern == Pattern::BruteForce && m.pattern == Pattern::BruteForce => {
+                last.token.push_str(&m.token);
+                last.guesses_log2 += m.guesses_log2;
+            },
+            _ => matches.push(m),
+        }
+    }
+
+    let score = match bits {
+        b if b < 10.0 => 0,
+        b if b < 20.0 => 1,
+        b if b < 27.0 => 2,
+        b if b < 34.0 => 3,
+        _ => 4,
+    };
+
+    return Strength {
+        guesses_log2: bits,
+        score,
+        matches,
+    };
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,48 @@
+use super::*;
+
+#[test]
+fn common_passwords_are_very_weak() {
+    assert_eq!(estimate("password").score, 0);
+    assert_eq!(estimate("P4ssw0rd").score, 0);
+    assert_eq!(estimate("123456").score, 0);
+}
+
+#[test]
+fn patterns_are_detected() {
+    let patterns = |p: &str| estimate(p).matches.into_iter().map(|m| m.pattern).collect::<Vec<Pattern>>();
+
+    assert_eq!(patterns("sdfghjk"), vec![Pattern::KeyboardWalk]);
+    assert_eq!(patterns("zzzzzzzz"), vec![Pattern::Repeat]);
+    assert_eq!(patterns("31-12-1999"), vec![Pattern::Date]);
+    assert_eq!(patterns("lmnopq"), vec![Pattern::Sequence]);
+    assert_eq!(patterns("abacus"), vec![Pattern::Dictionary { word: "abacus".to_string() }]);
+}
+
+#[test]
+fn diceware_words_give_their_entropy() {
+    let strength = estimate("abacusabdomenabide");
+
+    assert_eq!(strength.matches.len(), 3);
+    assert!((strength.guesses_log2 - 3.0 * 7776f64.log2()).abs() < 0.001);
+    assert_eq!(strength.score, 4);
+}
+
+#[test]
+fn random_passwords_are_strong() {
+    let strength = estimate("x8#Kq2!vR9$m");
+
+    assert_eq!(strength.score, 4);
+    assert_eq!(strength.crack_time_display(Attack::OfflineSlowHash), "centuries");
+}
+
+#[test]
+fn crack_time_display() {
+    let strength = Strength {
+        guesses_log2: 1.0 + (7200f64 * 10.0).log2(),
+        score: 2,
+        matches: vec![],
+    };
+
+    assert_eq!(strength.crack_time_display(Attack::Online), "2 hours");
+    assert_eq!(strength.crack_time_display(Attack::OfflineFastHash), "less than a second");
+}
@@ -0,0 +1,29 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use tar::Archive;
+
+/// Returns the path of a file in `testres`.
+pub(crate) fn testres(name: &str) -> PathBuf {
+    let mut base_path: PathBuf = std::env::current_exe().unwrap();
+    base_path.pop();
+    base_path.pop();
+    base_path.pop();
+    base_path.pop();
+    base_path.push("testres");
+    base_path.push(name);
+
+    base_path
+}
+
+/// Unpacks one of the archives in `testres` into `target` and returns the path of the store
+/// inside it.
+pub(crate) fn unpack_tar_gz(target: &Path, tar_gz_name: &str) -> std::io::Result<PathBuf> {
+    let tar_gz = File::open(testres(tar_gz_name))?;
+    let tar = GzDecoder::new(tar_gz);
+    let mut archive = Archive::new(tar);
+    archive.unpack(target)?;
+
+    Ok(target.join(tar_gz_name.trim_end_matches(".tar.gz")))
+}
@@ -1,20 +1,244 @@
 extern crate rand;
 
-use rand::distributions::{Distribution, Uniform};
+use std::fs;
+use std::path;
 
-/// Generate a random password, consisting of `number_of_words` words.
-pub fn generate_password(number_of_words: isize) -> String {
-    let mut rng = rand::thread_rng();
-    let die = Uniform::from(0..7776);
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use zeroize::Zeroize;
+
+use crate::pass::Error;
+use crate::secret::SecretString;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!#$%&*+-=?@^_~";
+
+/// A list of words to build passphrases from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WordList {
+    /// The name of the list, shown when selecting a list.
+    pub name: String,
+    words: Vec<String>,
+}
+
+impl WordList {
+    /// The long word list from EFF, with 7776 words.
+    pub fn eff_large() -> WordList {
+        WordList {
+            name: "EFF large".to_string(),
+            words: WORDS.iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    /// The word lists that are bundled with ripasso. Only the English long EFF list is bundled,
+    /// the short EFF lists and lists in other languages can be added as files, see `available`.
+    pub fn bundled() -> Vec<WordList> {
+        vec![WordList::eff_large()]
+    }
+
+    /// Parses a word list, either with one word per line or in the diceware format where
+    /// every line starts with the dice rolls. Empty lines, lines starting with `#` and
+    /// duplicate words are ignored, as duplicates would lower the entropy.
+    pub fn parse(name: &str, contents: &str) -> Result<WordList> {
+        let mut words: Vec<String> = vec![];
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let word = line.split_whitespace().last().unwrap().to_string();
+            if !words.contains(&word) {
+                words.push(word);
+            }
+        }
+
+        if words.len() < 2 {
+            return Err(Error::Generic("a word list must contain at least two different words"));
+        }
+
+        return Ok(WordList {
+            name: name.to_string(),
+            words,
+        });
+    }
+
+    /// Reads a word list from a file, named after the file.
+    pub fn from_file(path: &path::Path) -> Result<WordList> {
+        let contents = fs::read_to_string(path)?;
+        let name = path.file_stem().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
 
-    let mut rand_words = vec![];
-    for _ in 0..number_of_words {
-        rand_words.push(WORDS[die.sample(&mut rng)]);
+        return WordList::parse(&name, &contents);
     }
-    return rand_words.join(" ");
+
+    /// The bundled word lists, followed by the user supplied lists in the `wordlists` folder of
+    /// the ripasso configuration directory, for example `~/.config/ripasso/wordlists/` on Linux.
+    /// The files from EFF can be used as they are. Files that can't be read are skipped.
+    pub fn available() -> Vec<WordList> {
+        let mut lists = WordList::bundled();
+
+        if let Some(config) = dirs::config_dir() {
+            if let Ok(files) = fs::read_dir(config.join("ripasso").join("wordlists")) {
+                let mut paths: Vec<path::PathBuf> = files.filter_map(|f| f.ok()).map(|f| f.path()).collect();
+                paths.sort();
+                for path in paths {
+                    match WordList::from_file(&path) {
+                        Ok(list) => lists.push(list),
+                        Err(err) => warn!("skipping word list {}: {}", path.display(), err),
+                    }
+                }
+            }
+        }
+
+        return lists;
+    }
+
+    /// The number of words in the list.
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Returns true if the list doesn't contain any words.
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// The entropy in bits that a single word from this list gives.
+    pub fn bits_per_word(&self) -> f64 {
+        (self.words.len() as f64).log2()
+    }
+}
+
+/// How the words of a passphrase should be capitalised.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Capitalisation {
+    /// Keep the words as they are in the list.
+    None,
+    /// Upper case the first letter of every word.
+    First,
+    /// Upper case every letter.
+    All,
+    /// Upper case the first letter of each word with a 50% chance, which adds a bit of entropy
+    /// per word.
+    Random,
 }
 
-const WORDS: &'static [&'static str] = &["abacus", "abdomen", "abdominal", "abide", "abiding", "ability",
+/// The options for generating a passphrase.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassphraseOptions {
+    /// The number of words.
+    pub words: usize,
+    /// The list to pick the words from.
+    pub list: WordList,
+    /// The text put between the words.
+    pub separator: String,
+    /// How the words are capitalised.
+    pub capitalisation: Capitalisation,
+    /// Add a random digit to the end of a random word.
+    pub digit: bool,
+    /// Add a random symbol to the end of a random word.
+    pub symbol: bool,
+}
+
+impl Default for PassphraseOptions {
+    fn default() -> PassphraseOptions {
+        PassphraseOptions {
+            words: 6,
+            list: WordList::eff_large(),
+            separator: " ".to_string(),
+            capitalisation: Capitalisation::None,
+            digit: false,
+            symbol: false,
+        }
+    }
+}
+
+/// A generated passphrase.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Passphrase {
+    /// The passphrase.
+    pub passphrase: SecretString,
+    /// The entropy of the passphrase in bits, assuming that the attacker knows the options it
+    /// was generated with.
+    pub entropy: f64,
+}
+
+fn capitalise_first(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn add_random_char<R: Rng>(words: &mut Vec<String>, chars: &str, rng: &mut R) -> f64 {
+    let chars: Vec<char> = chars.chars().collect();
+    let i = rng.gen_range(0, words.len());
+    words[i].push(*chars.choose(rng).unwrap());
+
+    return (chars.len() as f64).log2() + (words.len() as f64).log2();
+}
+
+/// Generate a random passphrase according to `options`, using the random number generator of
+/// the operating system.
+pub fn generate_passphrase(options: &PassphraseOptions) -> Result<Passphrase> {
+    if options.words == 0 {
+        return Err(Error::Generic("a passphrase needs at least one word"));
+    }
+    if options.list.len() < 2 {
+        return Err(Error::Generic("a word list must contain at least two different words"));
+    }
+
+    let mut rng = OsRng;
+    let mut entropy = options.words as f64 * options.list.bits_per_word();
+
+    let mut words = vec![];
+    for _ in 0..options.words {
+        let word = options.list.words.choose(&mut rng).unwrap();
+        words.push(match options.capitalisation {
+            Capitalisation::None => word.clone(),
+            Capitalisation::First => capitalise_first(word),
+            Capitalisation::All => word.to_uppercase(),
+            Capitalisation::Random => {
+                entropy += 1.0;
+                if rng.gen() { capitalise_first(word) } else { word.clone() }
+            },
+        });
+    }
+
+    if options.digit {
+        entropy += add_random_char(&mut words, DIGITS, &mut rng);
+    }
+    if options.symbol {
+        entropy += add_random_char(&mut words, SYMBOLS, &mut rng);
+    }
+
+    let passphrase = SecretString::new(words.join(&options.separator));
+    words.zeroize();
+
+    return Ok(Passphrase {
+        passphrase,
+        entropy,
+    });
+}
+
+/// Generate a random password, consisting of `number_of_words` words.
+pub fn generate_password(number_of_words: isize) -> SecretString {
+    if number_of_words < 1 {
+        return SecretString::default();
+    }
+
+    let options = PassphraseOptions {
+        words: number_of_words as usize,
+        ..PassphraseOptions::default()
+    };
+
+    return generate_passphrase(&options).unwrap().passphrase;
+}
+
+pub(crate) const WORDS: &'static [&'static str] = &["abacus", "abdomen", "abdominal", "abide", "abiding", "ability",
                  "ablaze", "able", "abnormal", "abrasion", "abrasive", "abreast", "abridge",
                  "abroad", "abruptly", "absence", "absentee", "absently", "absinthe", "absolute",
                  "absolve", "abstain", "abstract", "absurd", "accent", "acclaim", "acclimate",
@@ -1034,3 +1258,4 @@ const WORDS: &'static [&'static str] = &["abacus", "abdomen", "abdominal", "abid
 
 #[cfg(test)]
 mod test;
+
@@ -13,3 +13,44 @@ fn generate_long_enough_passwords() {
 
     assert!(pass.len() > 10);
 }
+
+#[test]
+fn bundled_lists_have_the_expected_entropy() {
+    assert_eq!(WordList::eff_large().len(), 7776);
+    assert!((WordList::eff_large().bits_per_word() - 12.925).abs() < 0.001);
+    assert_eq!(WordList::bundled(), vec![WordList::eff_large()]);
+}
+
+#[test]
+fn parse_diceware_and_plain_lists() {
+    let diceware = WordList::parse("diceware", "# a comment\n11111\tabacus\n11112\tabdomen\n\n11113 abide\n").unwrap();
+    let plain = WordList::parse("plain", "apa\nbepa\napa\n").unwrap();
+    let four_dice = WordList::parse("eff_short_wordlist_1", "1111\tacid\n1112\tacorn\n").unwrap();
+
+    assert_eq!(diceware.len(), 3);
+    assert_eq!(four_dice.len(), 2);
+    assert_eq!(plain.len(), 2);
+    assert_eq!(WordList::parse("single", "apa\napa\n").is_err(), true);
+}
+
+#[test]
+fn passphrase_with_separator_capitalisation_and_extras() {
+    let options = PassphraseOptions {
+        words: 4,
+        list: WordList::parse("test", "apa\nbepa\ncepa\ndepa\n").unwrap(),
+        separator: ".".to_string(),
+        capitalisation: Capitalisation::First,
+        digit: true,
+        symbol: true,
+    };
+
+    let passphrase = generate_passphrase(&options).unwrap();
+
+    let words: Vec<&str> = passphrase.passphrase.split('.').collect();
+    assert_eq!(words.len(), 4);
+    assert_eq!(words.iter().all(|w| w.chars().next().unwrap().is_uppercase()), true);
+    assert_eq!(passphrase.passphrase.chars().any(|c| c.is_ascii_digit()), true);
+    assert_eq!(passphrase.passphrase.chars().any(|c| SYMBOLS.contains(c)), true);
+    // 4 words * 2 bits + digit (log2(10) + 2 bits position) + symbol (log2(14) + 2 bits position)
+    assert!((passphrase.entropy - (8.0 + 10f64.log2() + 2.0 + 14f64.log2() + 2.0)).abs() < 0.001);
+}